@@ -13,6 +13,12 @@ pub struct NostrClientCore {
     pub signer: Option<CustomSigner>,
 }
 
+impl Default for NostrClientCore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NostrClientCore {
     pub fn new() -> Self {
         Self {
@@ -167,3 +173,41 @@ impl NostrClientCore {
         Ok(format!("Nostr {}", BASE64.encode(event.as_json())))
     }
 }
+
+/// Check a game config's signature was made by the server for this session, returning the
+/// signed config JSON to play with
+pub fn verify_game_config(
+    signature: &str,
+    server_pubkey: &str,
+    session_id: &str,
+) -> Result<String, NostrError> {
+    let server_pubkey = PublicKey::parse(server_pubkey)?;
+    let event = Event::from_json(signature)
+        .map_err(|e| NostrError::InvalidSignature(format!("Invalid signature event: {}", e)))?;
+
+    event
+        .verify()
+        .map_err(|e| NostrError::InvalidSignature(e.to_string()))?;
+    if event.pubkey != server_pubkey {
+        return Err(NostrError::InvalidSignature(
+            "Config was not signed by the server".into(),
+        ));
+    }
+    if event.kind != Kind::ApplicationSpecificData {
+        return Err(NostrError::InvalidSignature(
+            "Signature is not for a game config".into(),
+        ));
+    }
+
+    let signed_session = event
+        .tags
+        .find(TagKind::custom("session"))
+        .and_then(|tag| tag.content());
+    if signed_session != Some(session_id) {
+        return Err(NostrError::InvalidSignature(
+            "Config was signed for a different session".into(),
+        ));
+    }
+
+    Ok(event.content)
+}
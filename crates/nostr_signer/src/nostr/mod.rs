@@ -4,7 +4,7 @@ mod types;
 #[cfg(target_arch = "wasm32")]
 mod wasm;
 
-pub use core::NostrClientCore;
+pub use core::{verify_game_config, NostrClientCore};
 pub use types::{CustomSigner, SignerType};
 
 use thiserror::Error;
@@ -37,6 +37,8 @@ pub enum NostrError {
     SignerError(#[from] nostr_sdk::signer::SignerError),
     #[error("Event builder error: {0}")]
     EventBuilderError(#[from] nostr_sdk::event::builder::Error),
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(String),
     #[error("Browser signer error: {0}")]
     #[cfg(target_arch = "wasm32")]
     BrowserSigner(#[from] nostr_sdk::nips::nip07::Error),
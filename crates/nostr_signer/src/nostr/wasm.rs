@@ -1,4 +1,4 @@
-use super::core::NostrClientCore;
+use super::core::{verify_game_config, NostrClientCore};
 use super::SignerType;
 use nostr_sdk::{serde_json, JsonUtil, PublicKey, ToBech32, UnsignedEvent};
 use std::str::FromStr;
@@ -86,6 +86,19 @@ impl NostrClientWrapper {
             .map_err(|e| JsValue::from_str(&e.to_string()))
     }
 
+    /// Check a game config's signature against the server's public key, returning the
+    /// config JSON that was signed. Needs no signer, anonymous players can check theirs too
+    #[wasm_bindgen(js_name = "verifyGameConfig")]
+    pub fn verify_game_config(
+        &self,
+        signature: &str,
+        server_pubkey: &str,
+        session_id: &str,
+    ) -> Result<String, JsValue> {
+        verify_game_config(signature, server_pubkey, session_id)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
     #[wasm_bindgen(js_name = "getAuthHeader")]
     pub async fn get_auth_header(
         &self,
@@ -6,6 +6,7 @@ class AuthClient {
     this.nostrClient = null;
     this.sessionId = null;
     this.username = null;
+    this.serverPubkey = null;
   }
 
   async initialize() {
@@ -367,6 +368,36 @@ class AuthClient {
     }
   }
 
+  async getServerPubkey() {
+    if (!this.serverPubkey) {
+      const response = await fetch(`${this.apiBase}/api/v1/game/pubkey`);
+      if (!response.ok) {
+        throw new Error(`Error fetching server key: ${response.statusText}`);
+      }
+      this.serverPubkey = (await response.json()).pubkey;
+    }
+    return this.serverPubkey;
+  }
+
+  // Check a config was signed by the server for its session and play with what was signed
+  async verifyGameConfig(config) {
+    if (!config.signature) {
+      throw new Error("Game config is not signed");
+    }
+    if (typeof this.nostrClient.verifyGameConfig !== "function") {
+      console.warn("This build of the signer cannot check game configs");
+      return config;
+    }
+
+    const serverPubkey = await this.getServerPubkey();
+    const signed = this.nostrClient.verifyGameConfig(
+      config.signature,
+      serverPubkey,
+      config.sessionId,
+    );
+    return { ...JSON.parse(signed), signature: config.signature };
+  }
+
   isLoggedIn() {
     return !!this.sessionId;
   }
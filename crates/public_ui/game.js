@@ -344,27 +344,34 @@ function initializeElements() {
 // Fetch game configuration from server
 async function fetchGameConfig() {
   try {
-    // Only attempt to fetch config if we have a session
-    if (!window.gameAuth.isLoggedIn()) {
-      throw new Error("Not logged in");
-    }
-
     const currentSessionId = sessionId;
+    const loggedIn = window.gameAuth.isLoggedIn();
 
-    let url = `${API_BASE}/api/v1/game/config`;
+    // Players who are not logged in can still play practice games
+    let url = loggedIn
+      ? `${API_BASE}/api/v1/game/config`
+      : `${API_BASE}/api/v1/game/practice/config`;
     if (currentSessionId) {
-      url += `?session_id=${currentSessionId}`;
+      // The next config's difficulty follows how far the game has got
+      url += `?session_id=${currentSessionId}&level=${gameState.level}&score=${gameState.score}`;
     }
 
     console.log("Fetching game config with session ID:", currentSessionId);
-    const response = await window.gameAuth.get(url);
+    const response = loggedIn
+      ? await window.gameAuth.get(url)
+      : await fetch(url);
 
     if (!response.ok) {
       throw new Error(`Error fetching game config: ${response.statusText}`);
     }
 
-    const config = await response.json();
+    const config = await window.gameAuth.verifyGameConfig(
+      await response.json(),
+    );
     console.log("Received game config:", config);
+    if (config.sessionId) {
+      sessionId = config.sessionId;
+    }
     return config;
   } catch (error) {
     console.error("Failed to fetch game config:", error);
@@ -396,7 +403,7 @@ async function startNewSession() {
       console.log("Updated session ID to:", sessionId);
     }
 
-    return data.config;
+    return await window.gameAuth.verifyGameConfig(data.config);
   } catch (error) {
     console.error("Failed to start new session:", error);
     return null;
@@ -405,23 +412,38 @@ async function startNewSession() {
 
 // Submit game score to server
 async function submitScore(score, level, gameTime) {
-  if (!window.gameAuth.isLoggedIn() || !sessionId) {
+  if (!sessionId) {
     console.warn("No session ID available, cannot submit score");
     return;
   }
 
+  const practice = Boolean(gameConfig && gameConfig.practice);
+  if (!practice && !window.gameAuth.isLoggedIn()) {
+    console.warn("Not logged in, cannot submit score");
+    return;
+  }
+
   try {
     console.log("Submitting score with session ID:", sessionId);
 
-    const response = await window.gameAuth.post(
-      `${API_BASE}/api/v1/game/score`,
-      {
-        score: score,
-        level: level,
-        play_time: gameTime,
-        session_id: sessionId,
-      },
-    );
+    // Practice scores are kept apart and never count towards prizes
+    const url = practice
+      ? `${API_BASE}/api/v1/game/practice/score`
+      : `${API_BASE}/api/v1/game/score`;
+    const body = {
+      score: score,
+      level: level,
+      play_time: gameTime,
+      session_id: sessionId,
+    };
+
+    const response = window.gameAuth.isLoggedIn()
+      ? await window.gameAuth.post(url, body)
+      : await fetch(url, {
+          method: "POST",
+          headers: { "Content-Type": "application/json" },
+          body: JSON.stringify(body),
+        });
 
     if (!response.ok) {
       throw new Error(`Error submitting score: ${response.statusText}`);
@@ -0,0 +1,3 @@
+-- Set when a player deletes their account, the row is kept (anonymised) so
+-- historical scores and prize calculations still line up
+ALTER TABLE users ADD COLUMN deleted_at TEXT;
@@ -0,0 +1,17 @@
+DROP INDEX IF EXISTS idx_game_payments_competition;
+
+DROP INDEX IF EXISTS idx_scores_competition;
+
+DROP INDEX IF EXISTS idx_competitions_status_end;
+
+DROP INDEX IF EXISTS idx_competitions_kind_start;
+
+ALTER TABLE prize_payouts DROP COLUMN competition_id;
+
+ALTER TABLE game_payments DROP COLUMN competition_id;
+
+ALTER TABLE scores DROP COLUMN competition_id;
+
+ALTER TABLE game_sessions DROP COLUMN competition_id;
+
+DROP TABLE IF EXISTS competitions;
@@ -0,0 +1,94 @@
+CREATE TABLE IF NOT EXISTS competitions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL, -- 'daily'
+    start_time TEXT NOT NULL, -- RFC3339, UTC
+    end_time TEXT NOT NULL, -- RFC3339, UTC
+    entry_fee_sats INTEGER NOT NULL,
+    rake_percent INTEGER NOT NULL,
+    rules_version TEXT NOT NULL,
+    seed TEXT NOT NULL,
+    status TEXT NOT NULL, -- 'open', 'closed', 'settling', 'settled'
+    pot_sats INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL DEFAULT (datetime ('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime ('now'))
+);
+
+ALTER TABLE game_sessions ADD COLUMN competition_id INTEGER REFERENCES competitions (id);
+
+ALTER TABLE scores ADD COLUMN competition_id INTEGER REFERENCES competitions (id);
+
+ALTER TABLE game_payments ADD COLUMN competition_id INTEGER REFERENCES competitions (id);
+
+ALTER TABLE prize_payouts ADD COLUMN competition_id INTEGER REFERENCES competitions (id);
+
+-- Every day already played becomes a daily competition at the old 500 sat fee and 10% rake, so
+-- the day in progress keeps its pot and winner. Days count in UTC and payments count on the day
+-- they were paid, as the old daily task did. Days it awarded a prize for and anything older than
+-- yesterday are settled, the rest is left open for the settlement to pick up
+WITH days AS (
+    SELECT substr(created_at, 1, 10) AS day FROM scores
+    UNION
+    SELECT substr(COALESCE(paid_at, created_at), 1, 10) FROM game_payments
+    UNION
+    SELECT substr(start_time, 1, 10) FROM game_sessions
+)
+INSERT INTO competitions
+(name, kind, start_time, end_time, entry_fee_sats, rake_percent, rules_version, seed, status, pot_sats)
+SELECT
+    'Daily ' || day,
+    'daily',
+    day || 'T00:00:00Z',
+    date(day, '+1 day') || 'T00:00:00Z',
+    500,
+    10,
+    '1.0.0',
+    lower(hex(randomblob(16))),
+    CASE
+        WHEN EXISTS (SELECT 1 FROM prize_payouts p WHERE p.date = days.day) THEN 'settled'
+        WHEN day >= date('now', '-1 day') THEN 'open'
+        ELSE 'settled'
+    END,
+    COALESCE((
+        SELECT SUM(p.amount_sats) FROM game_payments p
+        WHERE p.status = 'paid' AND substr(COALESCE(p.paid_at, p.created_at), 1, 10) = days.day
+    ), 0)
+FROM days
+WHERE day IS NOT NULL;
+
+UPDATE game_sessions
+SET competition_id = (
+    SELECT id FROM competitions
+    WHERE kind = 'daily' AND start_time = substr(game_sessions.start_time, 1, 10) || 'T00:00:00Z'
+);
+
+UPDATE scores
+SET competition_id = (
+    SELECT id FROM competitions
+    WHERE kind = 'daily' AND start_time = substr(scores.created_at, 1, 10) || 'T00:00:00Z'
+);
+
+UPDATE game_payments
+SET competition_id = (
+    SELECT id FROM competitions
+    WHERE kind = 'daily'
+        AND start_time = substr(COALESCE(game_payments.paid_at, game_payments.created_at), 1, 10) || 'T00:00:00Z'
+);
+
+UPDATE prize_payouts
+SET competition_id = (
+    SELECT id FROM competitions
+    WHERE kind = 'daily' AND start_time = prize_payouts.date || 'T00:00:00Z'
+);
+
+-- Only one competition of a kind can start at a given time
+CREATE UNIQUE INDEX idx_competitions_kind_start ON competitions (kind, start_time);
+
+-- Index for finding competitions that need settling
+CREATE INDEX idx_competitions_status_end ON competitions (status, end_time);
+
+-- Index for finding a competition's high scores
+CREATE INDEX idx_scores_competition ON scores (competition_id, score);
+
+-- Index for counting a competition's paid entries
+CREATE INDEX idx_game_payments_competition ON game_payments (competition_id, status);
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_prize_payouts_user_date;
+
+DROP INDEX IF EXISTS idx_prize_payouts_competition;
+
+CREATE UNIQUE INDEX idx_prize_payouts_user_date ON prize_payouts (user_id, date);
@@ -0,0 +1,7 @@
+-- The daily competition and recurring tournaments can share a date, so a
+-- competition has one winner instead of a user having one prize per date
+DROP INDEX IF EXISTS idx_prize_payouts_user_date;
+
+CREATE UNIQUE INDEX idx_prize_payouts_competition ON prize_payouts (competition_id);
+
+CREATE INDEX idx_prize_payouts_user_date ON prize_payouts (user_id, date);
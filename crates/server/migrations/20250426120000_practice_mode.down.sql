@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_practice_scores_user;
+
+DROP TABLE IF EXISTS practice_scores;
+
+DROP TABLE IF EXISTS practice_sessions;
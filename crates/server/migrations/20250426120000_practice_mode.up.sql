@@ -0,0 +1,26 @@
+-- Practice sessions are free, can be played without an account and never enter a competition
+CREATE TABLE IF NOT EXISTS practice_sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    session_id TEXT NOT NULL UNIQUE,
+    user_id INTEGER, -- NULL for anonymous players
+    start_time TEXT NOT NULL,
+    last_active TEXT NOT NULL,
+    difficulty_factor REAL NOT NULL DEFAULT 1.0,
+    FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+-- Kept apart from scores so practice games never count towards prizes or rankings
+CREATE TABLE IF NOT EXISTS practice_scores (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    practice_session_id INTEGER NOT NULL,
+    user_id INTEGER,
+    score INTEGER NOT NULL,
+    level INTEGER NOT NULL,
+    play_time INTEGER NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime ('now')),
+    FOREIGN KEY (practice_session_id) REFERENCES practice_sessions (id),
+    FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+-- Index for finding a player's practice history
+CREATE INDEX idx_practice_scores_user ON practice_scores (user_id);
@@ -0,0 +1,9 @@
+DROP INDEX IF EXISTS idx_game_credits_session;
+
+DROP INDEX IF EXISTS idx_game_credits_payment;
+
+DROP INDEX IF EXISTS idx_game_credits_user_competition;
+
+DROP TABLE IF EXISTS game_credits;
+
+ALTER TABLE game_payments DROP COLUMN credits;
@@ -0,0 +1,24 @@
+-- Number of games a payment buys
+ALTER TABLE game_payments ADD COLUMN credits INTEGER NOT NULL DEFAULT 1;
+
+-- Ledger of game credits, a player's balance is the sum of their deltas in a competition
+CREATE TABLE IF NOT EXISTS game_credits (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    user_id INTEGER NOT NULL,
+    competition_id INTEGER NOT NULL,
+    delta INTEGER NOT NULL, -- credits bought are positive, games played are negative
+    reason TEXT NOT NULL, -- 'purchase', 'session'
+    payment_id TEXT, -- set for purchases
+    session_id TEXT, -- set for games played
+    created_at TEXT NOT NULL DEFAULT (datetime ('now')),
+    FOREIGN KEY (user_id) REFERENCES users (id),
+    FOREIGN KEY (competition_id) REFERENCES competitions (id)
+);
+
+-- Index for working out a player's balance
+CREATE INDEX idx_game_credits_user_competition ON game_credits (user_id, competition_id);
+
+-- A payment mints its credits once and a session uses exactly one credit
+CREATE UNIQUE INDEX idx_game_credits_payment ON game_credits (payment_id);
+
+CREATE UNIQUE INDEX idx_game_credits_session ON game_credits (session_id);
@@ -0,0 +1,11 @@
+DROP INDEX IF EXISTS idx_ledger_entries_transaction;
+
+DROP INDEX IF EXISTS idx_ledger_entries_account;
+
+DROP INDEX IF EXISTS idx_ledger_transactions_kind_reference;
+
+DROP TABLE IF EXISTS ledger_entries;
+
+DROP TABLE IF EXISTS ledger_transactions;
+
+DROP TABLE IF EXISTS ledger_accounts;
@@ -0,0 +1,120 @@
+CREATE TABLE IF NOT EXISTS ledger_accounts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    code TEXT NOT NULL UNIQUE, -- 'lightning', 'house', 'fees', 'equity', 'pot:<competition_id>', 'user:<user_id>'
+    kind TEXT NOT NULL, -- 'asset', 'liability', 'revenue', 'expense', 'equity'
+    created_at TEXT NOT NULL DEFAULT (datetime ('now'))
+);
+
+CREATE TABLE IF NOT EXISTS ledger_transactions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    kind TEXT NOT NULL, -- 'entry_fee', 'prize', 'payout', 'routing_fee', 'refund'
+    reference TEXT NOT NULL, -- id of the payment, competition or prize that caused it
+    description TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime ('now'))
+);
+
+CREATE TABLE IF NOT EXISTS ledger_entries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    transaction_id INTEGER NOT NULL,
+    account_id INTEGER NOT NULL,
+    amount_sats INTEGER NOT NULL, -- debits are positive, credits are negative
+    created_at TEXT NOT NULL DEFAULT (datetime ('now')),
+    FOREIGN KEY (transaction_id) REFERENCES ledger_transactions (id),
+    FOREIGN KEY (account_id) REFERENCES ledger_accounts (id)
+);
+
+-- Each money movement is only ever recorded once
+CREATE UNIQUE INDEX idx_ledger_transactions_kind_reference ON ledger_transactions (kind, reference);
+
+-- Index for account balances
+CREATE INDEX idx_ledger_entries_account ON ledger_entries (account_id);
+
+-- Index for checking transactions balance
+CREATE INDEX idx_ledger_entries_transaction ON ledger_entries (transaction_id);
+
+-- Opening balances: entry fees already received sit in the pot of their competition, prizes
+-- already awarded moved them on to the winner and any prize already paid left the wallet.
+-- Whatever else the wallet holds is posted to equity by the first reconciliation
+INSERT INTO ledger_accounts (code, kind) VALUES ('lightning', 'asset');
+
+INSERT INTO ledger_accounts (code, kind)
+SELECT DISTINCT 'pot:' || competition_id, 'liability'
+FROM game_payments
+WHERE status = 'paid' AND competition_id IS NOT NULL;
+
+INSERT INTO ledger_transactions (kind, reference, description, created_at)
+SELECT 'entry_fee', payment_id, 'Opening balance', COALESCE(paid_at, updated_at)
+FROM game_payments
+WHERE status = 'paid' AND competition_id IS NOT NULL;
+
+INSERT INTO ledger_entries (transaction_id, account_id, amount_sats, created_at)
+SELECT t.id, a.id, p.amount_sats, t.created_at
+FROM ledger_transactions t
+JOIN game_payments p ON p.payment_id = t.reference
+JOIN ledger_accounts a ON a.code = 'lightning'
+WHERE t.kind = 'entry_fee';
+
+INSERT INTO ledger_entries (transaction_id, account_id, amount_sats, created_at)
+SELECT t.id, a.id, -p.amount_sats, t.created_at
+FROM ledger_transactions t
+JOIN game_payments p ON p.payment_id = t.reference
+JOIN ledger_accounts a ON a.code = 'pot:' || p.competition_id
+WHERE t.kind = 'entry_fee';
+
+INSERT OR IGNORE INTO ledger_accounts (code, kind) VALUES ('house', 'revenue');
+
+INSERT OR IGNORE INTO ledger_accounts (code, kind)
+SELECT DISTINCT 'pot:' || competition_id, 'liability'
+FROM prize_payouts
+WHERE competition_id IS NOT NULL;
+
+INSERT OR IGNORE INTO ledger_accounts (code, kind)
+SELECT DISTINCT 'user:' || user_id, 'liability'
+FROM prize_payouts
+WHERE competition_id IS NOT NULL;
+
+INSERT INTO ledger_transactions (kind, reference, description, created_at)
+SELECT 'prize', CAST(competition_id AS TEXT), 'Opening balance', created_at
+FROM prize_payouts
+WHERE competition_id IS NOT NULL;
+
+INSERT INTO ledger_entries (transaction_id, account_id, amount_sats, created_at)
+SELECT t.id, a.id, c.pot_sats, t.created_at
+FROM ledger_transactions t
+JOIN competitions c ON CAST(c.id AS TEXT) = t.reference
+JOIN ledger_accounts a ON a.code = 'pot:' || c.id
+WHERE t.kind = 'prize';
+
+INSERT INTO ledger_entries (transaction_id, account_id, amount_sats, created_at)
+SELECT t.id, a.id, -p.amount_sats, t.created_at
+FROM ledger_transactions t
+JOIN prize_payouts p ON CAST(p.competition_id AS TEXT) = t.reference
+JOIN ledger_accounts a ON a.code = 'user:' || p.user_id
+WHERE t.kind = 'prize';
+
+INSERT INTO ledger_entries (transaction_id, account_id, amount_sats, created_at)
+SELECT t.id, a.id, -(c.pot_sats - p.amount_sats), t.created_at
+FROM ledger_transactions t
+JOIN prize_payouts p ON CAST(p.competition_id AS TEXT) = t.reference
+JOIN competitions c ON c.id = p.competition_id
+JOIN ledger_accounts a ON a.code = 'house'
+WHERE t.kind = 'prize' AND c.pot_sats > p.amount_sats;
+
+INSERT INTO ledger_transactions (kind, reference, description, created_at)
+SELECT 'payout', COALESCE(payment_id, 'prize:' || id), 'Opening balance', COALESCE(paid_at, updated_at)
+FROM prize_payouts
+WHERE status = 'paid' AND competition_id IS NOT NULL;
+
+INSERT INTO ledger_entries (transaction_id, account_id, amount_sats, created_at)
+SELECT t.id, a.id, p.amount_sats, t.created_at
+FROM ledger_transactions t
+JOIN prize_payouts p ON COALESCE(p.payment_id, 'prize:' || p.id) = t.reference
+JOIN ledger_accounts a ON a.code = 'user:' || p.user_id
+WHERE t.kind = 'payout' AND p.status = 'paid';
+
+INSERT INTO ledger_entries (transaction_id, account_id, amount_sats, created_at)
+SELECT t.id, a.id, -p.amount_sats, t.created_at
+FROM ledger_transactions t
+JOIN prize_payouts p ON COALESCE(p.payment_id, 'prize:' || p.id) = t.reference
+JOIN ledger_accounts a ON a.code = 'lightning'
+WHERE t.kind = 'payout' AND p.status = 'paid';
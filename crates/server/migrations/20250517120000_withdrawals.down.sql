@@ -0,0 +1,5 @@
+DROP VIEW IF EXISTS user_balances;
+
+DROP INDEX IF EXISTS idx_withdrawals_user;
+
+DROP TABLE IF EXISTS withdrawals;
@@ -0,0 +1,33 @@
+CREATE TABLE IF NOT EXISTS withdrawals (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    user_id INTEGER NOT NULL,
+    amount_sats INTEGER NOT NULL, -- sats sent to the player
+    fee_sats INTEGER NOT NULL, -- withdrawal fee kept by the house
+    destination TEXT NOT NULL, -- 'bolt11' or the Lightning Address paid
+    invoice TEXT NOT NULL,
+    payment_id TEXT,
+    status TEXT NOT NULL, -- 'invoice_received', 'sending', 'paid', 'failed', 'needs_attention'
+    created_at TEXT NOT NULL DEFAULT (datetime ('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime ('now')),
+    paid_at TEXT,
+    FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+-- Index for listing a user's withdrawals
+CREATE INDEX idx_withdrawals_user ON withdrawals (user_id);
+
+-- What each player can spend or withdraw: their ledger account, less the prizes claimed to an
+-- invoice, which are held back until their payout settles
+CREATE VIEW IF NOT EXISTS user_balances AS
+SELECT u.id as user_id, (
+    SELECT COALESCE(-SUM(e.amount_sats), 0)
+    FROM ledger_entries e
+    JOIN ledger_accounts a ON a.id = e.account_id
+    WHERE a.code = 'user:' || u.id
+) - (
+    SELECT COALESCE(SUM(p.amount_sats), 0)
+    FROM prize_payouts p
+    WHERE p.user_id = u.id
+    AND p.status IN ('invoice_received', 'sending', 'needs_attention')
+) as balance_sats
+FROM users u;
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_payout_jobs_next_attempt;
+
+DROP TABLE IF EXISTS payout_jobs;
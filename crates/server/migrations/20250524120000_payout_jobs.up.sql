@@ -0,0 +1,19 @@
+-- Prize payouts move through 'pending' -> 'invoice_received' -> 'sending' -> 'paid' | 'failed' | 'needs_attention',
+-- or settle as 'credited' once their winnings are spent from the balance
+CREATE TABLE IF NOT EXISTS payout_jobs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    prize_payout_id INTEGER NOT NULL UNIQUE, -- a prize only ever has one job, so it is never paid twice
+    invoice TEXT NOT NULL,
+    amount_sats INTEGER NOT NULL,
+    payment_id TEXT, -- id of the payment currently or last sent to the Lightning backend
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TEXT NOT NULL,
+    last_error TEXT,
+    sent_at TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (prize_payout_id) REFERENCES prize_payouts (id)
+);
+
+-- Index for finding due jobs
+CREATE INDEX idx_payout_jobs_next_attempt ON payout_jobs (next_attempt_at);
@@ -0,0 +1,34 @@
+CREATE TABLE IF NOT EXISTS payout_jobs_old (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    prize_payout_id INTEGER NOT NULL UNIQUE,
+    invoice TEXT NOT NULL,
+    amount_sats INTEGER NOT NULL,
+    payment_id TEXT,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TEXT NOT NULL,
+    last_error TEXT,
+    sent_at TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (prize_payout_id) REFERENCES prize_payouts (id)
+);
+
+INSERT INTO payout_jobs_old
+(id, prize_payout_id, invoice, amount_sats, payment_id, attempts, next_attempt_at, last_error, sent_at, created_at, updated_at)
+SELECT id, target_id, invoice, amount_sats, payment_id, attempts, next_attempt_at, last_error, sent_at, created_at, updated_at
+FROM payout_jobs
+WHERE kind = 'prize';
+
+DROP INDEX IF EXISTS idx_payout_jobs_next_attempt;
+
+DROP INDEX IF EXISTS idx_payout_jobs_kind_target;
+
+DROP TABLE payout_jobs;
+
+ALTER TABLE payout_jobs_old RENAME TO payout_jobs;
+
+CREATE INDEX idx_payout_jobs_next_attempt ON payout_jobs (next_attempt_at);
+
+DROP INDEX IF EXISTS idx_refunds_user_competition;
+
+DROP TABLE IF EXISTS refunds;
@@ -0,0 +1,53 @@
+-- Entry fees owed back to players of a voided competition
+CREATE TABLE IF NOT EXISTS refunds (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    user_id INTEGER NOT NULL,
+    competition_id INTEGER NOT NULL,
+    amount_sats INTEGER NOT NULL,
+    reason TEXT NOT NULL, -- why the competition was voided
+    destination TEXT, -- 'balance', 'bolt11' or the Lightning Address paid
+    payment_request TEXT,
+    payment_id TEXT,
+    status TEXT NOT NULL, -- 'pending', 'credited', 'invoice_received', 'sending', 'paid', 'failed', 'needs_attention'
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    paid_at TEXT,
+    FOREIGN KEY (user_id) REFERENCES users (id),
+    FOREIGN KEY (competition_id) REFERENCES competitions (id)
+);
+
+-- A player gets one refund per competition, however many entries they bought
+CREATE UNIQUE INDEX idx_refunds_user_competition ON refunds (user_id, competition_id);
+
+-- Payout jobs now pay refunds as well as prizes
+CREATE TABLE IF NOT EXISTS payout_jobs_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    kind TEXT NOT NULL, -- 'prize', 'refund' or 'withdrawal'
+    target_id INTEGER NOT NULL, -- id of the prize payout, refund or withdrawal being paid
+    invoice TEXT NOT NULL,
+    amount_sats INTEGER NOT NULL,
+    payment_id TEXT,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TEXT NOT NULL,
+    last_error TEXT,
+    sent_at TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+INSERT INTO payout_jobs_new
+(id, kind, target_id, invoice, amount_sats, payment_id, attempts, next_attempt_at, last_error, sent_at, created_at, updated_at)
+SELECT id, 'prize', prize_payout_id, invoice, amount_sats, payment_id, attempts, next_attempt_at, last_error, sent_at, created_at, updated_at
+FROM payout_jobs;
+
+DROP INDEX IF EXISTS idx_payout_jobs_next_attempt;
+
+DROP TABLE payout_jobs;
+
+ALTER TABLE payout_jobs_new RENAME TO payout_jobs;
+
+-- Anything paid out only ever has one job, so it is never paid twice
+CREATE UNIQUE INDEX idx_payout_jobs_kind_target ON payout_jobs (kind, target_id);
+
+-- Index for finding due jobs
+CREATE INDEX idx_payout_jobs_next_attempt ON payout_jobs (next_attempt_at);
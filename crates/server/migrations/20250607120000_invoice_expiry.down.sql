@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_game_payments_status_expires;
+
+ALTER TABLE game_payments DROP COLUMN expires_at;
@@ -0,0 +1,10 @@
+-- RFC3339, UTC, read from the invoice when it is created
+ALTER TABLE game_payments ADD COLUMN expires_at TEXT;
+
+-- Invoices created so far used the default expiry of an hour
+UPDATE game_payments
+SET expires_at = strftime('%Y-%m-%dT%H:%M:%SZ', substr(created_at, 1, 19), '+1 hour')
+WHERE expires_at IS NULL;
+
+-- Index for sweeping pending payments
+CREATE INDEX idx_game_payments_status_expires ON game_payments (status, expires_at);
@@ -0,0 +1,6 @@
+ALTER TABLE scores DROP COLUMN voided_by;
+ALTER TABLE scores DROP COLUMN void_reason;
+ALTER TABLE scores DROP COLUMN voided_at;
+
+DROP INDEX IF EXISTS idx_bans_active;
+DROP TABLE IF EXISTS bans;
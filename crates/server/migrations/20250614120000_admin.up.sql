@@ -0,0 +1,18 @@
+-- Pubkeys banned by an operator, a ban is lifted rather than deleted so the history stays
+CREATE TABLE IF NOT EXISTS bans (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    pubkey TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    banned_by TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    lifted_at TEXT,
+    lifted_by TEXT
+);
+
+-- A pubkey has at most one ban in force
+CREATE UNIQUE INDEX idx_bans_active ON bans (pubkey) WHERE lifted_at IS NULL;
+
+-- Scores an operator has thrown out no longer count towards any ranking
+ALTER TABLE scores ADD COLUMN voided_at TEXT;
+ALTER TABLE scores ADD COLUMN void_reason TEXT;
+ALTER TABLE scores ADD COLUMN voided_by TEXT;
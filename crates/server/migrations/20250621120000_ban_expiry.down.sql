@@ -0,0 +1,3 @@
+DROP VIEW IF EXISTS active_bans;
+
+ALTER TABLE bans DROP COLUMN expires_at;
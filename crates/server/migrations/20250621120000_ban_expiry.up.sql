@@ -0,0 +1,10 @@
+-- Temporary bans run out on their own, a ban without an expiry lasts until it is lifted
+ALTER TABLE bans ADD COLUMN expires_at TEXT;
+
+-- Bans in force right now. Expiry times are stored as RFC 3339 to the whole second, so they
+-- compare as text against the current time written the same way
+CREATE VIEW IF NOT EXISTS active_bans AS
+SELECT id, pubkey, reason, banned_by, created_at, expires_at, lifted_at, lifted_by
+FROM bans
+WHERE lifted_at IS NULL
+AND (expires_at IS NULL OR expires_at > strftime ('%Y-%m-%dT%H:%M:%SZ', 'now'));
@@ -0,0 +1,6 @@
+DROP INDEX IF EXISTS idx_scores_suspicion;
+DROP INDEX IF EXISTS idx_scores_session;
+
+ALTER TABLE scores DROP COLUMN suspicion_reasons;
+ALTER TABLE scores DROP COLUMN suspicion;
+ALTER TABLE scores DROP COLUMN session_id;
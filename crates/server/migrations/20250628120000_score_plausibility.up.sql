@@ -0,0 +1,11 @@
+-- Scores remember the session they were played in, so a session only ever gets one
+ALTER TABLE scores ADD COLUMN session_id TEXT;
+
+-- How suspicious the plausibility checks found a score, from 0 up to 1, and why
+ALTER TABLE scores ADD COLUMN suspicion REAL NOT NULL DEFAULT 0;
+ALTER TABLE scores ADD COLUMN suspicion_reasons TEXT;
+
+CREATE INDEX idx_scores_session ON scores (session_id);
+
+-- Index for the admin review of suspicious scores
+CREATE INDEX idx_scores_suspicion ON scores (suspicion);
@@ -0,0 +1,9 @@
+DROP INDEX IF EXISTS idx_game_sessions_status;
+
+DROP INDEX IF EXISTS idx_scores_session;
+CREATE INDEX idx_scores_session ON scores (session_id);
+
+ALTER TABLE scores DROP COLUMN reported_play_time;
+
+ALTER TABLE game_sessions DROP COLUMN ended_at;
+ALTER TABLE game_sessions DROP COLUMN status;
@@ -0,0 +1,29 @@
+-- Sessions move through 'created' -> 'active' -> 'finished' | 'abandoned' | 'expired'
+ALTER TABLE game_sessions ADD COLUMN status TEXT NOT NULL DEFAULT 'created';
+ALTER TABLE game_sessions ADD COLUMN ended_at TEXT;
+
+-- Sessions that already have a score are over, the sweeper expires the rest once idle
+UPDATE game_sessions
+SET status = 'finished', ended_at = last_active
+WHERE session_id IN (SELECT session_id FROM scores WHERE session_id IS NOT NULL);
+
+-- Scores keep the play time the server saw, the client's own count is kept for comparison
+ALTER TABLE scores ADD COLUMN reported_play_time INTEGER;
+
+-- A session only ever gets one score
+DROP INDEX IF EXISTS idx_scores_session;
+CREATE UNIQUE INDEX idx_scores_session ON scores (session_id) WHERE session_id IS NOT NULL;
+
+-- Index for finding idle sessions to expire
+CREATE INDEX idx_game_sessions_status ON game_sessions (status, last_active);
+
+-- Score times are stored as RFC 3339 from now on and compared as text with leaderboard windows,
+-- so the ones written before in the "2025-04-10 9:05:03.5 +00:00:00" display format, or by the
+-- column default, are rewritten to match. Both were always in UTC
+UPDATE scores
+SET created_at = (
+    SELECT substr(created_at, 1, 10) || 'T' || printf('%02d', substr(t, 1, instr(t, ':') - 1))
+        || substr(t, instr(t, ':')) || 'Z'
+    FROM (SELECT substr(created_at || ' ', 12, instr(substr(created_at || ' ', 12), ' ') - 1) as t)
+)
+WHERE created_at LIKE '____-__-__ %';
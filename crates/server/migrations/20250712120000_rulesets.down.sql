@@ -0,0 +1,9 @@
+DROP INDEX IF EXISTS idx_game_configs_session;
+
+ALTER TABLE game_configs DROP COLUMN ruleset_id;
+ALTER TABLE game_configs DROP COLUMN session_id;
+
+ALTER TABLE game_sessions DROP COLUMN ruleset_version;
+ALTER TABLE game_sessions DROP COLUMN ruleset_id;
+
+ALTER TABLE competitions DROP COLUMN ruleset_id;
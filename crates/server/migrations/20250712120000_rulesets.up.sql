@@ -0,0 +1,9 @@
+ALTER TABLE competitions ADD COLUMN ruleset_id TEXT NOT NULL DEFAULT 'classic';
+
+ALTER TABLE game_sessions ADD COLUMN ruleset_id TEXT NOT NULL DEFAULT 'classic';
+ALTER TABLE game_sessions ADD COLUMN ruleset_version TEXT NOT NULL DEFAULT '1.0.0';
+
+ALTER TABLE game_configs ADD COLUMN session_id TEXT;
+ALTER TABLE game_configs ADD COLUMN ruleset_id TEXT NOT NULL DEFAULT 'classic';
+
+CREATE INDEX idx_game_configs_session ON game_configs (session_id);
@@ -0,0 +1,2 @@
+ALTER TABLE game_configs DROP COLUMN config;
+ALTER TABLE game_configs DROP COLUMN difficulty_factor;
@@ -0,0 +1,2 @@
+ALTER TABLE game_configs ADD COLUMN difficulty_factor REAL NOT NULL DEFAULT 1.0;
+ALTER TABLE game_configs ADD COLUMN config TEXT;
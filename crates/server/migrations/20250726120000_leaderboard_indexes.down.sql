@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_scores_competition_ranking;
+DROP INDEX IF EXISTS idx_scores_user_best;
+DROP INDEX IF EXISTS idx_scores_ranking;
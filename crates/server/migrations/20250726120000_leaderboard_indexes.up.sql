@@ -0,0 +1,4 @@
+-- Ranking every counted score, and each player's best, in leaderboard order
+CREATE INDEX idx_scores_ranking ON scores (score DESC, id) WHERE voided_at IS NULL;
+CREATE INDEX idx_scores_user_best ON scores (user_id, score DESC, id) WHERE voided_at IS NULL;
+CREATE INDEX idx_scores_competition_ranking ON scores (competition_id, score DESC, id) WHERE voided_at IS NULL;
@@ -0,0 +1 @@
+ALTER TABLE prize_payouts DROP COLUMN payment_preimage;
@@ -0,0 +1 @@
+ALTER TABLE prize_payouts ADD COLUMN payment_preimage TEXT;
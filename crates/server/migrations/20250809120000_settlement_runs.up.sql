@@ -0,0 +1,17 @@
+-- One row per round of a competition series the scheduler has settled or tried to, so days
+-- missed while the server was down are caught up and a settled round is never redone
+CREATE TABLE IF NOT EXISTS settlement_runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    kind TEXT NOT NULL,
+    period_start TEXT NOT NULL,
+    period_end TEXT NOT NULL,
+    competition_id INTEGER, -- none if nobody played that round
+    status TEXT NOT NULL, -- 'running' -> 'completed' | 'failed'
+    outcome TEXT, -- 'settled', 'voided' or 'no_entries' once completed
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    started_at TEXT NOT NULL,
+    finished_at TEXT,
+    UNIQUE (kind, period_start),
+    FOREIGN KEY (competition_id) REFERENCES competitions (id)
+);
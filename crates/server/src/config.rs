@@ -1,7 +1,8 @@
 use anyhow::anyhow;
-use clap::{command, Parser};
+use clap::Parser;
 use fern::colors::{Color, ColoredLevelConfig};
 use log::LevelFilter;
+use nostr_sdk::PublicKey;
 use serde::{Deserialize, Serialize};
 use std::{
     env,
@@ -11,6 +12,8 @@ use std::{
 };
 use time::{format_description::well_known::Iso8601, OffsetDateTime};
 
+use crate::{DayBoundary, Schedule, CLASSIC_RULESET};
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
@@ -23,13 +26,23 @@ pub struct Cli {
     pub level: Option<String>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Settings {
     pub config: Option<String>,
     pub level: Option<String>,
     pub db_settings: DBSettings,
     pub api_settings: APISettings,
     pub ui_settings: UISettings,
+    #[serde(default)]
+    pub competition_settings: CompetitionSettings,
+    #[serde(default)]
+    pub wallet_settings: WalletSettings,
+    #[serde(default)]
+    pub payout_settings: PayoutSettings,
+    #[serde(default)]
+    pub admin_settings: AdminSettings,
+    #[serde(default)]
+    pub game_settings: GameSettings,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -80,7 +93,7 @@ impl Default for APISettings {
         APISettings {
             domain: String::from("127.0.0.1"),
             port: String::from("8900"),
-            private_key_file: String::from("./creds/private.key"),
+            private_key_file: String::from("./creds/private.pem"),
             voltage_api_key: String::from(""),
             voltage_api_url: String::from("https://voltageapi.com/v1/"),
             voltage_org_id: String::from(""),
@@ -90,16 +103,326 @@ impl Default for APISettings {
     }
 }
 
-impl Default for Settings {
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CompetitionSettings {
+    /// Sats charged to enter the daily competition
+    pub entry_fee_sats: i64,
+    /// Percentage of the pot kept by the house
+    pub rake_percent: i64,
+    /// Recurring tournaments that run alongside the daily competition
+    #[serde(default)]
+    pub tournaments: Vec<TournamentSettings>,
+    /// Numbers of games that can be bought at once, each priced off the entry fee
+    #[serde(default = "default_bundles")]
+    pub bundles: Vec<EntryBundle>,
+    /// Competitions with fewer paying players than this are voided and refunded
+    #[serde(default = "default_min_players")]
+    pub min_players: i64,
+    /// Competitions cancelled by the operator, e.g. after an exploit, and refunded
+    #[serde(default)]
+    pub voided: Vec<VoidedCompetition>,
+    /// Ruleset the daily competition is played under, the game's default ruleset if not set
+    #[serde(default)]
+    pub ruleset: Option<String>,
+    /// IANA timezone the daily competition's days are counted in, e.g. "America/New_York"
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    /// Local time (HH:MM) each daily competition closes and the next one opens
+    #[serde(default = "default_cutoff")]
+    pub cutoff: String,
+}
+
+impl Default for CompetitionSettings {
+    fn default() -> Self {
+        CompetitionSettings {
+            entry_fee_sats: 500,
+            rake_percent: 10,
+            tournaments: vec![],
+            bundles: default_bundles(),
+            min_players: default_min_players(),
+            voided: vec![],
+            ruleset: None,
+            timezone: default_timezone(),
+            cutoff: default_cutoff(),
+        }
+    }
+}
+
+fn default_min_players() -> i64 {
+    2
+}
+
+fn default_timezone() -> String {
+    String::from("UTC")
+}
+
+fn default_cutoff() -> String {
+    String::from("00:00")
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VoidedCompetition {
+    pub competition_id: i64,
+    /// Shown to players alongside their refund
+    pub reason: String,
+}
+
+fn default_bundles() -> Vec<EntryBundle> {
+    vec![EntryBundle {
+        games: 1,
+        discount_percent: 0,
+    }]
+}
+
+impl CompetitionSettings {
+    pub fn day_boundary(&self) -> Result<DayBoundary, anyhow::Error> {
+        DayBoundary::parse(&self.timezone, &self.cutoff)
+            .map_err(|e| anyhow!("Invalid daily competition day: {}", e))
+    }
+
+    /// Make sure every tournament can be entered and settled before the server starts
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        validate_fees("daily", self.entry_fee_sats, self.rake_percent)?;
+        self.day_boundary()?;
+
+        let mut keys = vec![String::from("daily")];
+        for tournament in &self.tournaments {
+            if keys.contains(&tournament.key) {
+                return Err(anyhow!("Duplicate tournament key: {}", tournament.key));
+            }
+            keys.push(tournament.key.clone());
+
+            validate_fees(
+                &tournament.key,
+                tournament.entry_fee_sats,
+                tournament.rake_percent,
+            )?;
+            tournament
+                .schedule
+                .period_containing(OffsetDateTime::now_utc())
+                .map_err(|e| anyhow!("Invalid schedule for {}: {}", tournament.key, e))?;
+        }
+
+        if self.bundles.is_empty() {
+            return Err(anyhow!("At least one entry bundle must be offered"));
+        }
+        let mut sizes = vec![];
+        for bundle in &self.bundles {
+            if bundle.games <= 0 {
+                return Err(anyhow!("Entry bundles must contain at least one game"));
+            }
+            if sizes.contains(&bundle.games) {
+                return Err(anyhow!("Duplicate entry bundle of {} games", bundle.games));
+            }
+            sizes.push(bundle.games);
+
+            if !(0..100).contains(&bundle.discount_percent) {
+                return Err(anyhow!(
+                    "Discount for the {} game bundle must be between 0 and 99 percent",
+                    bundle.games
+                ));
+            }
+        }
+
+        if self.min_players < 1 {
+            return Err(anyhow!("Competitions need at least one player"));
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_fees(key: &str, entry_fee_sats: i64, rake_percent: i64) -> Result<(), anyhow::Error> {
+    if entry_fee_sats <= 0 {
+        return Err(anyhow!("Entry fee for {} must be above 0 sats", key));
+    }
+    if !(0..=100).contains(&rake_percent) {
+        return Err(anyhow!(
+            "Rake for {} must be between 0 and 100 percent",
+            key
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WalletSettings {
+    /// Smallest amount a player can withdraw from their balance
+    pub min_withdrawal_sats: i64,
+    /// Flat fee charged on every withdrawal
+    pub withdrawal_fee_sats: i64,
+    /// Fee charged on top as a percentage of the amount withdrawn
+    pub withdrawal_fee_percent: i64,
+}
+
+impl Default for WalletSettings {
+    fn default() -> Self {
+        WalletSettings {
+            min_withdrawal_sats: 1000,
+            withdrawal_fee_sats: 0,
+            withdrawal_fee_percent: 1,
+        }
+    }
+}
+
+impl WalletSettings {
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        if self.min_withdrawal_sats <= 0 {
+            return Err(anyhow!("Minimum withdrawal must be above 0 sats"));
+        }
+        if self.withdrawal_fee_sats < 0 {
+            return Err(anyhow!("Withdrawal fee cannot be negative"));
+        }
+        if !(0..100).contains(&self.withdrawal_fee_percent) {
+            return Err(anyhow!(
+                "Withdrawal fee percentage must be between 0 and 99 percent"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Fee charged to the player for withdrawing `amount_sats`, rounded up
+    pub fn withdrawal_fee(&self, amount_sats: i64) -> i64 {
+        self.withdrawal_fee_sats + (amount_sats * self.withdrawal_fee_percent + 99) / 100
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PayoutSettings {
+    /// How often the payout queue is checked for work
+    pub poll_interval_secs: u64,
+    /// Times a prize payment is attempted before it is given up on
+    pub max_attempts: i64,
+    /// Wait after the first failed attempt, doubled after every further failure
+    pub retry_base_secs: i64,
+    /// Longest wait between two attempts
+    pub retry_max_secs: i64,
+    /// How long a payment can stay in flight before an operator has to look at it
+    pub in_flight_timeout_secs: i64,
+}
+
+impl Default for PayoutSettings {
+    fn default() -> Self {
+        PayoutSettings {
+            poll_interval_secs: 10,
+            max_attempts: 5,
+            retry_base_secs: 30,
+            retry_max_secs: 3600,
+            in_flight_timeout_secs: 900,
+        }
+    }
+}
+
+impl PayoutSettings {
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        if self.poll_interval_secs == 0 {
+            return Err(anyhow!("Payout poll interval must be above 0 seconds"));
+        }
+        if self.max_attempts <= 0 {
+            return Err(anyhow!("Payouts need at least one attempt"));
+        }
+        if self.retry_base_secs <= 0 || self.retry_max_secs < self.retry_base_secs {
+            return Err(anyhow!(
+                "Payout retry delays must be above 0 and the maximum at least the base"
+            ));
+        }
+        if self.in_flight_timeout_secs <= 0 {
+            return Err(anyhow!("Payout in flight timeout must be above 0 seconds"));
+        }
+        Ok(())
+    }
+
+    /// Wait before the next attempt after `attempts` failed ones, doubling each time
+    pub fn retry_delay_secs(&self, attempts: i64) -> i64 {
+        let exponent = attempts.saturating_sub(1).clamp(0, 32) as u32;
+        self.retry_base_secs
+            .saturating_mul(2_i64.saturating_pow(exponent))
+            .min(self.retry_max_secs)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameSettings {
+    /// How long a competition session can go without asking for a config before it expires
+    pub session_idle_timeout_secs: i64,
+    /// Folder of ruleset files (.toml or .json) loaded on top of the built in classic rules
+    #[serde(default)]
+    pub rulesets_dir: Option<String>,
+    /// Ruleset for practice games and competitions that do not pick one
+    #[serde(default = "default_ruleset")]
+    pub default_ruleset: String,
+}
+
+impl Default for GameSettings {
     fn default() -> Self {
-        Self {
-            config: None,
-            level: None,
-            db_settings: DBSettings::default(),
-            api_settings: APISettings::default(),
-            ui_settings: UISettings::default(),
-            //ln_settings: LnSettings::default(), -- TODO
+        GameSettings {
+            session_idle_timeout_secs: 600,
+            rulesets_dir: None,
+            default_ruleset: default_ruleset(),
+        }
+    }
+}
+
+fn default_ruleset() -> String {
+    CLASSIC_RULESET.to_string()
+}
+
+impl GameSettings {
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        if self.session_idle_timeout_secs <= 0 {
+            return Err(anyhow!("Session idle timeout must be above 0 seconds"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AdminSettings {
+    /// Nostr pubkeys, hex or npub, allowed to use the admin API. Empty turns it off
+    pub pubkeys: Vec<String>,
+}
+
+impl AdminSettings {
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        for pubkey in &self.pubkeys {
+            PublicKey::parse(pubkey)
+                .map_err(|e| anyhow!("Invalid admin pubkey {}: {}", pubkey, e))?;
         }
+        Ok(())
+    }
+
+    pub fn is_admin(&self, pubkey: &PublicKey) -> bool {
+        self.pubkeys
+            .iter()
+            .any(|admin| PublicKey::parse(admin).is_ok_and(|admin| &admin == pubkey))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TournamentSettings {
+    /// Unique key used to enter the tournament, e.g. "weekly"
+    pub key: String,
+    pub name: String,
+    pub schedule: Schedule,
+    pub entry_fee_sats: i64,
+    pub rake_percent: i64,
+    /// Ruleset the tournament is played under, the daily competition's if not set
+    #[serde(default)]
+    pub ruleset: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EntryBundle {
+    /// Games credited once the bundle is paid for
+    pub games: i64,
+    /// Percentage taken off the price of buying the games one by one
+    pub discount_percent: i64,
+}
+
+impl EntryBundle {
+    /// Price of the bundle for a competition with the given entry fee
+    pub fn price_sats(&self, entry_fee_sats: i64) -> i64 {
+        (entry_fee_sats * self.games * (100 - self.discount_percent) / 100).max(1)
     }
 }
 
@@ -191,8 +514,7 @@ pub fn setup_logger(level: Option<String>) -> Result<(), fern::InitError> {
 }
 
 pub fn get_log_level(level: Option<String>) -> LevelFilter {
-    if level.is_some() {
-        let level = level.as_ref().unwrap();
+    if let Some(level) = level {
         match level.as_ref() {
             "trace" => LevelFilter::Trace,
             "debug" => LevelFilter::Debug,
@@ -213,3 +535,78 @@ pub fn get_log_level(level: Option<String>) -> LevelFilter {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_price_applies_discount() {
+        let single = EntryBundle {
+            games: 1,
+            discount_percent: 0,
+        };
+        let ten = EntryBundle {
+            games: 10,
+            discount_percent: 20,
+        };
+
+        assert_eq!(single.price_sats(500), 500);
+        assert_eq!(ten.price_sats(500), 4000);
+    }
+
+    #[test]
+    fn test_payout_retry_delay_backs_off() {
+        let settings = PayoutSettings::default();
+
+        assert_eq!(settings.retry_delay_secs(1), 30);
+        assert_eq!(settings.retry_delay_secs(2), 60);
+        assert_eq!(settings.retry_delay_secs(4), 240);
+        assert_eq!(settings.retry_delay_secs(20), 3600);
+    }
+
+    #[test]
+    fn test_withdrawal_fee_rounds_up() {
+        let settings = WalletSettings {
+            min_withdrawal_sats: 1000,
+            withdrawal_fee_sats: 10,
+            withdrawal_fee_percent: 1,
+        };
+
+        assert_eq!(settings.withdrawal_fee(1000), 20);
+        assert_eq!(settings.withdrawal_fee(1050), 21);
+    }
+
+    #[test]
+    fn test_admin_pubkeys_accept_hex_and_npub() {
+        use nostr_sdk::{Keys, ToBech32};
+
+        let admin = Keys::generate().public_key();
+        let other = Keys::generate().public_key();
+        let settings = AdminSettings {
+            pubkeys: vec![admin.to_bech32().unwrap(), other.to_hex()],
+        };
+
+        assert!(settings.validate().is_ok());
+        assert!(settings.is_admin(&admin));
+        assert!(settings.is_admin(&other));
+        assert!(!settings.is_admin(&Keys::generate().public_key()));
+        assert!(AdminSettings {
+            pubkeys: vec![String::from("not a pubkey")]
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_duplicate_bundles() {
+        let mut settings = CompetitionSettings::default();
+        assert!(settings.validate().is_ok());
+
+        settings.bundles.push(EntryBundle {
+            games: 1,
+            discount_percent: 10,
+        });
+        assert!(settings.validate().is_err());
+    }
+}
@@ -1,95 +1,492 @@
-use log::{error, info};
-use std::sync::Arc;
+use log::{error, info, warn};
+use std::{future::Future, sync::Arc};
 use time::{Duration, OffsetDateTime};
-use tokio::time as tokio_time;
+use tokio::{select, time as tokio_time};
+use tokio_util::sync::CancellationToken;
 
-use crate::startup::AppState;
+use crate::{
+    domain::Error, format_time, parse_time, startup::AppState, Competition, GamePaymentStatus,
+    PaymentStatus, PrizePayout,
+};
 
-// Process to run daily to find winners and set up prizes
-pub async fn run_daily_tasks(app_state: Arc<AppState>) {
+// Most rounds of a series settled in one tick, any left over are caught up on the next one
+const MAX_CATCH_UP: usize = 100;
+
+// Process to run regularly to close finished competitions and set up prizes. Stops between
+// rounds once `shutdown` is cancelled, so a settlement is never cut off half way
+pub async fn run_daily_tasks(app_state: Arc<AppState>, shutdown: CancellationToken) {
     info!("Starting daily tasks runner");
 
-    let mut interval = tokio_time::interval(tokio_time::Duration::from_secs(3600)); // Run every hour
+    let mut interval = tokio_time::interval(tokio_time::Duration::from_secs(300)); // Run every 5 minutes
 
     loop {
-        interval.tick().await;
+        select! {
+            _ = interval.tick() => {}
+            _ = shutdown.cancelled() => {
+                info!("Stopping daily tasks runner");
+                return;
+            }
+        }
 
-        // Check if it's the right time to run (e.g., 00:05 AM)
         let now = OffsetDateTime::now_utc();
-        if now.hour() == 0 && now.minute() >= 5 && now.minute() < 15 {
-            info!("Running daily tasks to find winners");
-
-            // Calculate yesterday's date
-            let yesterday = (now - Duration::days(1))
-                .format(&time::format_description::well_known::Iso8601::DEFAULT)
-                .unwrap_or_else(|_| {
-                    error!("Failed to format date");
-                    String::from("unknown")
-                })
-                .chars()
-                .take(10) // Get just the YYYY-MM-DD part
-                .collect::<String>();
-
-            info!("Processing winners for date: {}", yesterday);
-
-            // Find top scorer for yesterday
+
+        // Refund competitions the operator has cancelled
+        for voided in app_state.competition_store.voided() {
+            if let Err(e) =
+                void_by_operator(&app_state, voided.competition_id, &voided.reason).await
+            {
+                error!(
+                    "Failed to void competition {}: {}",
+                    voided.competition_id, e
+                );
+            }
+        }
+
+        if let Err(e) = run_settlements(&app_state, now).await {
+            error!("Failed to run settlements: {}", e);
+        }
+    }
+}
+
+// Settle or expire invoices players never came back for, every 5 minutes. Runs on its own so
+// a slow Lightning backend never holds up settlement
+pub async fn run_payment_sweeper(app_state: Arc<AppState>, shutdown: CancellationToken) {
+    run_every("payment sweeper", 300, shutdown, || async {
+        if let Err(e) = sweep_pending_payments(&app_state, OffsetDateTime::now_utc()).await {
+            error!("Failed to sweep pending payments: {}", e);
+        }
+    })
+    .await
+}
+
+// Close sessions players walked away from, every 5 minutes
+pub async fn run_session_expiry(app_state: Arc<AppState>, shutdown: CancellationToken) {
+    run_every("session expiry", 300, shutdown, || async {
+        if let Err(e) = expire_idle_sessions(&app_state, OffsetDateTime::now_utc()).await {
+            error!("Failed to expire idle sessions: {}", e);
+        }
+    })
+    .await
+}
+
+// Check the books against the Lightning backend once an hour
+pub async fn run_reconciliation(app_state: Arc<AppState>, shutdown: CancellationToken) {
+    run_every("ledger reconciliation", 3600, shutdown, || async {
+        if let Err(e) = reconcile_ledger(&app_state).await {
+            error!("Failed to reconcile ledger: {}", e);
+        }
+    })
+    .await
+}
+
+// Run `task` straight away and then every `period_secs` until `shutdown` is cancelled
+async fn run_every<F, Fut>(name: &str, period_secs: u64, shutdown: CancellationToken, task: F)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    info!("Starting {}", name);
+
+    let mut interval = tokio_time::interval(tokio_time::Duration::from_secs(period_secs));
+
+    loop {
+        select! {
+            _ = interval.tick() => {}
+            _ = shutdown.cancelled() => {
+                info!("Stopping {}", name);
+                return;
+            }
+        }
+
+        task().await;
+    }
+}
+
+// Settle every round that has ended since the last one the scheduler got to, including rounds
+// missed while the server was down and earlier runs that failed. Each round is recorded as a
+// settlement run, so running this again never settles a round twice
+pub async fn run_settlements(app_state: &AppState, now: OffsetDateTime) -> Result<(), Error> {
+    let competitions = &app_state.competition_store;
+    let settlements = &app_state.settlement_store;
+
+    let mut due = vec![];
+    for run in settlements.get_unfinished().await? {
+        due.push((
+            parse_time(&run.period_end)?,
+            parse_time(&run.period_start)?,
+            run.kind,
+        ));
+    }
+
+    for series in competitions.series() {
+        // A series is only followed from its first round, before that there is nothing to settle
+        let from = match settlements.last_period_end(&series.kind).await? {
+            Some(period_end) => Some(period_end),
+            None => competitions.first_start(&series.kind).await?,
+        };
+        let Some(from) = from else {
+            continue;
+        };
+
+        for (start, end) in
+            series
+                .schedule
+                .periods_between(parse_time(&from)?, now, MAX_CATCH_UP)?
+        {
+            due.push((end, start, series.kind.clone()));
+        }
+    }
+
+    // Rounds that no longer line up with their series' schedule, e.g. after it was changed
+    for competition in competitions.get_due_for_settlement(now).await? {
+        due.push((
+            competition.ends_at()?,
+            competition.starts_at()?,
+            competition.kind,
+        ));
+    }
+
+    due.sort();
+    due.dedup();
+
+    for (end, start, kind) in due {
+        if let Err(e) = settle_period(app_state, &kind, start, end, now).await {
+            error!("Failed to settle {} round starting {}: {}", kind, start, e);
+        }
+    }
+
+    Ok(())
+}
+
+// Settle one round of a series and record how it went
+pub async fn settle_period(
+    app_state: &AppState,
+    kind: &str,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    now: OffsetDateTime,
+) -> Result<(), Error> {
+    let settlements = &app_state.settlement_store;
+
+    let Some(run) = settlements
+        .start_run(kind, &format_time(start)?, &format_time(end)?, now)
+        .await?
+    else {
+        return Ok(());
+    };
+
+    match settle_round(app_state, kind, start).await {
+        Ok((competition_id, outcome)) => {
+            settlements
+                .complete_run(run.id, competition_id, outcome, now)
+                .await
+        }
+        Err(e) => {
+            settlements.fail_run(run.id, &e.to_string(), now).await?;
+            Err(e)
+        }
+    }
+}
+
+// Settle the competition of a round unless that already happened, returning it with what became
+// of it. A round nobody played has no competition
+async fn settle_round(
+    app_state: &AppState,
+    kind: &str,
+    start: OffsetDateTime,
+) -> Result<(Option<i64>, &'static str), Error> {
+    let competitions = &app_state.competition_store;
+
+    let Some(competition) = competitions.find_by_kind_and_start(kind, start).await? else {
+        return Ok((None, "no_entries"));
+    };
+
+    if matches!(competition.status.as_str(), "open" | "closed" | "settling") {
+        info!(
+            "Settling competition {} ({})",
+            competition.id, competition.name
+        );
+        settle_competition(app_state, &competition).await?;
+    }
+
+    let competition = competitions
+        .find_by_id(competition.id)
+        .await?
+        .ok_or_else(|| Error::NotFound(format!("Competition not found: {}", competition.id)))?;
+    let outcome = match competition.status.as_str() {
+        "voided" => "voided",
+        _ => "settled",
+    };
+
+    Ok((Some(competition.id), outcome))
+}
+
+// Close a finished competition, find its winner and record the prize
+pub async fn settle_competition(
+    app_state: &AppState,
+    competition: &Competition,
+) -> Result<(), Error> {
+    let competitions = &app_state.competition_store;
+
+    if competition.status == "open" {
+        competitions.update_status(competition.id, "closed").await?;
+    }
+    competitions
+        .update_status(competition.id, "settling")
+        .await?;
+
+    // Re-read the competition so the pot includes any payments settled while closing
+    let competition = competitions
+        .find_by_id(competition.id)
+        .await?
+        .ok_or_else(|| Error::NotFound(format!("Competition not found: {}", competition.id)))?;
+
+    // Without enough players there is nobody to compete against, so everyone gets their entry back
+    let players = competitions.count_paying_players(competition.id).await?;
+    if players > 0 && players < competitions.min_players() {
+        info!(
+            "Only {} paying players in {}, voiding it",
+            players, competition.name
+        );
+        return void_competition(
+            app_state,
+            &competition,
+            &format!("Fewer than {} players entered", competitions.min_players()),
+        )
+        .await;
+    }
+
+    match competitions.get_top_scorer(competition.id).await? {
+        Some(scorer) => {
+            info!(
+                "Found top scorer for {}: user_id={}, score={}",
+                competition.name, scorer.user_id, scorer.score
+            );
+
+            let date = competitions.competition_date(&competition)?;
             match app_state
                 .payment_store
-                .get_top_scorer_for_date(&yesterday)
-                .await
+                .record_winner(competition.id, &date, scorer.user_id, scorer.score)
+                .await?
             {
-                Ok(Some(scorer)) => {
-                    info!(
-                        "Found top scorer for {}: user_id={}, score={}",
-                        yesterday, scorer.user_id, scorer.score
-                    );
-
-                    // Count the number of games played that day
-                    match app_state
-                        .payment_store
-                        .count_games_for_date(&yesterday)
-                        .await
-                    {
-                        Ok(games_count) => {
-                            if games_count > 0 {
-                                // Calculate prize (90% of collected fees)
-                                let prize_amount = games_count * 450; // 90% of 500 sats per game
-
-                                // Record the winner
-                                match app_state
-                                    .payment_store
-                                    .record_daily_winner(
-                                        scorer.user_id,
-                                        &yesterday,
-                                        scorer.score,
-                                        prize_amount,
-                                    )
-                                    .await
-                                {
-                                    Ok(_) => {
-                                        info!("Recorded daily winner for {}: user_id={}, prize={} sats",
-                                              yesterday, scorer.user_id, prize_amount);
-                                    }
-                                    Err(e) => {
-                                        error!("Failed to record daily winner: {}", e);
-                                    }
-                                }
-                            } else {
-                                info!("No paid games found for {}, no prize to award", yesterday);
-                            }
-                        }
-                        Err(e) => {
-                            error!("Failed to count games for {}: {}", yesterday, e);
-                        }
-                    }
-                }
-                Ok(None) => {
-                    info!("No scores found for {}, no winner to announce", yesterday);
-                }
-                Err(e) => {
-                    error!("Failed to find top scorer for {}: {}", yesterday, e);
-                }
+                Some(prize) => info!(
+                    "Recorded winner for {}: user_id={}, prize={} sats",
+                    competition.name, prize.user_id, prize.amount_sats
+                ),
+                None => info!(
+                    "No prize recorded for {}, it has no pot or already has a winner",
+                    competition.name
+                ),
             }
         }
+        None => {
+            info!(
+                "No scores found for {}, no winner to announce",
+                competition.name
+            );
+        }
+    }
+
+    competitions.update_status(competition.id, "settled").await
+}
+
+// Cancel a competition and give every paying player a claimable refund of their entry fees
+pub async fn void_competition(
+    app_state: &AppState,
+    competition: &Competition,
+    reason: &str,
+) -> Result<(), Error> {
+    let refunds = app_state
+        .payout_store
+        .create_refunds(competition.id, reason)
+        .await?;
+
+    if competition.status != "voided" {
+        app_state
+            .competition_store
+            .update_status(competition.id, "voided")
+            .await?;
+        info!(
+            "Voided {}: {}, {} refunds created",
+            competition.name, reason, refunds
+        );
+    } else if refunds > 0 {
+        info!(
+            "Updated {} refunds for voided {}",
+            refunds, competition.name
+        );
+    }
+
+    Ok(())
+}
+
+// Award the prize of a settled competition to whoever wins it now that some of its
+// scores have been voided. Returns the prize if it changed hands
+pub async fn resettle_competition(
+    app_state: &AppState,
+    competition_id: i64,
+) -> Result<Option<PrizePayout>, Error> {
+    let competition = app_state
+        .competition_store
+        .find_by_id(competition_id)
+        .await?
+        .ok_or_else(|| Error::NotFound(format!("Competition not found: {}", competition_id)))?;
+
+    // Competitions still to be settled leave voided scores out by themselves
+    if competition.status != "settled" {
+        return Ok(None);
+    }
+    let Some(prize) = app_state
+        .payment_store
+        .get_competition_prize(competition.id)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    let Some(scorer) = app_state
+        .competition_store
+        .get_top_scorer(competition.id)
+        .await?
+    else {
+        return Err(Error::Conflict(format!(
+            "No scores left in {} to award prize {} to",
+            competition.name, prize.id
+        )));
+    };
+    if scorer.user_id == prize.user_id {
+        return Ok(None);
+    }
+
+    match app_state
+        .payment_store
+        .reassign_prize(&prize, scorer.user_id, scorer.score)
+        .await?
+    {
+        Some(prize) => {
+            info!(
+                "Re-settled {}: prize {} now goes to user_id={}, score={}",
+                competition.name, prize.id, scorer.user_id, scorer.score
+            );
+            Ok(Some(prize))
+        }
+        None => Err(Error::Conflict(format!(
+            "Prize {} for {} has already been claimed or spent by user {}, it has to be recovered by hand",
+            prize.id, competition.name, prize.user_id
+        ))),
     }
 }
+
+// Void a competition the operator cancelled, once its prize has been awarded it is too late
+async fn void_by_operator(
+    app_state: &AppState,
+    competition_id: i64,
+    reason: &str,
+) -> Result<(), Error> {
+    let competition = app_state
+        .competition_store
+        .find_by_id(competition_id)
+        .await?
+        .ok_or_else(|| Error::NotFound(format!("Competition not found: {}", competition_id)))?;
+
+    match competition.status.as_str() {
+        "open" | "closed" | "settling" | "voided" => {
+            void_competition(app_state, &competition, reason).await
+        }
+        status => {
+            warn!("Cannot void {}, it is already {}", competition.name, status);
+            Ok(())
+        }
+    }
+}
+
+// Bring pending payments up to date with the Lightning backend, crediting invoices that were
+// paid, failing the ones it rejected and expiring the ones that can no longer be paid
+pub async fn sweep_pending_payments(
+    app_state: &AppState,
+    now: OffsetDateTime,
+) -> Result<(), Error> {
+    let payments = &app_state.payment_store;
+
+    for payment in payments.get_pending_payments().await? {
+        let status = match app_state
+            .lightning_service
+            .get_payment_status(&payment.payment_id)
+            .await
+        {
+            Ok(Some(api_payment)) => Some(api_payment.status),
+            Ok(None) => None,
+            Err(e) => {
+                // Without knowing whether it was paid the payment cannot be expired yet
+                warn!(
+                    "Could not check payment {} while sweeping: {}",
+                    payment.payment_id, e
+                );
+                continue;
+            }
+        };
+
+        let swept = match status {
+            Some(PaymentStatus::Completed) => payments
+                .mark_payment_paid(&payment.payment_id)
+                .await?
+                .map(|payment| payment.status),
+            Some(PaymentStatus::Failed) => payments
+                .fail_payment(&payment.payment_id)
+                .await?
+                .then_some(GamePaymentStatus::Failed),
+            _ if payment.is_expired(now) => payments
+                .expire_payment(&payment.payment_id)
+                .await?
+                .then_some(GamePaymentStatus::Expired),
+            _ => None,
+        };
+
+        if let Some(status) = swept {
+            info!("Swept payment {} as {}", payment.payment_id, status);
+            if status == GamePaymentStatus::Paid {
+                app_state.live_board.invalidate();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Expire competition sessions that have not asked for a config within the idle timeout
+pub async fn expire_idle_sessions(app_state: &AppState, now: OffsetDateTime) -> Result<(), Error> {
+    let idle_since = now - Duration::seconds(app_state.game_settings.session_idle_timeout_secs);
+    let expired = app_state
+        .game_store
+        .expire_idle_sessions(idle_since)
+        .await?;
+
+    if expired > 0 {
+        info!("Expired {} idle sessions", expired);
+    }
+
+    Ok(())
+}
+
+// Compare the ledger with the Lightning wallet and report anything that does not add up
+pub async fn reconcile_ledger(app_state: &AppState) -> Result<(), Error> {
+    let balance = app_state.lightning_service.get_wallet_balance().await?;
+    let report = app_state.ledger_store.reconcile(&balance).await?;
+
+    if report.is_balanced() {
+        info!(
+            "Ledger reconciled, Lightning backend holds {} sats",
+            report.backend_total_sats
+        );
+    } else {
+        warn!(
+            "Ledger does not reconcile: ledger {} sats, backend {} sats, difference {} sats",
+            report.ledger_sats, report.backend_total_sats, report.difference_sats
+        );
+        for violation in &report.invariant_violations {
+            warn!("Ledger invariant violated: {}", violation);
+        }
+    }
+
+    Ok(())
+}
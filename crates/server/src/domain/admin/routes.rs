@@ -0,0 +1,731 @@
+use axum::{
+    extract::{FromRequestParts, Path, Query, State},
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use log::{error, info, warn};
+use nostr_sdk::PublicKey;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+use crate::{
+    map_error, mark_payout_paid, nostr_extractor::NostrAuth, parse_date, parse_time,
+    resettle_competition, settle_period, startup::AppState, void_competition, GamePaymentStatus,
+    PayoutKind, PayoutStatus, DAILY_KIND,
+};
+
+// Requests signed by one of the pubkeys in the admin settings
+#[derive(Clone, Debug)]
+pub struct AdminAuth {
+    pub pubkey: String,
+}
+
+impl FromRequestParts<Arc<AppState>> for AdminAuth {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let auth = NostrAuth::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        if !state.admin_settings.is_admin(&auth.pubkey) {
+            warn!("Admin request from non-admin pubkey: {}", auth.pubkey);
+            return Err((StatusCode::FORBIDDEN, "Not an admin").into_response());
+        }
+
+        Ok(Self {
+            pubkey: auth.pubkey.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsersQuery {
+    /// Part of a username or pubkey
+    pub search: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PaymentsQuery {
+    pub status: Option<GamePaymentStatus>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PayoutsQuery {
+    pub status: Option<PayoutStatus>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SettlementsQuery {
+    /// 'running', 'completed' or 'failed'
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SuspiciousScoresQuery {
+    /// Lowest suspicion to list, every flagged score if not given
+    pub min_suspicion: Option<f64>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SettleQuery {
+    /// Competition series to settle, the daily one if not given
+    pub competition: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReasonRequest {
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BanRequest {
+    /// Hex or npub
+    pub pubkey: String,
+    pub reason: String,
+    /// RFC3339 time the ban runs out, banned until lifted if not given
+    pub expires_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MarkPaidRequest {
+    /// Payment that paid the payout, the job's last payment if not given
+    pub payment_id: Option<String>,
+    pub fee_sats: Option<i64>,
+    /// Preimage shown by the Lightning backend, published for prizes
+    pub preimage: Option<String>,
+}
+
+fn page(limit: Option<i64>, offset: Option<i64>) -> (i64, i64) {
+    (
+        limit.unwrap_or(50).clamp(1, 200),
+        offset.unwrap_or(0).max(0),
+    )
+}
+
+// Find users by username or pubkey
+pub async fn admin_get_users(
+    admin: AdminAuth,
+    Query(query): Query<UsersQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    info!("Admin {} listing users", admin.pubkey);
+    let (limit, offset) = page(query.limit, query.offset);
+
+    match state
+        .user_store
+        .search_users(query.search.as_deref(), limit, offset)
+        .await
+    {
+        Ok(users) => Ok((StatusCode::OK, Json(users))),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// Everything about a single user, along with their bans
+pub async fn admin_get_user(
+    admin: AdminAuth,
+    Path(user_id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    info!("Admin {} viewing user {}", admin.pubkey, user_id);
+
+    let user = match state.user_store.find_by_id(user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, "User not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    let bans = state
+        .user_store
+        .get_bans(&user.nostr_pubkey)
+        .await
+        .map_err(map_error)?;
+    let balance_sats = state
+        .payment_store
+        .get_user_balance(user.id)
+        .await
+        .map_err(map_error)?;
+    let credits = state
+        .payment_store
+        .get_credit_balances(user.id)
+        .await
+        .map_err(map_error)?;
+    let scores = state
+        .game_store
+        .get_all_user_scores(user.id)
+        .await
+        .map_err(map_error)?;
+    let payments = state
+        .payment_store
+        .get_payments_for_user(user.id)
+        .await
+        .map_err(map_error)?;
+    let prizes = state
+        .payment_store
+        .get_prizes_for_user(user.id)
+        .await
+        .map_err(map_error)?;
+    let refunds = state
+        .payout_store
+        .get_refunds_for_user(user.id)
+        .await
+        .map_err(map_error)?;
+    let withdrawals = state
+        .payment_store
+        .get_withdrawals_for_user(user.id)
+        .await
+        .map_err(map_error)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "user": user,
+            "bans": bans,
+            "balance_sats": balance_sats,
+            "credits": credits,
+            "scores": scores,
+            "payments": payments,
+            "prizes": prizes,
+            "refunds": refunds,
+            "withdrawals": withdrawals
+        })),
+    ))
+}
+
+// List entry fee payments
+pub async fn admin_get_payments(
+    admin: AdminAuth,
+    Query(query): Query<PaymentsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    info!("Admin {} listing payments", admin.pubkey);
+    let (limit, offset) = page(query.limit, query.offset);
+
+    match state
+        .payment_store
+        .get_payments(query.status, limit, offset)
+        .await
+    {
+        Ok(payments) => Ok((StatusCode::OK, Json(payments))),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// List prize, refund and withdrawal payouts, e.g. the ones that need attention
+pub async fn admin_get_payouts(
+    admin: AdminAuth,
+    Query(query): Query<PayoutsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    info!("Admin {} listing payouts", admin.pubkey);
+    let (limit, offset) = page(query.limit, query.offset);
+
+    match state
+        .payout_store
+        .get_jobs(query.status, limit, offset)
+        .await
+    {
+        Ok(jobs) => Ok((StatusCode::OK, Json(jobs))),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// Record a payout as paid after checking by hand that its payment went through
+pub async fn admin_mark_payout_paid(
+    admin: AdminAuth,
+    Path((kind, target_id)): Path<(PayoutKind, i64)>,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<MarkPaidRequest>,
+) -> Result<impl IntoResponse, Response> {
+    info!(
+        "Admin {} marking {} {} as paid",
+        admin.pubkey, kind, target_id
+    );
+
+    let job = match state.payout_store.get_job(kind, target_id).await {
+        Ok(Some(job)) => job,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, "Payout not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    let Some(payment_id) = request.payment_id.or(job.payment_id.clone()) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "The payout was never sent, a payment_id is required",
+        )
+            .into_response());
+    };
+    let fee_sats = request.fee_sats.unwrap_or(0);
+    if fee_sats < 0 {
+        return Err((StatusCode::BAD_REQUEST, "Fee cannot be negative").into_response());
+    }
+
+    match mark_payout_paid(
+        &state,
+        &job,
+        &payment_id,
+        fee_sats,
+        request.preimage.as_deref(),
+    )
+    .await
+    {
+        Ok(true) => {
+            info!(
+                "{} {} marked as paid with payment {} by admin {}",
+                kind, target_id, payment_id, admin.pubkey
+            );
+            Ok((
+                StatusCode::OK,
+                Json(json!({
+                    "kind": kind,
+                    "target_id": target_id,
+                    "status": PayoutStatus::Paid,
+                    "payment_id": payment_id
+                })),
+            ))
+        }
+        Ok(false) => Err((
+            StatusCode::CONFLICT,
+            format!("Payout cannot be marked as paid, status: {}", job.status),
+        )
+            .into_response()),
+        Err(e) => {
+            error!("Failed to mark {} {} as paid: {}", kind, target_id, e);
+            Err(map_error(e))
+        }
+    }
+}
+
+// Send a payout that needs attention again after checking its last payment did not go through
+pub async fn admin_retry_payout(
+    admin: AdminAuth,
+    Path((kind, target_id)): Path<(PayoutKind, i64)>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    info!("Admin {} retrying {} {}", admin.pubkey, kind, target_id);
+
+    let job = match state.payout_store.get_job(kind, target_id).await {
+        Ok(Some(job)) => job,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, "Payout not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    match state.payout_store.requeue(&job).await {
+        Ok(true) => Ok((
+            StatusCode::ACCEPTED,
+            Json(json!({
+                "kind": kind,
+                "target_id": target_id,
+                "status": PayoutStatus::InvoiceReceived
+            })),
+        )),
+        Ok(false) => Err((
+            StatusCode::CONFLICT,
+            format!(
+                "Only payouts that need attention can be retried, status: {}",
+                job.status
+            ),
+        )
+            .into_response()),
+        Err(e) => {
+            error!("Failed to retry {} {}: {}", kind, target_id, e);
+            Err(map_error(e))
+        }
+    }
+}
+
+// Scores the plausibility checks flagged for review
+pub async fn admin_get_suspicious_scores(
+    admin: AdminAuth,
+    Query(query): Query<SuspiciousScoresQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    info!("Admin {} listing suspicious scores", admin.pubkey);
+    let (limit, offset) = page(query.limit, query.offset);
+
+    match state
+        .game_store
+        .get_suspicious_scores(query.min_suspicion.unwrap_or(0.0), limit, offset)
+        .await
+    {
+        Ok(scores) => Ok((StatusCode::OK, Json(scores))),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// Every config handed out for a session, to settle what a player actually faced
+pub async fn admin_get_session_configs(
+    admin: AdminAuth,
+    Path(session_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    info!(
+        "Admin {} viewing configs for session {}",
+        admin.pubkey, session_id
+    );
+
+    let session = match state.game_store.find_session(&session_id).await {
+        Ok(Some(session)) => session,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, "Session not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    match state.game_store.get_session_configs(&session_id).await {
+        Ok(configs) => Ok((
+            StatusCode::OK,
+            Json(json!({
+                "session": session,
+                "configs": configs
+            })),
+        )),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// Throw out a score so it no longer counts towards any ranking
+pub async fn admin_void_score(
+    admin: AdminAuth,
+    Path(score_id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ReasonRequest>,
+) -> Result<impl IntoResponse, Response> {
+    info!("Admin {} voiding score {}", admin.pubkey, score_id);
+
+    let score = match state
+        .game_store
+        .void_score(score_id, &request.reason, &admin.pubkey)
+        .await
+    {
+        Ok(Some(score)) => score,
+        Ok(None) => {
+            return Err((StatusCode::NOT_FOUND, "Score not found or already voided").into_response())
+        }
+        Err(e) => return Err(map_error(e)),
+    };
+    state.live_board.invalidate();
+
+    // If the score had already won its competition the prize goes to the next best player
+    let prize = match score.competition_id {
+        Some(competition_id) => match resettle_competition(&state, competition_id).await {
+            Ok(prize) => prize,
+            Err(e) => {
+                error!(
+                    "Score {} voided but competition {} could not be re-settled: {}",
+                    score.id, competition_id, e
+                );
+                return Err(map_error(e));
+            }
+        },
+        None => None,
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "score": score,
+            "reassigned_prize": prize
+        })),
+    ))
+}
+
+// Re-settle a competition by hand, e.g. after recovering a prize that was claimed
+// before the winning score was voided
+pub async fn admin_resettle_competition(
+    admin: AdminAuth,
+    Path(competition_id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    info!(
+        "Admin {} re-settling competition {}",
+        admin.pubkey, competition_id
+    );
+
+    match resettle_competition(&state, competition_id).await {
+        Ok(prize) => Ok((StatusCode::OK, Json(json!({ "reassigned_prize": prize })))),
+        Err(e) => {
+            error!("Failed to re-settle competition {}: {}", competition_id, e);
+            Err(map_error(e))
+        }
+    }
+}
+
+// Ban a pubkey from logging in and playing
+pub async fn admin_ban(
+    admin: AdminAuth,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<BanRequest>,
+) -> Result<impl IntoResponse, Response> {
+    let pubkey = match PublicKey::parse(&request.pubkey) {
+        Ok(pubkey) => pubkey.to_string(),
+        Err(e) => {
+            return Err((StatusCode::BAD_REQUEST, format!("Invalid pubkey: {}", e)).into_response())
+        }
+    };
+    let expires_at = match request.expires_at.as_deref().map(parse_time).transpose() {
+        Ok(expires_at) => expires_at,
+        Err(e) => return Err(map_error(e)),
+    };
+    if expires_at.is_some_and(|expires_at| expires_at <= OffsetDateTime::now_utc()) {
+        return Err((StatusCode::BAD_REQUEST, "Ban expiry must be in the future").into_response());
+    }
+    info!("Admin {} banning {}", admin.pubkey, pubkey);
+
+    let ban = match state
+        .user_store
+        .ban(&pubkey, &request.reason, &admin.pubkey, expires_at)
+        .await
+    {
+        Ok(Some(ban)) => ban,
+        Ok(None) => return Err((StatusCode::CONFLICT, "Pubkey is already banned").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+    state.live_board.invalidate();
+
+    // Banned players drop off the leaderboards, so prizes they have not claimed yet go to
+    // the next best player
+    let user = match state.user_store.find_by_pubkey(pubkey).await {
+        Ok(user) => user,
+        Err(e) => return Err(map_error(e)),
+    };
+    let prizes = match user {
+        Some(user) => match state.payment_store.get_prizes_for_user(user.id).await {
+            Ok(prizes) => prizes,
+            Err(e) => return Err(map_error(e)),
+        },
+        None => Vec::new(),
+    };
+    for prize in prizes.iter().filter(|prize| prize.status.is_claimable()) {
+        let Some(competition_id) = prize.competition_id else {
+            continue;
+        };
+        if let Err(e) = resettle_competition(&state, competition_id).await {
+            error!(
+                "Banned {} but competition {} could not be re-settled: {}",
+                ban.pubkey, competition_id, e
+            );
+        }
+    }
+
+    Ok((StatusCode::CREATED, Json(ban)))
+}
+
+// Lift the ban on a pubkey
+pub async fn admin_unban(
+    admin: AdminAuth,
+    Path(pubkey): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    let pubkey = match PublicKey::parse(&pubkey) {
+        Ok(pubkey) => pubkey.to_string(),
+        Err(e) => {
+            return Err((StatusCode::BAD_REQUEST, format!("Invalid pubkey: {}", e)).into_response())
+        }
+    };
+    info!("Admin {} lifting ban on {}", admin.pubkey, pubkey);
+
+    match state.user_store.lift_ban(&pubkey, &admin.pubkey).await {
+        Ok(true) => Ok((
+            StatusCode::OK,
+            Json(json!({ "pubkey": pubkey, "banned": false })),
+        )),
+        Ok(false) => Err((StatusCode::NOT_FOUND, "Pubkey is not banned").into_response()),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// List the rounds the settlement scheduler has run, e.g. the ones that failed
+pub async fn admin_get_settlements(
+    admin: AdminAuth,
+    Query(query): Query<SettlementsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    info!("Admin {} listing settlement runs", admin.pubkey);
+    let (limit, offset) = page(query.limit, query.offset);
+
+    match state
+        .settlement_store
+        .get_runs(query.status, limit, offset)
+        .await
+    {
+        Ok(runs) => Ok((StatusCode::OK, Json(runs))),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// Settle the round of a competition that ran on a YYYY-MM-DD date without waiting for the
+// daily tasks, e.g. after fixing whatever stopped it from settling
+pub async fn admin_settle(
+    admin: AdminAuth,
+    Path(date): Path<String>,
+    Query(query): Query<SettleQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    let kind = query.competition.as_deref().unwrap_or(DAILY_KIND);
+    info!("Admin {} settling {} for {}", admin.pubkey, kind, date);
+
+    let date = parse_date(&date).map_err(map_error)?;
+    let Some(series) = state.competition_store.find_series(kind) else {
+        return Err((StatusCode::NOT_FOUND, "Unknown competition").into_response());
+    };
+    let start = state
+        .competition_store
+        .start_on(&series, date)
+        .map_err(map_error)?;
+
+    let competition = match state
+        .competition_store
+        .find_by_kind_and_start(kind, start)
+        .await
+    {
+        Ok(Some(competition)) => competition,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, "Competition not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    if !matches!(competition.status.as_str(), "open" | "closed" | "settling") {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("{} is already {}", competition.name, competition.status),
+        )
+            .into_response());
+    }
+    if competition.ends_at().map_err(map_error)? > OffsetDateTime::now_utc() {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("{} has not ended yet", competition.name),
+        )
+            .into_response());
+    }
+
+    // Settled as a run of its round, like the scheduler does, so the round is not settled again
+    let now = OffsetDateTime::now_utc();
+    let start = competition.starts_at().map_err(map_error)?;
+    let end = competition.ends_at().map_err(map_error)?;
+    if let Err(e) = settle_period(&state, &competition.kind, start, end, now).await {
+        error!("Failed to settle competition {}: {}", competition.id, e);
+        return Err(map_error(e));
+    }
+
+    match state.competition_store.find_by_id(competition.id).await {
+        Ok(Some(settled)) if matches!(settled.status.as_str(), "open" | "closed" | "settling") => {
+            Err((
+                StatusCode::CONFLICT,
+                format!("The round of {} was already settled", settled.name),
+            )
+                .into_response())
+        }
+        Ok(competition) => Ok((StatusCode::OK, Json(competition))),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// Cancel a competition and refund its players
+pub async fn admin_void_competition(
+    admin: AdminAuth,
+    Path(competition_id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ReasonRequest>,
+) -> Result<impl IntoResponse, Response> {
+    info!(
+        "Admin {} voiding competition {}",
+        admin.pubkey, competition_id
+    );
+
+    let competition = match state.competition_store.find_by_id(competition_id).await {
+        Ok(Some(competition)) => competition,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, "Competition not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    // Once the prize has been awarded it is too late
+    if !matches!(
+        competition.status.as_str(),
+        "open" | "closed" | "settling" | "voided"
+    ) {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("{} is already {}", competition.name, competition.status),
+        )
+            .into_response());
+    }
+
+    if let Err(e) = void_competition(&state, &competition, &request.reason).await {
+        error!("Failed to void competition {}: {}", competition.id, e);
+        return Err(map_error(e));
+    }
+
+    match state.competition_store.find_by_id(competition.id).await {
+        Ok(competition) => Ok((StatusCode::OK, Json(competition))),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// Balance of the Lightning wallet checked against the ledger
+pub async fn admin_get_wallet(
+    admin: AdminAuth,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    info!("Admin {} viewing the wallet", admin.pubkey);
+
+    let balance = match state.lightning_service.get_wallet_balance().await {
+        Ok(balance) => balance,
+        Err(e) => {
+            error!("Failed to get wallet balance: {}", e);
+            return Err((
+                StatusCode::BAD_GATEWAY,
+                format!("Failed to get wallet balance: {}", e),
+            )
+                .into_response());
+        }
+    };
+
+    let report = state
+        .ledger_store
+        .reconcile(&balance)
+        .await
+        .map_err(map_error)?;
+    let accounts = state.ledger_store.balances().await.map_err(map_error)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "balance": balance,
+            "reconciliation": report,
+            "accounts": accounts
+        })),
+    ))
+}
+
+pub async fn admin_get_rulesets(
+    admin: AdminAuth,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    info!("Admin {} viewing rulesets", admin.pubkey);
+
+    let rulesets = state.game_store.rulesets();
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "default": rulesets.default_ruleset(),
+            "rulesets": rulesets.all()
+        })),
+    ))
+}
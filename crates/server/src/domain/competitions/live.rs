@@ -0,0 +1,181 @@
+use serde::Serialize;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, RwLock,
+};
+use time::OffsetDateTime;
+use tokio::sync::broadcast;
+
+use crate::domain::Error;
+
+use super::store::{format_time, CompetitionStore, LeaderboardEntry};
+
+// Players shown on the live leaderboard
+const LIVE_LEADERS: i64 = 10;
+
+/// Today's daily competition as it stands
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveCompetition {
+    pub competition_id: i64,
+    pub date: String,
+    pub entry_fee_sats: i64,
+    pub paid_entries: i64,
+    pub players: i64,
+    pub pot_sats: i64,
+    pub prize_sats: i64,
+    pub closes_at: String,
+    /// Seconds left until entries close, as of when this was sent
+    pub seconds_remaining: i64,
+    pub leaders: Vec<LeaderboardEntry>,
+}
+
+#[derive(Debug, Clone)]
+struct Snapshot {
+    live: LiveCompetition,
+    closes_at: OffsetDateTime,
+}
+
+// Cache of the live competition, rebuilt on the first request after a paid entry or a new
+// score. Subscribers are told when it changes so they can fetch it again
+#[derive(Debug, Clone)]
+pub struct LiveBoard {
+    competition_store: CompetitionStore,
+    snapshot: Arc<RwLock<Option<Snapshot>>>,
+    // Bumped on every change, so a rebuild that raced with one is not kept
+    generation: Arc<AtomicU64>,
+    changes: broadcast::Sender<()>,
+}
+
+impl LiveBoard {
+    pub fn new(competition_store: CompetitionStore) -> Self {
+        let (changes, _) = broadcast::channel(16);
+        Self {
+            competition_store,
+            snapshot: Arc::new(RwLock::new(None)),
+            generation: Arc::new(AtomicU64::new(0)),
+            changes,
+        }
+    }
+
+    // Drop the cached competition and let subscribers know it changed
+    pub fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        *self.snapshot.write().unwrap_or_else(|e| e.into_inner()) = None;
+        // Nobody listening is fine
+        let _ = self.changes.send(());
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<()> {
+        self.changes.subscribe()
+    }
+
+    // The live competition from the cache, rebuilt if it changed or the day rolled over
+    pub async fn current(&self, now: OffsetDateTime) -> Result<LiveCompetition, Error> {
+        let cached = self
+            .snapshot
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if let Some(snapshot) = cached.filter(|snapshot| now < snapshot.closes_at) {
+            return Ok(with_time_remaining(snapshot, now));
+        }
+
+        let generation = self.generation.load(Ordering::SeqCst);
+        let snapshot = self.build().await?;
+
+        let mut cached = self.snapshot.write().unwrap_or_else(|e| e.into_inner());
+        if self.generation.load(Ordering::SeqCst) == generation {
+            *cached = Some(snapshot.clone());
+        }
+
+        Ok(with_time_remaining(snapshot, now))
+    }
+
+    async fn build(&self) -> Result<Snapshot, Error> {
+        let store = &self.competition_store;
+        let competition = store.current_daily().await?;
+
+        let paid_entries = store.count_paid_entries(competition.id).await?;
+        let players = store.count_paying_players(competition.id).await?;
+        let leaders = store.get_leaderboard(competition.id, LIVE_LEADERS).await?;
+        let closes_at = competition.ends_at()?;
+
+        Ok(Snapshot {
+            live: LiveCompetition {
+                competition_id: competition.id,
+                date: store.competition_date(&competition)?,
+                entry_fee_sats: competition.entry_fee_sats,
+                paid_entries,
+                players,
+                pot_sats: competition.pot_sats,
+                prize_sats: competition.prize_sats(),
+                closes_at: format_time(closes_at)?,
+                seconds_remaining: 0,
+                leaders,
+            },
+            closes_at,
+        })
+    }
+}
+
+fn with_time_remaining(snapshot: Snapshot, now: OffsetDateTime) -> LiveCompetition {
+    LiveCompetition {
+        seconds_remaining: (snapshot.closes_at - now).whole_seconds().max(0),
+        ..snapshot.live
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        domain::testing::{
+            create_test_user, pay_entry, test_competition_store, test_db, test_game_store,
+        },
+        Assessment,
+    };
+
+    #[tokio::test]
+    async fn test_snapshot_follows_entries_and_scores() {
+        let db = test_db().await;
+        let competitions = test_competition_store(&db);
+        let games = test_game_store(&db);
+        let board = LiveBoard::new(competitions.clone());
+        let mut changes = board.subscribe();
+        let now = OffsetDateTime::now_utc();
+
+        let before = board.current(now).await.unwrap();
+        assert_eq!(before.paid_entries, 0);
+        assert!(before.leaders.is_empty());
+
+        let user = create_test_user(&db).await;
+        pay_entry(&db, user.id, before.competition_id, 500, 1).await;
+        // Served from the cache until told otherwise
+        assert_eq!(board.current(now).await.unwrap().paid_entries, 0);
+
+        board.invalidate();
+        changes.try_recv().unwrap();
+        let paid = board.current(now).await.unwrap();
+        assert_eq!(paid.paid_entries, 1);
+        assert_eq!(paid.players, 1);
+        assert_eq!(paid.pot_sats, before.pot_sats + 500);
+        assert!(paid.leaders.is_empty());
+
+        let session = games
+            .create_paid_session(user.id, before.competition_id)
+            .await
+            .unwrap()
+            .unwrap();
+        games
+            .submit_score(&session, 1200, 3, 60, &Assessment::default(), now)
+            .await
+            .unwrap();
+        board.invalidate();
+        changes.try_recv().unwrap();
+        let scored = board.current(now).await.unwrap();
+        assert_eq!(scored.leaders.len(), 1);
+        assert_eq!(scored.leaders[0].score, 1200);
+        assert_eq!(scored.paid_entries, 1);
+    }
+}
@@ -0,0 +1,11 @@
+mod live;
+mod routes;
+mod schedule;
+mod settlements;
+mod store;
+
+pub use live::*;
+pub use routes::*;
+pub use schedule::*;
+pub use settlements::*;
+pub use store::*;
@@ -0,0 +1,266 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use futures::{stream, Stream};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use time::OffsetDateTime;
+use tokio::{sync::broadcast::error::RecvError, time as tokio_time};
+
+use crate::{invoice_payment_hash, map_error, parse_date, startup::AppState, PayoutStatus};
+
+use super::store::{Competition, LeaderboardEntry, TopScorer};
+
+#[derive(Debug, Deserialize)]
+pub struct LeaderboardQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LeaderboardResponse {
+    pub competition: Competition,
+    pub prize_sats: i64,
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrizeProof {
+    pub winner: String,
+    pub score: i64,
+    pub amount_sats: i64,
+    pub status: PayoutStatus,
+    /// Invoice, payment hash and preimage, set once the prize is paid
+    pub payment_request: Option<String>,
+    pub payment_hash: Option<String>,
+    pub preimage: Option<String>,
+    pub paid_at: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompetitionReport {
+    pub competition: Competition,
+    pub paid_entries: i64,
+    pub players: i64,
+    pub pot_sats: i64,
+    pub rake_percent: i64,
+    pub rake_sats: i64,
+    pub prize_sats: i64,
+    /// Best player so far while the competition is running
+    pub leader: Option<TopScorer>,
+    /// Final leaderboard once the competition has ended
+    pub standings: Vec<LeaderboardEntry>,
+    pub prize: Option<PrizeProof>,
+}
+
+// List the competitions currently accepting entries
+pub async fn get_current_competitions(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    info!("Get current competitions request");
+
+    match state.competition_store.current_competitions().await {
+        Ok(competitions) => Ok((StatusCode::OK, Json(competitions))),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// Today's running pot, time left and leaders, from memory until something changes
+pub async fn get_live_competition(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    info!("Get live competition request");
+
+    match state.live_board.current(OffsetDateTime::now_utc()).await {
+        Ok(live) => Ok((StatusCode::OK, Json(live))),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// Stream of the live competition, sent straight away and again after each paid entry or new
+// score, and when the day's competition closes
+pub async fn get_live_competition_events(
+    State(state): State<Arc<AppState>>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    info!("Live competition events subscription");
+
+    let board = state.live_board.clone();
+    let changes = board.subscribe();
+    let shutdown = state.supervisor.shutdown_token();
+
+    let events = stream::unfold(
+        (board, changes, shutdown, None::<OffsetDateTime>),
+        |(board, mut changes, shutdown, closes_at)| async move {
+            // Wait for a change, or for the competition to close so the next day's is sent
+            if let Some(closes_at) = closes_at {
+                let until_close =
+                    (closes_at - OffsetDateTime::now_utc()).max(time::Duration::SECOND);
+                tokio::select! {
+                    _ = shutdown.cancelled() => return None,
+                    change = changes.recv() => {
+                        if let Err(RecvError::Closed) = change {
+                            return None;
+                        }
+                    }
+                    _ = tokio_time::sleep(until_close.unsigned_abs()) => {}
+                }
+            }
+
+            let now = OffsetDateTime::now_utc();
+            let (event, closes_at) = match board.current(now).await {
+                Ok(live) => (
+                    Event::default().event("competition").json_data(&live),
+                    now + time::Duration::seconds(live.seconds_remaining),
+                ),
+                Err(e) => {
+                    error!("Failed to build live competition: {}", e);
+                    // Try again after a short while rather than on the next change only
+                    let retry_at = now + time::Duration::seconds(30);
+                    (
+                        Ok(Event::default()
+                            .event("error")
+                            .data("Competition unavailable")),
+                        retry_at,
+                    )
+                }
+            };
+
+            Some((event, (board, changes, shutdown, Some(closes_at))))
+        },
+    );
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+// Get the leaderboard of a single competition
+pub async fn get_competition_leaderboard(
+    Path(competition_id): Path<i64>,
+    Query(query): Query<LeaderboardQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    info!(
+        "Get leaderboard request for competition: {}",
+        competition_id
+    );
+
+    let competition = match state.competition_store.find_by_id(competition_id).await {
+        Ok(Some(competition)) => competition,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, "Competition not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    let limit = query.limit.unwrap_or(10).clamp(1, 100);
+
+    match state
+        .competition_store
+        .get_leaderboard(competition.id, limit)
+        .await
+    {
+        Ok(entries) => Ok((
+            StatusCode::OK,
+            Json(LeaderboardResponse {
+                prize_sats: competition.prize_sats(),
+                competition,
+                entries,
+            }),
+        )),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// Public record of a day's competition, so anyone can check the pot, the rake and that the
+// winner was paid what they were promised
+pub async fn get_daily_competition(
+    Path(date): Path<String>,
+    Query(query): Query<LeaderboardQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    info!("Get daily competition request for: {}", date);
+
+    let date = match parse_date(&date) {
+        Ok(date) => date,
+        Err(e) => return Err(map_error(e)),
+    };
+    let competition = match state.competition_store.find_daily(date).await {
+        Ok(Some(competition)) => competition,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, "Competition not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    let store = &state.competition_store;
+    let paid_entries = match store.count_paid_entries(competition.id).await {
+        Ok(count) => count,
+        Err(e) => return Err(map_error(e)),
+    };
+    let players = match store.count_paying_players(competition.id).await {
+        Ok(count) => count,
+        Err(e) => return Err(map_error(e)),
+    };
+
+    let ended = match competition.ends_at() {
+        Ok(ends_at) => ends_at <= OffsetDateTime::now_utc(),
+        Err(e) => return Err(map_error(e)),
+    };
+    let leader = if ended {
+        None
+    } else {
+        match store.get_top_scorer(competition.id).await {
+            Ok(leader) => leader,
+            Err(e) => return Err(map_error(e)),
+        }
+    };
+    let standings = if ended {
+        let limit = query.limit.unwrap_or(10).clamp(1, 100);
+        match store.get_leaderboard(competition.id, limit).await {
+            Ok(entries) => entries,
+            Err(e) => return Err(map_error(e)),
+        }
+    } else {
+        Vec::new()
+    };
+
+    let prize = match store.get_prize(competition.id).await {
+        Ok(prize) => prize.map(|prize| {
+            let paid = prize.status == PayoutStatus::Paid;
+            PrizeProof {
+                winner: prize.username,
+                score: prize.score,
+                amount_sats: prize.amount_sats,
+                status: prize.status,
+                payment_hash: prize
+                    .payment_request
+                    .as_deref()
+                    .filter(|_| paid)
+                    .and_then(invoice_payment_hash),
+                payment_request: prize.payment_request.filter(|_| paid),
+                preimage: prize.payment_preimage.filter(|_| paid),
+                paid_at: prize.paid_at,
+            }
+        }),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(CompetitionReport {
+            paid_entries,
+            players,
+            pot_sats: competition.pot_sats,
+            rake_percent: competition.rake_percent,
+            rake_sats: competition.pot_sats - competition.prize_sats(),
+            prize_sats: competition.prize_sats(),
+            leader,
+            standings,
+            prize,
+            competition,
+        }),
+    ))
+}
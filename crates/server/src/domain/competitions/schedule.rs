@@ -0,0 +1,371 @@
+use serde::{Deserialize, Serialize};
+use time::{
+    macros::format_description, Date, Duration, Month, OffsetDateTime, PrimitiveDateTime, Time,
+    UtcOffset,
+};
+use time_tz::{
+    timezones, Offset, OffsetDateTimeExt, OffsetResult, PrimitiveDateTimeExt, TimeZone, Tz,
+};
+
+use crate::domain::Error;
+
+/// How often a recurring competition starts a new round
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Schedule {
+    /// Midnight to midnight UTC
+    Daily,
+    /// Monday 00:00 UTC to the following Monday
+    Weekly,
+    /// The 1st of the month 00:00 UTC to the 1st of the next month
+    Monthly,
+    /// Back to back rounds of `every_hours`, counted from `anchor` (RFC3339)
+    Custom { every_hours: i64, anchor: String },
+    /// From `cutoff` (HH:MM) local time in an IANA `timezone` to the same time the next day,
+    /// so a day can be 23 or 25 hours long when the clocks change
+    DailyAt { timezone: String, cutoff: String },
+}
+
+impl Schedule {
+    /// Start and end of the round that is running at `now`
+    pub fn period_containing(
+        &self,
+        now: OffsetDateTime,
+    ) -> Result<(OffsetDateTime, OffsetDateTime), Error> {
+        let today = now.date();
+        match self {
+            Schedule::Daily => {
+                let start = today.midnight().assume_utc();
+                Ok((start, start + Duration::days(1)))
+            }
+            Schedule::Weekly => {
+                let monday =
+                    today - Duration::days(today.weekday().number_days_from_monday() as i64);
+                let start = monday.midnight().assume_utc();
+                Ok((start, start + Duration::weeks(1)))
+            }
+            Schedule::Monthly => {
+                let first = first_of_month(today.year(), today.month())?;
+                let next = if today.month() == Month::December {
+                    first_of_month(today.year() + 1, Month::January)?
+                } else {
+                    first_of_month(today.year(), today.month().next())?
+                };
+                Ok((first.midnight().assume_utc(), next.midnight().assume_utc()))
+            }
+            Schedule::Custom {
+                every_hours,
+                anchor,
+            } => {
+                if *every_hours <= 0 {
+                    return Err(Error::InvalidInput(format!(
+                        "Custom schedule needs a positive interval, got {} hours",
+                        every_hours
+                    )));
+                }
+                let anchor = super::parse_time(anchor)?;
+                let length = Duration::hours(*every_hours);
+                let elapsed = (now - anchor).whole_seconds();
+                let rounds = elapsed.div_euclid(length.whole_seconds());
+                let start = anchor + length * rounds as i32;
+                Ok((start, start + length))
+            }
+            Schedule::DailyAt { timezone, cutoff } => {
+                let boundary = DayBoundary::parse(timezone, cutoff)?;
+                let day = boundary.day_of(now);
+                let next = day
+                    .next_day()
+                    .ok_or_else(|| Error::InvalidInput(format!("No day after {}", day)))?;
+                Ok((boundary.start_of(day), boundary.start_of(next)))
+            }
+        }
+    }
+
+    /// Rounds that start with the one running at `from` and have ended by `now`, at most
+    /// `limit` of them
+    pub fn periods_between(
+        &self,
+        from: OffsetDateTime,
+        now: OffsetDateTime,
+        limit: usize,
+    ) -> Result<Vec<(OffsetDateTime, OffsetDateTime)>, Error> {
+        let mut periods = vec![];
+        let (mut start, mut end) = self.period_containing(from)?;
+        while end <= now && periods.len() < limit {
+            periods.push((start, end));
+            (start, end) = self.period_containing(end)?;
+        }
+
+        Ok(periods)
+    }
+}
+
+/// Where one day's daily competition ends and the next one starts: a time of day in an IANA
+/// timezone, whatever its offset from UTC is on the day
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DayBoundary {
+    timezone: &'static Tz,
+    cutoff: Time,
+}
+
+impl Default for DayBoundary {
+    fn default() -> Self {
+        DayBoundary {
+            timezone: timezones::db::UTC,
+            cutoff: Time::MIDNIGHT,
+        }
+    }
+}
+
+impl DayBoundary {
+    /// From a timezone like "Europe/Berlin" (or "UTC") and a local time like "06:00"
+    pub fn parse(timezone: &str, cutoff: &str) -> Result<Self, Error> {
+        let timezone = match timezone {
+            "UTC" | "Z" => timezones::db::UTC,
+            name => timezones::get_by_name(name)
+                .ok_or_else(|| Error::InvalidInput(format!("Unknown timezone {}", name)))?,
+        };
+        let cutoff = Time::parse(cutoff, format_description!("[hour]:[minute]"))
+            .map_err(|e| Error::InvalidInput(format!("Invalid cutoff {}: {}", cutoff, e)))?;
+
+        Ok(DayBoundary { timezone, cutoff })
+    }
+
+    /// When the competition of a local date starts, in UTC
+    pub fn start_of(&self, date: Date) -> OffsetDateTime {
+        let local = PrimitiveDateTime::new(date, self.cutoff);
+        let start = match local.assume_timezone(self.timezone) {
+            OffsetResult::Some(start) => start,
+            // The clocks went back over the cutoff, the day starts the first time it comes round
+            OffsetResult::Ambiguous(first, second) => first.min(second),
+            // The clocks went forward over it, the day starts as long after the jump as the
+            // cutoff is after the last time before it
+            OffsetResult::None => {
+                let before = self
+                    .timezone
+                    .get_offset_utc(&(local - Duration::days(1)).assume_utc());
+                local.assume_offset(before.to_utc())
+            }
+        };
+        start.to_offset(UtcOffset::UTC)
+    }
+
+    /// Local date of the competition running at `at`
+    pub fn day_of(&self, at: OffsetDateTime) -> Date {
+        let local = at.to_timezone(self.timezone);
+        if local.time() < self.cutoff {
+            local.date().previous_day().unwrap_or(local.date())
+        } else {
+            local.date()
+        }
+    }
+
+    /// Schedule the daily competition runs on, from one day's start to the next
+    pub fn schedule(&self) -> Result<Schedule, Error> {
+        if *self == DayBoundary::default() {
+            return Ok(Schedule::Daily);
+        }
+
+        Ok(Schedule::DailyAt {
+            timezone: self.timezone.name().to_string(),
+            cutoff: self
+                .cutoff
+                .format(format_description!("[hour]:[minute]"))
+                .map_err(|e| Error::InvalidInput(format!("Failed to format cutoff: {}", e)))?,
+        })
+    }
+}
+
+fn first_of_month(year: i32, month: Month) -> Result<Date, Error> {
+    Date::from_calendar_date(year, month, 1)
+        .map_err(|e| Error::InvalidInput(format!("Invalid date: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::{date, datetime};
+
+    #[test]
+    fn test_daily_period() {
+        let (start, end) = Schedule::Daily
+            .period_containing(datetime!(2025-04-16 13:45 UTC))
+            .unwrap();
+
+        assert_eq!(start, datetime!(2025-04-16 00:00 UTC));
+        assert_eq!(end, datetime!(2025-04-17 00:00 UTC));
+    }
+
+    #[test]
+    fn test_weekly_period_starts_on_monday() {
+        // 2025-04-16 is a Wednesday
+        let (start, end) = Schedule::Weekly
+            .period_containing(datetime!(2025-04-16 13:45 UTC))
+            .unwrap();
+
+        assert_eq!(start, datetime!(2025-04-14 00:00 UTC));
+        assert_eq!(end, datetime!(2025-04-21 00:00 UTC));
+
+        let (start, _) = Schedule::Weekly
+            .period_containing(datetime!(2025-04-14 00:00 UTC))
+            .unwrap();
+        assert_eq!(start, datetime!(2025-04-14 00:00 UTC));
+    }
+
+    #[test]
+    fn test_monthly_period_rolls_over_year() {
+        let (start, end) = Schedule::Monthly
+            .period_containing(datetime!(2025-12-31 23:59 UTC))
+            .unwrap();
+
+        assert_eq!(start, datetime!(2025-12-01 00:00 UTC));
+        assert_eq!(end, datetime!(2026-01-01 00:00 UTC));
+    }
+
+    #[test]
+    fn test_custom_period() {
+        let schedule = Schedule::Custom {
+            every_hours: 6,
+            anchor: String::from("2025-04-01T00:00:00Z"),
+        };
+
+        let (start, end) = schedule
+            .period_containing(datetime!(2025-04-02 13:00 UTC))
+            .unwrap();
+        assert_eq!(start, datetime!(2025-04-02 12:00 UTC));
+        assert_eq!(end, datetime!(2025-04-02 18:00 UTC));
+
+        // Times before the anchor still land in a whole round
+        let (start, _) = schedule
+            .period_containing(datetime!(2025-03-31 23:00 UTC))
+            .unwrap();
+        assert_eq!(start, datetime!(2025-03-31 18:00 UTC));
+    }
+
+    #[test]
+    fn test_periods_between_catches_up_ended_rounds() {
+        let periods = Schedule::Daily
+            .periods_between(
+                datetime!(2025-04-14 00:00 UTC),
+                datetime!(2025-04-16 13:45 UTC),
+                10,
+            )
+            .unwrap();
+        assert_eq!(
+            periods,
+            vec![
+                (
+                    datetime!(2025-04-14 00:00 UTC),
+                    datetime!(2025-04-15 00:00 UTC)
+                ),
+                (
+                    datetime!(2025-04-15 00:00 UTC),
+                    datetime!(2025-04-16 00:00 UTC)
+                ),
+            ]
+        );
+
+        let periods = Schedule::Daily
+            .periods_between(
+                datetime!(2025-04-01 00:00 UTC),
+                datetime!(2025-04-16 13:45 UTC),
+                3,
+            )
+            .unwrap();
+        assert_eq!(periods.len(), 3);
+    }
+
+    #[test]
+    fn test_day_boundary_in_another_timezone() {
+        let boundary = DayBoundary::parse("America/New_York", "20:00").unwrap();
+
+        // 20:00 in New York on the 2nd is 00:00 UTC on the 3rd while on summer time
+        assert_eq!(
+            boundary.start_of(date!(2025 - 04 - 02)),
+            datetime!(2025-04-03 00:00 UTC)
+        );
+        assert_eq!(
+            boundary.day_of(datetime!(2025-04-02 23:59 UTC)),
+            date!(2025 - 04 - 01)
+        );
+        assert_eq!(
+            boundary.day_of(datetime!(2025-04-03 00:00 UTC)),
+            date!(2025 - 04 - 02)
+        );
+
+        let (start, end) = boundary
+            .schedule()
+            .unwrap()
+            .period_containing(datetime!(2025-04-03 12:00 UTC))
+            .unwrap();
+        assert_eq!(start, datetime!(2025-04-03 00:00 UTC));
+        assert_eq!(end, datetime!(2025-04-04 00:00 UTC));
+
+        assert_eq!(
+            DayBoundary::parse("UTC", "00:00")
+                .unwrap()
+                .schedule()
+                .unwrap(),
+            Schedule::Daily
+        );
+        assert!(DayBoundary::parse("+01:00", "00:00").is_err());
+        assert!(DayBoundary::parse("Mars/Olympus_Mons", "00:00").is_err());
+        assert!(DayBoundary::parse("Europe/Paris", "25:00").is_err());
+    }
+
+    #[test]
+    fn test_days_follow_the_clocks_changing() {
+        let schedule = DayBoundary::parse("Europe/Berlin", "06:00")
+            .unwrap()
+            .schedule()
+            .unwrap();
+
+        // The clocks go forward on 30 March 2025, that day is 23 hours long
+        let (start, end) = schedule
+            .period_containing(datetime!(2025-03-30 12:00 UTC))
+            .unwrap();
+        assert_eq!(start, datetime!(2025-03-30 04:00 UTC));
+        assert_eq!(end, datetime!(2025-03-31 04:00 UTC));
+        let (start, _) = schedule
+            .period_containing(datetime!(2025-03-30 03:00 UTC))
+            .unwrap();
+        assert_eq!(start, datetime!(2025-03-29 05:00 UTC));
+
+        // and go back on 26 October, when the day lasts 25 hours
+        let (start, end) = schedule
+            .period_containing(datetime!(2025-10-26 12:00 UTC))
+            .unwrap();
+        assert_eq!(start, datetime!(2025-10-26 05:00 UTC));
+        assert_eq!(end, datetime!(2025-10-27 05:00 UTC));
+        assert_eq!(end - start, Duration::hours(24));
+        let (start, end) = schedule
+            .period_containing(datetime!(2025-10-26 04:30 UTC))
+            .unwrap();
+        assert_eq!(start, datetime!(2025-10-25 04:00 UTC));
+        assert_eq!(end - start, Duration::hours(25));
+
+        // A cutoff the clocks jump over starts the day just after the jump
+        let boundary = DayBoundary::parse("Europe/Berlin", "02:30").unwrap();
+        assert_eq!(
+            boundary.start_of(date!(2025 - 03 - 30)),
+            datetime!(2025-03-30 01:30 UTC)
+        );
+        // and one they pass twice starts it the first time round
+        assert_eq!(
+            boundary.start_of(date!(2025 - 10 - 26)),
+            datetime!(2025-10-26 00:30 UTC)
+        );
+    }
+
+    #[test]
+    fn test_custom_period_rejects_empty_interval() {
+        let schedule = Schedule::Custom {
+            every_hours: 0,
+            anchor: String::from("2025-04-01T00:00:00Z"),
+        };
+
+        assert!(schedule
+            .period_containing(datetime!(2025-04-02 13:00 UTC))
+            .is_err());
+    }
+}
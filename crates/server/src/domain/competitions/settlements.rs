@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+use time::OffsetDateTime;
+
+use crate::domain::Error;
+
+use super::store::format_time_secs;
+
+/// A round of a competition series the scheduler has settled or tried to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettlementRun {
+    pub id: i64,
+    pub kind: String,
+    pub period_start: String,
+    pub period_end: String,
+    /// None if nobody played the round
+    pub competition_id: Option<i64>,
+    /// 'running' -> 'completed' | 'failed', failed and interrupted runs are tried again
+    pub status: String,
+    /// 'settled', 'voided' or 'no_entries' once completed
+    pub outcome: Option<String>,
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SettlementStore {
+    db: Pool<Sqlite>,
+}
+
+impl SettlementStore {
+    pub fn new(db: Pool<Sqlite>) -> Self {
+        Self { db }
+    }
+
+    // Start settling a round, or try it again after it failed. Returns None once the round
+    // has been settled, so it is never settled twice
+    pub async fn start_run(
+        &self,
+        kind: &str,
+        period_start: &str,
+        period_end: &str,
+        now: OffsetDateTime,
+    ) -> Result<Option<SettlementRun>, Error> {
+        let now = format_time_secs(now)?;
+
+        let run = sqlx::query_as!(
+            SettlementRun,
+            r#"
+            INSERT INTO settlement_runs (kind, period_start, period_end, status, attempts, started_at)
+            VALUES (?, ?, ?, 'running', 1, ?)
+            ON CONFLICT(kind, period_start) DO UPDATE
+            SET status = 'running', attempts = attempts + 1, started_at = excluded.started_at,
+                finished_at = NULL
+            WHERE settlement_runs.status != 'completed'
+            RETURNING id as "id!: i64", kind, period_start, period_end, competition_id, status,
+                outcome, attempts, last_error, started_at, finished_at
+            "#,
+            kind,
+            period_start,
+            period_end,
+            now
+        )
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(run)
+    }
+
+    pub async fn complete_run(
+        &self,
+        id: i64,
+        competition_id: Option<i64>,
+        outcome: &str,
+        now: OffsetDateTime,
+    ) -> Result<(), Error> {
+        let now = format_time_secs(now)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE settlement_runs
+            SET status = 'completed', competition_id = ?, outcome = ?, last_error = NULL, finished_at = ?
+            WHERE id = ?
+            "#,
+            competition_id,
+            outcome,
+            now,
+            id
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn fail_run(&self, id: i64, error: &str, now: OffsetDateTime) -> Result<(), Error> {
+        let now = format_time_secs(now)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE settlement_runs
+            SET status = 'failed', last_error = ?, finished_at = ?
+            WHERE id = ?
+            "#,
+            error,
+            now,
+            id
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    // End of the latest round of a series the scheduler has got to, the next round to settle
+    // starts there
+    pub async fn last_period_end(&self, kind: &str) -> Result<Option<String>, Error> {
+        let result = sqlx::query!(
+            r#"
+            SELECT MAX(period_end) as "period_end: String"
+            FROM settlement_runs
+            WHERE kind = ?
+            "#,
+            kind
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(result.period_end)
+    }
+
+    // Runs that failed or were cut short by a restart, oldest first
+    pub async fn get_unfinished(&self) -> Result<Vec<SettlementRun>, Error> {
+        let runs = sqlx::query_as!(
+            SettlementRun,
+            r#"
+            SELECT id, kind, period_start, period_end, competition_id, status, outcome, attempts,
+                last_error, started_at, finished_at
+            FROM settlement_runs
+            WHERE status != 'completed'
+            ORDER BY period_end ASC
+            "#
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(runs)
+    }
+
+    // Most recent rounds first, optionally only those with a status
+    pub async fn get_runs(
+        &self,
+        status: Option<String>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<SettlementRun>, Error> {
+        let runs = sqlx::query_as!(
+            SettlementRun,
+            r#"
+            SELECT id, kind, period_start, period_end, competition_id, status, outcome, attempts,
+                last_error, started_at, finished_at
+            FROM settlement_runs
+            WHERE ?1 IS NULL OR status = ?1
+            ORDER BY period_end DESC, id DESC
+            LIMIT ?2 OFFSET ?3
+            "#,
+            status,
+            limit,
+            offset
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(runs)
+    }
+}
@@ -0,0 +1,822 @@
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+use time::{
+    format_description::well_known::Rfc3339, macros::format_description, Date, OffsetDateTime,
+};
+
+use crate::{
+    domain::Error, CompetitionSettings, DayBoundary, EntryBundle, PayoutStatus, Rulesets, Schedule,
+    VoidedCompetition,
+};
+
+pub const DAILY_KIND: &str = "daily";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompetitionSeries {
+    pub kind: String,
+    pub name: String,
+    pub schedule: Schedule,
+    pub entry_fee_sats: i64,
+    pub rake_percent: i64,
+    /// Ruleset new rounds are played under, at its latest version
+    pub ruleset: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Competition {
+    pub id: i64,
+    pub name: String,
+    pub kind: String,
+    pub start_time: String,
+    pub end_time: String,
+    pub entry_fee_sats: i64,
+    pub rake_percent: i64,
+    pub ruleset_id: String,
+    pub rules_version: String,
+    pub seed: String,
+    pub status: String,
+    pub pot_sats: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl Competition {
+    pub fn starts_at(&self) -> Result<OffsetDateTime, Error> {
+        parse_time(&self.start_time)
+    }
+
+    pub fn ends_at(&self) -> Result<OffsetDateTime, Error> {
+        parse_time(&self.end_time)
+    }
+
+    // Amount paid to the winner once the house rake is taken out of the pot
+    pub fn prize_sats(&self) -> i64 {
+        self.pot_sats * (100 - self.rake_percent) / 100
+    }
+
+    pub fn is_open_at(&self, now: OffsetDateTime) -> Result<bool, Error> {
+        Ok(self.status == "open" && self.starts_at()? <= now && now < self.ends_at()?)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardEntry {
+    pub rank: i64,
+    pub user_id: i64,
+    pub username: String,
+    pub score: i64,
+    pub level: i64,
+    pub play_time: i64,
+    pub games_played: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TopScorer {
+    pub user_id: i64,
+    pub score: i64,
+    pub games_played: i64,
+    pub username: String,
+}
+
+/// A competition's prize as awarded to its winner
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompetitionPrize {
+    pub username: String,
+    pub score: i64,
+    pub amount_sats: i64,
+    pub status: PayoutStatus,
+    /// Invoice the prize was paid to
+    pub payment_request: Option<String>,
+    pub payment_preimage: Option<String>,
+    pub paid_at: Option<String>,
+}
+
+pub fn parse_date(value: &str) -> Result<Date, Error> {
+    Date::parse(value, format_description!("[year]-[month]-[day]"))
+        .map_err(|e| Error::InvalidInput(format!("Invalid date {}: {}", value, e)))
+}
+
+pub fn format_time(time: OffsetDateTime) -> Result<String, Error> {
+    time.format(&Rfc3339)
+        .map_err(|e| Error::InvalidInput(format!("Failed to format time: {}", e)))
+}
+
+// Whole seconds, for times compared as text in queries
+pub fn format_time_secs(time: OffsetDateTime) -> Result<String, Error> {
+    format_time(time.replace_nanosecond(0).unwrap_or(time))
+}
+
+pub fn parse_time(value: &str) -> Result<OffsetDateTime, Error> {
+    OffsetDateTime::parse(value, &Rfc3339)
+        .map_err(|e| Error::InvalidInput(format!("Invalid time {}: {}", value, e)))
+}
+
+#[derive(Debug, Clone)]
+pub struct CompetitionStore {
+    db: Pool<Sqlite>,
+    settings: CompetitionSettings,
+    rulesets: Rulesets,
+    day_boundary: DayBoundary,
+    daily_schedule: Schedule,
+}
+
+impl CompetitionStore {
+    pub fn new(db: Pool<Sqlite>, settings: CompetitionSettings, rulesets: Rulesets) -> Self {
+        // The timezone and cutoff are checked when the settings are validated
+        let day_boundary = settings.day_boundary().unwrap_or_default();
+        let daily_schedule = day_boundary.schedule().unwrap_or(Schedule::Daily);
+
+        Self {
+            db,
+            settings,
+            rulesets,
+            day_boundary,
+            daily_schedule,
+        }
+    }
+
+    pub async fn ping(&self) -> Result<(), Error> {
+        sqlx::query!("SELECT 1 as ping").fetch_one(&self.db).await?;
+        Ok(())
+    }
+
+    pub async fn find_by_id(&self, id: i64) -> Result<Option<Competition>, Error> {
+        let competition = sqlx::query_as!(
+            Competition,
+            r#"
+            SELECT id, name, kind, start_time, end_time, entry_fee_sats, rake_percent,
+                ruleset_id, rules_version, seed, status, pot_sats, created_at, updated_at
+            FROM competitions
+            WHERE id = ?
+            "#,
+            id
+        )
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(competition)
+    }
+
+    pub async fn find_by_kind_and_start(
+        &self,
+        kind: &str,
+        start: OffsetDateTime,
+    ) -> Result<Option<Competition>, Error> {
+        let start_time = format_time(start)?;
+
+        let competition = sqlx::query_as!(
+            Competition,
+            r#"
+            SELECT id, name, kind, start_time, end_time, entry_fee_sats, rake_percent,
+                ruleset_id, rules_version, seed, status, pot_sats, created_at, updated_at
+            FROM competitions
+            WHERE kind = ? AND start_time = ?
+            "#,
+            kind,
+            start_time
+        )
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(competition)
+    }
+
+    // Find the daily competition for a date without creating it
+    pub async fn find_daily(&self, date: Date) -> Result<Option<Competition>, Error> {
+        self.find_by_kind_and_start(DAILY_KIND, self.day_boundary.start_of(date))
+            .await
+    }
+
+    // Where the daily competition's days start and end
+    pub fn day_boundary(&self) -> DayBoundary {
+        self.day_boundary
+    }
+
+    // Start of the round of a series running on a date, daily competitions count the date in
+    // their own timezone and the rest in UTC
+    pub fn start_on(
+        &self,
+        series: &CompetitionSeries,
+        date: Date,
+    ) -> Result<OffsetDateTime, Error> {
+        if series.kind == DAILY_KIND {
+            return Ok(self.day_boundary.start_of(date));
+        }
+
+        let (start, _) = series
+            .schedule
+            .period_containing(date.midnight().assume_utc())?;
+        Ok(start)
+    }
+
+    // Date a round of a series started on, the same way `start_on` counts dates
+    pub fn date_of(&self, kind: &str, start: OffsetDateTime) -> Date {
+        if kind == DAILY_KIND {
+            self.day_boundary.day_of(start)
+        } else {
+            start.date()
+        }
+    }
+
+    // Date a competition started on in YYYY-MM-DD format
+    pub fn competition_date(&self, competition: &Competition) -> Result<String, Error> {
+        Ok(self
+            .date_of(&competition.kind, competition.starts_at()?)
+            .to_string())
+    }
+
+    // The daily competition plus every configured tournament
+    pub fn series(&self) -> Vec<CompetitionSeries> {
+        let mut series = vec![CompetitionSeries {
+            kind: DAILY_KIND.to_string(),
+            name: String::from("Daily"),
+            schedule: self.daily_schedule.clone(),
+            entry_fee_sats: self.settings.entry_fee_sats,
+            rake_percent: self.settings.rake_percent,
+            ruleset: self.ruleset_for(None),
+        }];
+
+        series.extend(
+            self.settings
+                .tournaments
+                .iter()
+                .map(|tournament| CompetitionSeries {
+                    kind: tournament.key.clone(),
+                    name: tournament.name.clone(),
+                    schedule: tournament.schedule.clone(),
+                    entry_fee_sats: tournament.entry_fee_sats,
+                    rake_percent: tournament.rake_percent,
+                    ruleset: self.ruleset_for(tournament.ruleset.as_deref()),
+                }),
+        );
+
+        series
+    }
+
+    // Tournaments without a ruleset of their own follow the daily competition's
+    fn ruleset_for(&self, ruleset: Option<&str>) -> String {
+        ruleset
+            .or(self.settings.ruleset.as_deref())
+            .unwrap_or(&self.rulesets.default_ruleset().id)
+            .to_string()
+    }
+
+    pub fn find_series(&self, kind: &str) -> Option<CompetitionSeries> {
+        self.series().into_iter().find(|series| series.kind == kind)
+    }
+
+    // Bundles of games players can buy in any competition
+    pub fn bundles(&self) -> &[EntryBundle] {
+        &self.settings.bundles
+    }
+
+    // Fewest paying players a competition needs to be settled rather than voided
+    pub fn min_players(&self) -> i64 {
+        self.settings.min_players
+    }
+
+    // Competitions the operator has cancelled
+    pub fn voided(&self) -> &[VoidedCompetition] {
+        &self.settings.voided
+    }
+
+    pub fn find_bundle(&self, games: i64) -> Option<EntryBundle> {
+        self.settings
+            .bundles
+            .iter()
+            .find(|bundle| bundle.games == games)
+            .cloned()
+    }
+
+    // Get the round of a series running at `now`, opening it if nobody has played yet
+    pub async fn get_or_create(
+        &self,
+        series: &CompetitionSeries,
+        now: OffsetDateTime,
+    ) -> Result<Competition, Error> {
+        let (start, end) = series.schedule.period_containing(now)?;
+
+        if let Some(competition) = self.find_by_kind_and_start(&series.kind, start).await? {
+            return Ok(competition);
+        }
+
+        let ruleset = self.rulesets.latest(&series.ruleset)?;
+        let start_time = format_time(start)?;
+        let end_time = format_time(end)?;
+        let name = format!("{} {}", series.name, self.date_of(&series.kind, start));
+        let seed = new_seed();
+        let created_at = format_time(OffsetDateTime::now_utc())?;
+
+        // Another request may have opened it in the meantime, the unique index keeps one
+        sqlx::query!(
+            r#"
+            INSERT INTO competitions
+            (name, kind, start_time, end_time, entry_fee_sats, rake_percent, ruleset_id, rules_version, seed, status, pot_sats, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 0, ?, ?)
+            ON CONFLICT(kind, start_time) DO NOTHING
+            "#,
+            name,
+            series.kind,
+            start_time,
+            end_time,
+            series.entry_fee_sats,
+            series.rake_percent,
+            ruleset.id,
+            ruleset.version,
+            seed,
+            "open",
+            created_at,
+            created_at
+        )
+        .execute(&self.db)
+        .await?;
+
+        self.find_by_kind_and_start(&series.kind, start)
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("{} competition", name)))
+    }
+
+    // The round of a series currently accepting entries
+    pub async fn current(&self, kind: &str) -> Result<Competition, Error> {
+        let series = self
+            .find_series(kind)
+            .ok_or_else(|| Error::NotFound(format!("Unknown competition: {}", kind)))?;
+
+        self.get_or_create(&series, OffsetDateTime::now_utc()).await
+    }
+
+    // The daily competition currently accepting entries
+    pub async fn current_daily(&self) -> Result<Competition, Error> {
+        self.current(DAILY_KIND).await
+    }
+
+    // Every competition currently accepting entries
+    pub async fn current_competitions(&self) -> Result<Vec<Competition>, Error> {
+        let now = OffsetDateTime::now_utc();
+        let mut competitions = vec![];
+        for series in self.series() {
+            competitions.push(self.get_or_create(&series, now).await?);
+        }
+
+        Ok(competitions)
+    }
+
+    // When the first round of a series started, None if it has never been played
+    pub async fn first_start(&self, kind: &str) -> Result<Option<String>, Error> {
+        let result = sqlx::query!(
+            r#"
+            SELECT MIN(start_time) as "start_time: String"
+            FROM competitions
+            WHERE kind = ?
+            "#,
+            kind
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(result.start_time)
+    }
+
+    // Competitions that have ended but have not been settled yet
+    pub async fn get_due_for_settlement(
+        &self,
+        now: OffsetDateTime,
+    ) -> Result<Vec<Competition>, Error> {
+        let now = format_time(now)?;
+
+        let competitions = sqlx::query_as!(
+            Competition,
+            r#"
+            SELECT id, name, kind, start_time, end_time, entry_fee_sats, rake_percent,
+                ruleset_id, rules_version, seed, status, pot_sats, created_at, updated_at
+            FROM competitions
+            WHERE status IN ('open', 'closed', 'settling') AND end_time <= ?
+            ORDER BY end_time ASC
+            "#,
+            now
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(competitions)
+    }
+
+    // Move a competition on to a new status. Once it is no longer open the games still being
+    // played in it are abandoned, their scores would not count anymore
+    pub async fn update_status(&self, id: i64, status: &str) -> Result<(), Error> {
+        let now = OffsetDateTime::now_utc();
+        let updated_at = format_time(now)?;
+        let ended_at = format_time(now)?;
+
+        let mut tx = self.db.begin().await?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE competitions
+            SET status = ?, updated_at = ?
+            WHERE id = ?
+            "#,
+            status,
+            updated_at,
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound(format!("Competition not found: {}", id)));
+        }
+
+        if status != "open" {
+            sqlx::query!(
+                r#"
+                UPDATE game_sessions
+                SET status = 'abandoned', ended_at = ?
+                WHERE competition_id = ? AND status IN ('created', 'active')
+                "#,
+                ended_at,
+                id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // Count the paid entries for a competition
+    pub async fn count_paid_entries(&self, competition_id: i64) -> Result<i64, Error> {
+        let result = sqlx::query!(
+            r#"
+            SELECT COUNT(*) as count
+            FROM game_payments
+            WHERE competition_id = ? AND status = 'paid'
+            "#,
+            competition_id
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(result.count)
+    }
+
+    // Count the players who paid to enter a competition
+    pub async fn count_paying_players(&self, competition_id: i64) -> Result<i64, Error> {
+        let result = sqlx::query!(
+            r#"
+            SELECT COUNT(DISTINCT user_id) as count
+            FROM game_payments
+            WHERE competition_id = ? AND status = 'paid'
+            "#,
+            competition_id
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(result.count)
+    }
+
+    // Find the player with the highest score in a competition, the one heading its leaderboard
+    // so ties go the same way in both
+    pub async fn get_top_scorer(&self, competition_id: i64) -> Result<Option<TopScorer>, Error> {
+        let leader = self.get_leaderboard(competition_id, 1).await?;
+
+        Ok(leader.into_iter().next().map(|entry| TopScorer {
+            user_id: entry.user_id,
+            score: entry.score,
+            games_played: entry.games_played,
+            username: entry.username,
+        }))
+    }
+
+    // The prize awarded in a competition with its winner's name, None until it is settled
+    pub async fn get_prize(&self, competition_id: i64) -> Result<Option<CompetitionPrize>, Error> {
+        let prize = sqlx::query_as!(
+            CompetitionPrize,
+            r#"
+            SELECT u.username, p.score, p.amount_sats, p.status as "status: PayoutStatus",
+                p.payment_request, p.payment_preimage, p.paid_at
+            FROM prize_payouts p
+            JOIN users u ON p.user_id = u.id
+            WHERE p.competition_id = ?
+            "#,
+            competition_id
+        )
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(prize)
+    }
+
+    // Best score of each player in a competition, highest first. Players banned for the
+    // time being are left out, so they can neither win nor push anyone else down
+    pub async fn get_leaderboard(
+        &self,
+        competition_id: i64,
+        limit: i64,
+    ) -> Result<Vec<LeaderboardEntry>, Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                s.user_id,
+                u.username,
+                s.score,
+                s.level,
+                s.play_time,
+                (SELECT COUNT(*) FROM scores c
+                    WHERE c.competition_id = s.competition_id AND c.user_id = s.user_id
+                        AND c.voided_at IS NULL) as "games_played!: i64"
+            FROM scores s
+            JOIN users u ON s.user_id = u.id
+            WHERE s.competition_id = ?
+                AND s.id = (
+                    SELECT b.id FROM scores b
+                    WHERE b.competition_id = s.competition_id AND b.user_id = s.user_id
+                        AND b.voided_at IS NULL
+                    ORDER BY b.score DESC, b.id ASC
+                    LIMIT 1
+                )
+                AND NOT EXISTS (
+                    SELECT 1 FROM active_bans x WHERE x.pubkey = u.nostr_pubkey
+                )
+            ORDER BY s.score DESC, s.id ASC
+            LIMIT ?
+            "#,
+            competition_id,
+            limit
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        let entries = rows
+            .into_iter()
+            .enumerate()
+            .map(|(index, row)| LeaderboardEntry {
+                rank: index as i64 + 1,
+                user_id: row.user_id,
+                username: row.username,
+                score: row.score,
+                level: row.level,
+                play_time: row.play_time,
+                games_played: row.games_played,
+            })
+            .collect();
+
+        Ok(entries)
+    }
+}
+
+fn new_seed() -> String {
+    let mut bytes = [0u8; 16];
+    thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        domain::testing::{
+            create_test_user, pay_entry, test_competition_store, test_db, test_game_store,
+        },
+        Assessment, GameStore, PaymentStore, Score, UserStore,
+    };
+    use time::Duration;
+
+    // Play one paid game of a competition
+    async fn play(games: &GameStore, user_id: i64, competition_id: i64, score: i64) -> Score {
+        let session = games
+            .create_paid_session(user_id, competition_id)
+            .await
+            .unwrap()
+            .unwrap();
+        games
+            .submit_score(
+                &session,
+                score,
+                3,
+                60,
+                &Assessment::default(),
+                OffsetDateTime::now_utc(),
+            )
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_tied_scores_go_to_the_first_to_reach_them() {
+        let db = test_db().await;
+        let games = test_game_store(&db);
+        let competitions = test_competition_store(&db);
+        let competition = competitions.current_daily().await.unwrap();
+        let first = create_test_user(&db).await;
+        let second = create_test_user(&db).await;
+        pay_entry(&db, first.id, competition.id, 1000, 2).await;
+        pay_entry(&db, second.id, competition.id, 500, 1).await;
+
+        // The first player scores early but only ties with a later game, after the second
+        // player had already reached the same score
+        let now = OffsetDateTime::now_utc();
+        for (user_id, score) in [(first.id, 100), (second.id, 500), (first.id, 500)] {
+            let session = games
+                .create_paid_session(user_id, competition.id)
+                .await
+                .unwrap()
+                .unwrap();
+            games
+                .submit_score(&session, score, 3, 60, &Assessment::default(), now)
+                .await
+                .unwrap();
+        }
+
+        let leaderboard = competitions
+            .get_leaderboard(competition.id, 10)
+            .await
+            .unwrap();
+        let top_scorer = competitions
+            .get_top_scorer(competition.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(leaderboard[0].user_id, second.id);
+        assert_eq!(top_scorer.user_id, second.id);
+        assert_eq!(top_scorer.score, 500);
+        assert_eq!(leaderboard[1].user_id, first.id);
+        assert_eq!(leaderboard[1].games_played, 2);
+    }
+
+    #[tokio::test]
+    async fn test_banned_players_leave_the_leaderboard() {
+        let db = test_db().await;
+        let games = test_game_store(&db);
+        let users = UserStore::new(db.clone());
+        let competitions = test_competition_store(&db);
+        let competition = competitions.current_daily().await.unwrap();
+        let cheat = create_test_user(&db).await;
+        let honest = create_test_user(&db).await;
+        pay_entry(&db, cheat.id, competition.id, 500, 1).await;
+        pay_entry(&db, honest.id, competition.id, 500, 1).await;
+        play(&games, cheat.id, competition.id, 2000).await;
+        play(&games, honest.id, competition.id, 1000).await;
+
+        users
+            .ban(&cheat.nostr_pubkey, "Cheating", "admin", None)
+            .await
+            .unwrap()
+            .unwrap();
+        let leaderboard = competitions
+            .get_leaderboard(competition.id, 10)
+            .await
+            .unwrap();
+        assert_eq!(leaderboard.len(), 1);
+        assert_eq!(leaderboard[0].user_id, honest.id);
+        assert_eq!(leaderboard[0].rank, 1);
+        assert_eq!(
+            competitions
+                .get_top_scorer(competition.id)
+                .await
+                .unwrap()
+                .unwrap()
+                .user_id,
+            honest.id
+        );
+        let top_scores = games.get_top_scores(10).await.unwrap();
+        assert_eq!(
+            top_scores
+                .iter()
+                .map(|score| score.score)
+                .collect::<Vec<_>>(),
+            vec![1000]
+        );
+
+        // Lifting the ban puts the scores back
+        assert!(users.lift_ban(&cheat.nostr_pubkey, "admin").await.unwrap());
+        assert_eq!(
+            competitions
+                .get_top_scorer(competition.id)
+                .await
+                .unwrap()
+                .unwrap()
+                .user_id,
+            cheat.id
+        );
+
+        // So does a ban running out
+        let expires_at = OffsetDateTime::now_utc() - Duration::seconds(1);
+        users
+            .ban(&cheat.nostr_pubkey, "Cheating", "admin", Some(expires_at))
+            .await
+            .unwrap();
+        assert_eq!(games.get_top_scores(10).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_voided_scores_leave_the_leaderboard() {
+        let db = test_db().await;
+        let games = test_game_store(&db);
+        let competitions = test_competition_store(&db);
+        let competition = competitions.current_daily().await.unwrap();
+        let user = create_test_user(&db).await;
+        let other = create_test_user(&db).await;
+        pay_entry(&db, user.id, competition.id, 1000, 2).await;
+        pay_entry(&db, other.id, competition.id, 500, 1).await;
+        let best = play(&games, user.id, competition.id, 3000).await;
+        play(&games, user.id, competition.id, 500).await;
+        play(&games, other.id, competition.id, 1000).await;
+
+        games
+            .void_score(best.id, "Impossible score", "admin")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(games
+            .void_score(best.id, "Impossible score", "admin")
+            .await
+            .unwrap()
+            .is_none());
+
+        // The player keeps their other games, ranked by the best one left
+        let leaderboard = competitions
+            .get_leaderboard(competition.id, 10)
+            .await
+            .unwrap();
+        assert_eq!(
+            leaderboard
+                .iter()
+                .map(|entry| (entry.user_id, entry.score, entry.games_played))
+                .collect::<Vec<_>>(),
+            vec![(other.id, 1000, 1), (user.id, 500, 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prizes_of_banned_winners_are_resettled() {
+        let db = test_db().await;
+        let games = test_game_store(&db);
+        let users = UserStore::new(db.clone());
+        let payments = PaymentStore::new(db.clone());
+        let competitions = test_competition_store(&db);
+        let competition = competitions.current_daily().await.unwrap();
+        let cheat = create_test_user(&db).await;
+        let honest = create_test_user(&db).await;
+        pay_entry(&db, cheat.id, competition.id, 500, 1).await;
+        pay_entry(&db, honest.id, competition.id, 500, 1).await;
+        play(&games, cheat.id, competition.id, 2000).await;
+        play(&games, honest.id, competition.id, 1000).await;
+
+        let competition = competitions
+            .find_by_id(competition.id)
+            .await
+            .unwrap()
+            .unwrap();
+        let winner = competitions
+            .get_top_scorer(competition.id)
+            .await
+            .unwrap()
+            .unwrap();
+        let awarded = payments
+            .record_winner(competition.id, "2025-01-01", winner.user_id, winner.score)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(awarded.user_id, cheat.id);
+        assert_eq!(payments.get_user_balance(cheat.id).await.unwrap(), 900);
+
+        // Once the winner is banned the next best player takes the prize and its winnings
+        users
+            .ban(&cheat.nostr_pubkey, "Cheating", "admin", None)
+            .await
+            .unwrap()
+            .unwrap();
+        let scorer = competitions
+            .get_top_scorer(competition.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(scorer.user_id, honest.id);
+        let prize = payments
+            .reassign_prize(&awarded, scorer.user_id, scorer.score)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(prize.user_id, honest.id);
+        assert_eq!(prize.score, 1000);
+        assert_eq!(payments.get_user_balance(cheat.id).await.unwrap(), 0);
+        assert_eq!(payments.get_user_balance(honest.id).await.unwrap(), 900);
+
+        // Re-settling again from the old winner finds nothing left to move
+        assert!(payments
+            .reassign_prize(&awarded, honest.id, 1000)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,258 @@
+use serde::{Deserialize, Serialize};
+
+/// How far a player has got in a game, as reported when asking for a fresh config
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    pub level: i64,
+    pub score: i64,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Progress { level: 1, score: 0 }
+    }
+}
+
+impl Progress {
+    // Anything the client leaves out counts as the start of a game
+    pub fn new(level: Option<i64>, score: Option<i64>) -> Self {
+        Progress {
+            level: level.unwrap_or(1).max(1),
+            score: score.unwrap_or(0).max(0),
+        }
+    }
+}
+
+/// How the difficulty factor a config is scaled by grows as a game goes on. Every curve
+/// starts at 1 on the first level and never goes below 1 or above its `max`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "curve",
+    rename_all = "snake_case",
+    rename_all_fields = "camelCase"
+)]
+pub enum DifficultyCurve {
+    /// Grows by the same amount every level
+    Linear { per_level: f64, max: f64 },
+    /// Holds for a number of levels, then jumps by `step`
+    Stepped {
+        every_levels: i64,
+        step: f64,
+        max: f64,
+    },
+    /// Grows by a factor of `growth` every level
+    Exponential { growth: f64, max: f64 },
+    /// Linear in the level, pushed up for players scoring above `target_points_per_level`
+    /// and eased off for those below it, by `sensitivity` times the difference
+    ScoreAdaptive {
+        per_level: f64,
+        target_points_per_level: i64,
+        sensitivity: f64,
+        max: f64,
+    },
+}
+
+impl Default for DifficultyCurve {
+    fn default() -> Self {
+        DifficultyCurve::Linear {
+            per_level: 0.1,
+            max: 3.0,
+        }
+    }
+}
+
+impl DifficultyCurve {
+    pub fn factor(&self, progress: Progress) -> f64 {
+        let levels_cleared = (progress.level.max(1) - 1) as f64;
+
+        let (factor, max) = match self {
+            DifficultyCurve::Linear { per_level, max } => (1.0 + per_level * levels_cleared, *max),
+            DifficultyCurve::Stepped {
+                every_levels,
+                step,
+                max,
+            } => {
+                let steps = (levels_cleared / *every_levels as f64).floor();
+                (1.0 + step * steps, *max)
+            }
+            DifficultyCurve::Exponential { growth, max } => (growth.powf(levels_cleared), *max),
+            DifficultyCurve::ScoreAdaptive {
+                per_level,
+                target_points_per_level,
+                sensitivity,
+                max,
+            } => {
+                let expected = (target_points_per_level * progress.level.max(1)) as f64;
+                let performance = progress.score.max(0) as f64 / expected;
+                let base = 1.0 + per_level * levels_cleared;
+                (base * (1.0 + sensitivity * (performance - 1.0)), *max)
+            }
+        };
+
+        factor.clamp(1.0, max)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let max = match self {
+            DifficultyCurve::Linear { per_level, max } => {
+                if *per_level < 0.0 {
+                    return Err(String::from("linear difficulty cannot shrink per level"));
+                }
+                max
+            }
+            DifficultyCurve::Stepped {
+                every_levels,
+                step,
+                max,
+            } => {
+                if *every_levels < 1 || *step < 0.0 {
+                    return Err(String::from(
+                        "stepped difficulty needs at least 1 level per step and a step from 0",
+                    ));
+                }
+                max
+            }
+            DifficultyCurve::Exponential { growth, max } => {
+                if *growth < 1.0 {
+                    return Err(String::from("exponential difficulty growth must be from 1"));
+                }
+                max
+            }
+            DifficultyCurve::ScoreAdaptive {
+                per_level,
+                target_points_per_level,
+                sensitivity,
+                max,
+            } => {
+                if *per_level < 0.0 || *target_points_per_level < 1 || *sensitivity < 0.0 {
+                    return Err(String::from(
+                        "adaptive difficulty needs a target above 0 and no negative rates",
+                    ));
+                }
+                max
+            }
+        };
+
+        if !max.is_finite() || *max < 1.0 {
+            return Err(String::from("maximum difficulty must be from 1"));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at_level(level: i64) -> Progress {
+        Progress { level, score: 0 }
+    }
+
+    #[test]
+    fn test_curves_start_at_one_and_stop_at_max() {
+        let curves = [
+            DifficultyCurve::default(),
+            DifficultyCurve::Stepped {
+                every_levels: 3,
+                step: 0.5,
+                max: 2.0,
+            },
+            DifficultyCurve::Exponential {
+                growth: 1.2,
+                max: 2.5,
+            },
+            DifficultyCurve::ScoreAdaptive {
+                per_level: 0.1,
+                target_points_per_level: 100,
+                sensitivity: 0.5,
+                max: 3.0,
+            },
+        ];
+
+        for curve in curves {
+            assert!(curve.validate().is_ok());
+            assert_eq!(curve.factor(at_level(1)), 1.0);
+            assert!(curve.factor(at_level(1000)) <= 3.0);
+            assert!(curve.factor(at_level(0)) >= 1.0);
+        }
+    }
+
+    #[test]
+    fn test_linear_and_exponential_grow_smoothly() {
+        let linear = DifficultyCurve::default();
+        assert!((linear.factor(at_level(6)) - 1.5).abs() < 1e-9);
+        assert_eq!(linear.factor(at_level(50)), 3.0);
+
+        let exponential = DifficultyCurve::Exponential {
+            growth: 1.1,
+            max: 10.0,
+        };
+        assert!((exponential.factor(at_level(3)) - 1.21).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_stepped_holds_between_steps() {
+        let stepped = DifficultyCurve::Stepped {
+            every_levels: 3,
+            step: 0.25,
+            max: 3.0,
+        };
+
+        assert_eq!(stepped.factor(at_level(3)), 1.0);
+        assert_eq!(stepped.factor(at_level(4)), 1.25);
+        assert_eq!(stepped.factor(at_level(6)), 1.25);
+        assert_eq!(stepped.factor(at_level(7)), 1.5);
+    }
+
+    #[test]
+    fn test_adaptive_follows_score() {
+        let adaptive = DifficultyCurve::ScoreAdaptive {
+            per_level: 0.1,
+            target_points_per_level: 100,
+            sensitivity: 0.5,
+            max: 3.0,
+        };
+
+        let on_target = adaptive.factor(Progress {
+            level: 3,
+            score: 300,
+        });
+        let ahead = adaptive.factor(Progress {
+            level: 3,
+            score: 600,
+        });
+        let behind = adaptive.factor(Progress {
+            level: 3,
+            score: 150,
+        });
+
+        assert!((on_target - 1.2).abs() < 1e-9);
+        assert!(ahead > on_target);
+        assert!(behind < on_target);
+        assert!(behind >= 1.0);
+    }
+
+    #[test]
+    fn test_invalid_curves_are_refused() {
+        assert!(DifficultyCurve::Linear {
+            per_level: 0.1,
+            max: 0.5
+        }
+        .validate()
+        .is_err());
+        assert!(DifficultyCurve::Stepped {
+            every_levels: 0,
+            step: 0.1,
+            max: 2.0
+        }
+        .validate()
+        .is_err());
+        assert!(DifficultyCurve::Exponential {
+            growth: 0.9,
+            max: 2.0
+        }
+        .validate()
+        .is_err());
+    }
+}
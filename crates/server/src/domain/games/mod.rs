@@ -1,5 +1,13 @@
+mod difficulty;
+mod plausibility;
 mod routes;
+mod rulesets;
+mod signing;
 mod store;
 
+pub use difficulty::*;
+pub use plausibility::*;
 pub use routes::*;
+pub use rulesets::*;
+pub use signing::*;
 pub use store::*;
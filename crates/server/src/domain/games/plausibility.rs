@@ -0,0 +1,255 @@
+use serde::{Deserialize, Serialize};
+
+use super::{rulesets::Ruleset, store::build_game_config};
+
+/// Seconds a reported play time may run over the time the server has seen the session open
+const PLAY_TIME_GRACE_SECS: i64 = 5;
+/// Sustained rate of destroyed asteroids above which a game looks scripted
+const SUSPICIOUS_KILLS_PER_SECOND: f64 = 3.0;
+/// Games longer than this refresh their config at least once along the way
+const CONFIG_REFRESH_SECS: i64 = 60;
+
+/// What the server knows about a session when its score comes in
+#[derive(Clone, Debug)]
+pub struct SessionFacts {
+    /// Seconds between starting the session and the score arriving, the authoritative play time
+    pub elapsed_secs: i64,
+    /// Highest difficulty any config handed out for the session was built with
+    pub max_difficulty: f64,
+    /// Whether the player came back for a fresh config after the first one
+    pub config_refreshed: bool,
+    /// Scores already submitted for the session
+    pub previous_scores: i64,
+    /// Rules the session was played under
+    pub ruleset: Ruleset,
+}
+
+/// A reason a submitted score cannot have come from a real game, or looks like it didn't
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Violation {
+    /// Negative numbers, or a level below the first
+    OutOfRange,
+    /// The session already has a score
+    DuplicateScore,
+    /// Played longer than the session has existed
+    PlayTimeExceedsSession { play_time: i64, elapsed_secs: i64 },
+    /// More points than clearing every level up to the reported one could give
+    ScoreAboveMaximum { max_score: i64 },
+    /// Too few points to have reached the reported level
+    ScoreBelowLevel { min_score: i64 },
+    /// Asteroids destroyed faster than a person plays
+    KillRate { kills_per_second: f64 },
+    /// A long game that never asked for the config updates the client fetches while playing
+    ConfigNotRefreshed,
+}
+
+impl Violation {
+    /// Violations that are impossible in a real game, the score is turned away
+    pub fn is_impossible(&self) -> bool {
+        !matches!(
+            self,
+            Violation::KillRate { .. } | Violation::ConfigNotRefreshed
+        )
+    }
+
+    // How much a merely suspicious violation adds to the suspicion score
+    fn weight(&self) -> f64 {
+        match self {
+            Violation::KillRate { kills_per_second } => {
+                ((kills_per_second - SUSPICIOUS_KILLS_PER_SECOND) / SUSPICIOUS_KILLS_PER_SECOND)
+                    .clamp(0.25, 1.0)
+            }
+            Violation::ConfigNotRefreshed => 0.3,
+            _ => 1.0,
+        }
+    }
+}
+
+/// Outcome of checking a score against the session it was played in
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Assessment {
+    /// From 0 for nothing unusual up to 1, kept with the score for admins to review
+    pub suspicion: f64,
+    pub violations: Vec<Violation>,
+}
+
+impl Assessment {
+    pub fn is_rejected(&self) -> bool {
+        self.violations.iter().any(Violation::is_impossible)
+    }
+}
+
+/// Check a submitted score against what the session's configs allow in the time it ran
+pub fn assess_score(score: i64, level: i64, play_time: i64, session: &SessionFacts) -> Assessment {
+    let mut violations = Vec::new();
+
+    if score < 0 || level < 1 || play_time < 0 {
+        violations.push(Violation::OutOfRange);
+        return Assessment {
+            suspicion: 1.0,
+            violations,
+        };
+    }
+
+    if session.previous_scores > 0 {
+        violations.push(Violation::DuplicateScore);
+    }
+
+    if play_time > session.elapsed_secs + PLAY_TIME_GRACE_SECS {
+        violations.push(Violation::PlayTimeExceedsSession {
+            play_time,
+            elapsed_secs: session.elapsed_secs,
+        });
+    }
+
+    let max_score = max_score(&session.ruleset, level, session.max_difficulty);
+    if score > max_score {
+        violations.push(Violation::ScoreAboveMaximum { max_score });
+    }
+
+    let min_score = min_score(&session.ruleset, level);
+    if score < min_score {
+        violations.push(Violation::ScoreBelowLevel { min_score });
+    }
+
+    // Measured against the time the server saw the session running, not the client's count
+    let kills_per_second = min_kills(&session.ruleset, score, level, session.max_difficulty) as f64
+        / session.elapsed_secs.max(1) as f64;
+    if kills_per_second > SUSPICIOUS_KILLS_PER_SECOND {
+        violations.push(Violation::KillRate { kills_per_second });
+    }
+
+    if play_time > CONFIG_REFRESH_SECS && !session.config_refreshed {
+        violations.push(Violation::ConfigNotRefreshed);
+    }
+
+    let suspicion = violations
+        .iter()
+        .map(Violation::weight)
+        .sum::<f64>()
+        .min(1.0);
+
+    Assessment {
+        suspicion,
+        violations,
+    }
+}
+
+// Asteroids on screen at a level and the points each is worth there, the same way the
+// client works them out from its config
+fn asteroids_and_points(ruleset: &Ruleset, level: i64, difficulty: f64) -> (i64, i64) {
+    let config = build_game_config(ruleset, String::new(), "", difficulty, false);
+    let asteroids = (config.asteroids.initial_count as f64 * (level as f64).sqrt()) as i64;
+    let points = config.scoring.points_per_asteroid as i64 * level;
+    (asteroids, points)
+}
+
+// Clearing every level up to and including this one at the highest difficulty seen
+fn max_score(ruleset: &Ruleset, level: i64, max_difficulty: f64) -> i64 {
+    (1..=level)
+        .map(|level| {
+            let (asteroids, points) = asteroids_and_points(ruleset, level, max_difficulty);
+            asteroids * points
+        })
+        .sum()
+}
+
+// Clearing every level before this one at the starting difficulty
+fn min_score(ruleset: &Ruleset, level: i64) -> i64 {
+    (1..level)
+        .map(|level| {
+            let (asteroids, points) = asteroids_and_points(ruleset, level, 1.0);
+            asteroids * points
+        })
+        .sum()
+}
+
+// Fewest asteroids that could have been destroyed to end up at this score and level
+fn min_kills(ruleset: &Ruleset, score: i64, level: i64, max_difficulty: f64) -> i64 {
+    let cleared: i64 = (1..level)
+        .map(|level| asteroids_and_points(ruleset, level, 1.0).0)
+        .sum();
+    let (_, best_points) = asteroids_and_points(ruleset, level, max_difficulty);
+    cleared.max(score / best_points.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(elapsed_secs: i64) -> SessionFacts {
+        SessionFacts {
+            elapsed_secs,
+            max_difficulty: 1.0,
+            config_refreshed: true,
+            previous_scores: 0,
+            ruleset: Ruleset::default(),
+        }
+    }
+
+    #[test]
+    fn test_plausible_game_passes() {
+        // Level 1 has 5 asteroids worth 10 each, level 2 has 7 worth 20
+        let assessment = assess_score(50 + 3 * 20, 2, 40, &session(45));
+
+        assert_eq!(assessment, Assessment::default());
+        assert!(!assessment.is_rejected());
+    }
+
+    #[test]
+    fn test_score_limits_follow_level_and_difficulty() {
+        assert_eq!(max_score(&Ruleset::default(), 2, 1.0), 50 + 7 * 20);
+        assert_eq!(min_score(&Ruleset::default(), 2), 50);
+
+        let assessment = assess_score(50 + 7 * 20 + 10, 2, 120, &session(130));
+        assert!(assessment.is_rejected());
+        assert!(assessment
+            .violations
+            .contains(&Violation::ScoreAboveMaximum { max_score: 190 }));
+
+        // Harder configs hand out more points per asteroid
+        let harder = SessionFacts {
+            max_difficulty: 2.0,
+            ..session(130)
+        };
+        assert!(!assess_score(200, 2, 120, &harder).is_rejected());
+
+        let assessment = assess_score(40, 2, 120, &session(130));
+        assert!(assessment
+            .violations
+            .contains(&Violation::ScoreBelowLevel { min_score: 50 }));
+    }
+
+    #[test]
+    fn test_impossible_sessions_are_rejected() {
+        let assessment = assess_score(50, 2, 600, &session(30));
+        assert!(assessment.is_rejected());
+
+        let replayed = SessionFacts {
+            previous_scores: 1,
+            ..session(60)
+        };
+        assert!(assess_score(50, 2, 30, &replayed).is_rejected());
+
+        assert!(assess_score(-10, 1, 30, &session(60)).is_rejected());
+    }
+
+    #[test]
+    fn test_suspicious_games_are_flagged_not_rejected() {
+        // Clearing 5 levels in 6 seconds is possible on paper but not by hand
+        let score = max_score(&Ruleset::default(), 5, 1.0);
+        let assessment = assess_score(score, 5, 5, &session(6));
+        assert!(!assessment.is_rejected());
+        assert!(assessment.suspicion > 0.0);
+
+        let stale = SessionFacts {
+            config_refreshed: false,
+            ..session(300)
+        };
+        let assessment = assess_score(50, 2, 240, &stale);
+        assert_eq!(assessment.violations, vec![Violation::ConfigNotRefreshed]);
+        assert!(!assessment.is_rejected());
+        assert!((assessment.suspicion - 0.3).abs() < f64::EPSILON);
+    }
+}
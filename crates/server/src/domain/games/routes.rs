@@ -1,26 +1,58 @@
 use axum::{
-    extract::{Query, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::{IntoResponse, Response},
     Json,
 };
-use log::{error, info};
+use log::{error, info, warn};
+use nostr_sdk::ToBech32;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::sync::Arc;
+use time::{Duration, OffsetDateTime};
 
-use crate::{map_error, nostr_extractor::NostrAuth, startup::AppState, PaymentStatus};
+use crate::{
+    assess_score, invoice_expires_at, map_error,
+    nostr_extractor::{NostrAuth, OptionalNostrAuth},
+    startup::AppState,
+    Competition, EntryBundle, GamePayment, PaymentStatus, Progress, DAILY_KIND,
+};
 
-use super::store::GameConfigResponse;
+use super::store::{GameConfigResponse, PracticeSession};
 
 #[derive(Debug, Deserialize)]
 pub struct ConfigQuery {
     pub session_id: Option<String>,
+    /// Level the player has reached, the next config's difficulty follows it
+    pub level: Option<i64>,
+    pub score: Option<i64>,
+}
+
+impl ConfigQuery {
+    fn progress(&self) -> Progress {
+        Progress::new(self.level, self.score)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScoresQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewSessionQuery {
+    pub competition: Option<String>,
+    /// Size of the bundle to buy when the player has no credits left
+    pub games: Option<i64>,
+    /// Pay for the bundle from the player's sats balance instead of a new invoice
+    pub use_balance: Option<bool>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct NewSessionResponse {
     pub config: GameConfigResponse,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credits_remaining: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -64,17 +96,35 @@ pub async fn get_game_config(
     };
 
     // Use existing session or create new one
+    let progress = query.progress();
     if let Some(session_id) = query.session_id {
+        // Practice sessions handed out below are refreshed through the practice flow
+        match state.game_store.find_practice_session(&session_id).await {
+            Ok(Some(_)) => {
+                return refresh_practice_config(&state, &session_id, Some(user.id), progress).await
+            }
+            Ok(None) => {}
+            Err(e) => return Err(map_error(e)),
+        }
+
+        let session = match state.game_store.find_session(&session_id).await {
+            Ok(Some(session)) => session,
+            Ok(None) => return Err((StatusCode::NOT_FOUND, "Session not found").into_response()),
+            Err(e) => return Err(map_error(e)),
+        };
+        if session.user_id != user.id {
+            return Err(
+                (StatusCode::FORBIDDEN, "Session belongs to a different user").into_response(),
+            );
+        }
+
         // Update existing session
-        match state.game_store.update_session_activity(&session_id).await {
+        match state
+            .game_store
+            .update_session_activity(&session, progress)
+            .await
+        {
             Ok(session) => {
-                if session.user_id != user.id {
-                    return Err(
-                        (StatusCode::FORBIDDEN, "Session belongs to a different user")
-                            .into_response(),
-                    );
-                }
-
                 // Get config for this session
                 match state.game_store.create_game_config(&session).await {
                     Ok(config) => Ok((StatusCode::OK, Json(config))),
@@ -84,25 +134,186 @@ pub async fn get_game_config(
             Err(e) => Err(map_error(e)),
         }
     } else {
-        // Create a new session
-        match state.game_store.create_session(user.id).await {
-            Ok(session) => match state.game_store.create_game_config(&session).await {
-                Ok(config) => Ok((StatusCode::OK, Json(config))),
-                Err(e) => Err(map_error(e)),
-            },
+        // Sessions started without paying are practice games and never enter a competition
+        match state
+            .game_store
+            .create_practice_session(Some(user.id))
+            .await
+            .and_then(|session| state.game_store.create_practice_config(&session))
+        {
+            Ok(config) => Ok((StatusCode::OK, Json(config))),
             Err(e) => Err(map_error(e)),
         }
     }
 }
 
+// Start a free practice game, open to anonymous players
+pub async fn start_practice_session(
+    OptionalNostrAuth(auth): OptionalNostrAuth,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    let user_id = find_practice_user(&state, auth).await?;
+    info!("New practice session request from user_id: {:?}", user_id);
+
+    match state
+        .game_store
+        .create_practice_session(user_id)
+        .await
+        .and_then(|session| state.game_store.create_practice_config(&session))
+    {
+        Ok(config) => Ok((
+            StatusCode::CREATED,
+            Json(NewSessionResponse {
+                config,
+                credits_remaining: None,
+            }),
+        )),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// Get an updated config for a running practice game
+pub async fn get_practice_config(
+    OptionalNostrAuth(auth): OptionalNostrAuth,
+    Query(query): Query<ConfigQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    let user_id = find_practice_user(&state, auth).await?;
+    info!("Practice config request from user_id: {:?}", user_id);
+
+    let progress = query.progress();
+    let Some(session_id) = query.session_id else {
+        return match state
+            .game_store
+            .create_practice_session(user_id)
+            .await
+            .and_then(|session| state.game_store.create_practice_config(&session))
+        {
+            Ok(config) => Ok((StatusCode::OK, Json(config))),
+            Err(e) => Err(map_error(e)),
+        };
+    };
+
+    refresh_practice_config(&state, &session_id, user_id, progress).await
+}
+
+async fn refresh_practice_config(
+    state: &AppState,
+    session_id: &str,
+    user_id: Option<i64>,
+    progress: Progress,
+) -> Result<(StatusCode, Json<GameConfigResponse>), Response> {
+    let session = match state.game_store.find_practice_session(session_id).await {
+        Ok(Some(session)) => session,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, "Session not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    if !is_practice_owner(&session, user_id) {
+        return Err((StatusCode::FORBIDDEN, "Session belongs to a different user").into_response());
+    }
+
+    match state
+        .game_store
+        .update_practice_session_activity(&session, progress)
+        .await
+        .and_then(|session| state.game_store.create_practice_config(&session))
+    {
+        Ok(config) => Ok((StatusCode::OK, Json(config))),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// Submit the score of a practice game, it is kept apart from competition scores
+pub async fn submit_practice_score(
+    OptionalNostrAuth(auth): OptionalNostrAuth,
+    State(state): State<Arc<AppState>>,
+    Json(submission): Json<ScoreSubmission>,
+) -> Result<impl IntoResponse, Response> {
+    let user_id = find_practice_user(&state, auth).await?;
+    info!("Practice score submission from user_id: {:?}", user_id);
+
+    let session = match state
+        .game_store
+        .find_practice_session(&submission.session_id)
+        .await
+    {
+        Ok(Some(session)) => session,
+        Ok(None) => {
+            info!("Practice session not found: {}", submission.session_id);
+            return Err((StatusCode::NOT_FOUND, "Session not found").into_response());
+        }
+        Err(e) => return Err(map_error(e)),
+    };
+
+    if !is_practice_owner(&session, user_id) {
+        return Err((StatusCode::FORBIDDEN, "Session belongs to a different user").into_response());
+    }
+
+    match state
+        .game_store
+        .submit_practice_score(
+            &session,
+            submission.score,
+            submission.level,
+            submission.play_time,
+        )
+        .await
+    {
+        Ok(score) => Ok((
+            StatusCode::CREATED,
+            Json(ScoreResponse {
+                id: score.id,
+                score: score.score,
+                level: score.level,
+                play_time: score.play_time,
+                created_at: score.created_at,
+            }),
+        )),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// Registered players keep their practice games, everyone else plays anonymously
+async fn find_practice_user(
+    state: &AppState,
+    auth: Option<NostrAuth>,
+) -> Result<Option<i64>, Response> {
+    let Some(auth) = auth else {
+        return Ok(None);
+    };
+
+    match state
+        .user_store
+        .find_by_pubkey(auth.pubkey.to_string())
+        .await
+    {
+        Ok(user) => Ok(user.map(|user| user.id)),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// Anonymous practice sessions can be used by anyone holding the session id
+fn is_practice_owner(session: &PracticeSession, user_id: Option<i64>) -> bool {
+    session.user_id.is_none() || session.user_id == user_id
+}
+
 // Create a new game session
 pub async fn start_new_session(
     auth: NostrAuth,
+    Query(query): Query<NewSessionQuery>,
     State(state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, Response> {
     let pubkey = auth.pubkey.to_string();
     info!("New session request from pubkey: {}", pubkey);
 
+    // Banned players can no longer play
+    state
+        .user_store
+        .check_not_banned(&pubkey)
+        .await
+        .map_err(map_error)?;
+
     // Find user
     let user = match state.user_store.find_by_pubkey(pubkey.clone()).await {
         Ok(Some(user)) => user,
@@ -110,130 +321,83 @@ pub async fn start_new_session(
         Err(e) => return Err(map_error(e)),
     };
 
-    // Check if the user has a valid payment within the last hour
-    let has_valid_payment = match state.payment_store.has_valid_payment(user.id).await {
-        Ok(valid) => valid,
+    // Sessions and payments are made against the running round of the chosen
+    // competition, the daily one unless a tournament was asked for
+    let kind = query.competition.as_deref().unwrap_or(DAILY_KIND);
+    let competition = match state.competition_store.current(kind).await {
+        Ok(competition) => competition,
         Err(e) => return Err(map_error(e)),
     };
 
-    if has_valid_payment {
-        // User has a valid payment, create a new session
-        match state.game_store.create_session(user.id).await {
-            Ok(session) => match state.game_store.create_game_config(&session).await {
-                Ok(config) => {
-                    return Ok((StatusCode::CREATED, Json(NewSessionResponse { config })))
-                }
-                Err(e) => return Err(map_error(e)),
-            },
+    if competition.status != "open" {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("{} is no longer accepting entries", competition.name),
+        )
+            .into_response());
+    }
+
+    let games = query.games.unwrap_or(1);
+    let Some(bundle) = state.competition_store.find_bundle(games) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("No bundle of {} games is on offer", games),
+        )
+            .into_response());
+    };
+
+    // Every game costs one credit, use one the user already paid for if there is any left
+    if let Some(response) = create_competition_session(&state, user.id, competition.id).await? {
+        return Ok(response);
+    }
+
+    // Winnings can pay for the next bundle without going through Lightning
+    if query.use_balance.unwrap_or(false) {
+        let amount_sats = bundle.price_sats(competition.entry_fee_sats);
+        match state
+            .payment_store
+            .buy_credits_with_balance(user.id, competition.id, amount_sats, bundle.games)
+            .await
+        {
+            Ok(Some(_)) => state.live_board.invalidate(),
+            Ok(None) => {
+                return Err((
+                    StatusCode::PAYMENT_REQUIRED,
+                    Json(json!({
+                        "error": "Insufficient balance",
+                        "amount_sats": amount_sats,
+                        "credits": bundle.games
+                    })),
+                )
+                    .into_response());
+            }
             Err(e) => return Err(map_error(e)),
         }
+
+        if let Some(response) = create_competition_session(&state, user.id, competition.id).await? {
+            return Ok(response);
+        }
     }
 
-    // Check if user has a pending payment
+    // Check if user has a pending payment for this bundle, invoices for other bundles are left
+    // to be paid or expire by themselves
     let pending_payment = match state
         .payment_store
-        .get_pending_payment_for_user(user.id)
+        .get_pending_payment_for_user(
+            user.id,
+            competition.id,
+            bundle.games,
+            bundle.price_sats(competition.entry_fee_sats),
+        )
         .await
     {
         Ok(Some(payment)) => payment,
         Ok(None) => {
             // No pending payment, create a new invoice
             info!("Creating new payment invoice for user_id: {}", user.id);
-
-            let description = format!("Asteroids Game Entry Fee - User:{}", pubkey);
-
-            // Step 1: Request a new payment from Voltage
-            let payment_id = match state
-                .lightning_service
-                .create_game_invoice(500, Some(&description))
-                .await
-            {
-                Ok(id) => id,
-                Err(e) => {
-                    error!("Failed to create invoice: {}", e);
-                    return Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        "Failed to create payment invoice",
-                    )
-                        .into_response());
-                }
-            };
-
-            info!(
-                "Created payment with ID: {}, polling for invoice",
-                payment_id
+            return Err(
+                create_entry_invoice(&state, user.id, &pubkey, &competition, &bundle).await,
             );
-
-            // Step 2: Poll for the invoice
-            let mut invoice: Option<String> = None;
-            let max_attempts = 10;
-
-            for attempt in 0..max_attempts {
-                info!("Poll attempt {} for invoice", attempt + 1);
-
-                match state
-                    .lightning_service
-                    .get_payment_invoice(&payment_id)
-                    .await
-                {
-                    Ok(Some(payment_request)) => {
-                        info!("Received invoice on attempt {}", attempt + 1);
-                        invoice = Some(payment_request);
-                        break;
-                    }
-                    Ok(None) => {
-                        // Invoice not available yet, wait and retry
-                        info!("Invoice not available yet, waiting");
-                        tokio::time::sleep(std::time::Duration::from_millis(5000)).await;
-                    }
-                    Err(e) => {
-                        error!("Error getting payment invoice: {}", e);
-                        return Err((
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            "Failed to retrieve payment invoice",
-                        )
-                            .into_response());
-                    }
-                }
-            }
-
-            // Step 3: Process the invoice result
-            match invoice {
-                Some(invoice_str) => {
-                    info!("Successfully obtained invoice: {}", invoice_str);
-
-                    // Store the payment in the database
-                    match state
-                        .payment_store
-                        .create_game_payment(user.id, &payment_id, &invoice_str, 500)
-                        .await
-                    {
-                        Ok(payment) => {
-                            // Return payment required response
-                            return Err((
-                                StatusCode::PAYMENT_REQUIRED,
-                                Json(json!({
-                                    "payment_required": true,
-                                    "invoice": payment.invoice,
-                                    "payment_id": payment.payment_id,
-                                    "amount_sats": payment.amount_sats,
-                                    "created_at": payment.created_at
-                                })),
-                            )
-                                .into_response());
-                        }
-                        Err(e) => return Err(map_error(e)),
-                    }
-                }
-                None => {
-                    error!("Failed to get invoice after {} attempts", max_attempts);
-                    return Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        "Failed to generate Lightning invoice. Please try again.",
-                    )
-                        .into_response());
-                }
-            }
         }
         Err(e) => return Err(map_error(e)),
     };
@@ -250,36 +414,37 @@ pub async fn start_new_session(
         .await
     {
         Ok(Some(payment_status)) => {
-            let status = payment_status["status"].as_str().unwrap_or("unknown");
-
-            match status {
-                "completed" => {
+            match payment_status.status {
+                PaymentStatus::Completed => {
                     info!(
                         "Payment {} is completed, updating status",
                         pending_payment.payment_id
                     );
 
-                    // Payment received, update our record
-                    if let Err(e) = state
+                    // Payment received, update our record, the competition pot and the user's credits
+                    match state
                         .payment_store
-                        .update_payment_status(&pending_payment.payment_id, "paid")
+                        .mark_payment_paid(&pending_payment.payment_id)
                         .await
                     {
-                        error!("Failed to update payment status: {}", e);
+                        Ok(Some(_)) => state.live_board.invalidate(),
+                        Ok(None) => {}
+                        Err(e) => error!("Failed to update payment status: {}", e),
                     }
 
-                    // Create a new session
-                    match state.game_store.create_session(user.id).await {
-                        Ok(session) => match state.game_store.create_game_config(&session).await {
-                            Ok(config) => {
-                                Ok((StatusCode::CREATED, Json(NewSessionResponse { config })))
-                            }
-                            Err(e) => Err(map_error(e)),
-                        },
-                        Err(e) => Err(map_error(e)),
+                    match create_competition_session(&state, user.id, competition.id).await? {
+                        Some(response) => Ok(response),
+                        None => Err(create_entry_invoice(
+                            &state,
+                            user.id,
+                            &pubkey,
+                            &competition,
+                            &bundle,
+                        )
+                        .await),
                     }
                 }
-                "failed" => {
+                PaymentStatus::Failed => {
                     info!(
                         "Payment {} has failed, creating new invoice",
                         pending_payment.payment_id
@@ -288,148 +453,300 @@ pub async fn start_new_session(
                     // Payment failed, update our record
                     if let Err(e) = state
                         .payment_store
-                        .update_payment_status(&pending_payment.payment_id, "failed")
+                        .fail_payment(&pending_payment.payment_id)
                         .await
                     {
                         error!("Failed to update payment status: {}", e);
                     }
 
                     // Create a new invoice for the user
-                    let description = format!("Asteroids Game Entry Fee - User:{}", pubkey);
-
-                    // Step 1: Request a new payment from Voltage
-                    let payment_id = match state
-                        .lightning_service
-                        .create_game_invoice(500, Some(&description))
-                        .await
-                    {
-                        Ok(id) => id,
-                        Err(e) => {
-                            error!("Failed to create new invoice after payment failure: {}", e);
-                            return Err((
-                                StatusCode::INTERNAL_SERVER_ERROR,
-                                "Failed to create payment invoice",
-                            )
-                                .into_response());
-                        }
-                    };
-
-                    // Step 2: Poll for the invoice
-                    let mut invoice: Option<String> = None;
-                    let max_attempts = 10;
-
-                    for attempt in 0..max_attempts {
-                        match state
-                            .lightning_service
-                            .get_payment_invoice(&payment_id)
-                            .await
-                        {
-                            Ok(Some(payment_request)) => {
-                                invoice = Some(payment_request);
-                                break;
-                            }
-                            Ok(None) => {
-                                // Invoice not available yet, wait and retry
-                                tokio::time::sleep(std::time::Duration::from_millis(2000)).await;
-                            }
-                            Err(e) => {
-                                error!("Error getting payment invoice: {}", e);
-                                return Err((
-                                    StatusCode::INTERNAL_SERVER_ERROR,
-                                    "Failed to retrieve payment invoice",
-                                )
-                                    .into_response());
-                            }
-                        }
-                    }
-
-                    // Step 3: Process the invoice result
-                    match invoice {
-                        Some(invoice_str) => {
-                            // Store the payment in the database
-                            match state
-                                .payment_store
-                                .create_game_payment(user.id, &payment_id, &invoice_str, 500)
-                                .await
-                            {
-                                Ok(payment) => {
-                                    // Return payment required response
-                                    return Err((
-                                        StatusCode::PAYMENT_REQUIRED,
-                                        Json(json!({
-                                            "payment_required": true,
-                                            "invoice": payment.invoice,
-                                            "payment_id": payment.payment_id,
-                                            "amount_sats": payment.amount_sats,
-                                            "created_at": payment.created_at
-                                        })),
-                                    )
-                                        .into_response());
-                                }
-                                Err(e) => return Err(map_error(e)),
-                            }
-                        }
-                        None => {
-                            error!("Failed to get invoice after {} attempts", max_attempts);
-                            return Err((
-                                StatusCode::INTERNAL_SERVER_ERROR,
-                                "Failed to generate Lightning invoice. Please try again.",
-                            )
-                                .into_response());
-                        }
-                    }
+                    Err(create_entry_invoice(&state, user.id, &pubkey, &competition, &bundle).await)
+                }
+                _ if pending_payment.is_expired(OffsetDateTime::now_utc()) => {
+                    Err(replace_expired_invoice(
+                        &state,
+                        &pending_payment,
+                        user.id,
+                        &pubkey,
+                        &competition,
+                        &bundle,
+                    )
+                    .await)
                 }
                 _ => {
                     info!("Payment {} is still pending", pending_payment.payment_id);
 
                     // Payment still pending
-                    return Err((
-                        StatusCode::PAYMENT_REQUIRED,
-                        Json(json!({
-                            "payment_required": true,
-                            "invoice": pending_payment.invoice,
-                            "payment_id": pending_payment.payment_id,
-                            "amount_sats": pending_payment.amount_sats,
-                            "created_at": pending_payment.created_at
-                        })),
-                    )
-                        .into_response());
+                    Err(payment_required_response(&pending_payment, None))
                 }
             }
         }
+        // The Lightning API has never heard of the payment, once expired it cannot be paid
+        Ok(None) if pending_payment.is_expired(OffsetDateTime::now_utc()) => {
+            Err(replace_expired_invoice(
+                &state,
+                &pending_payment,
+                user.id,
+                &pubkey,
+                &competition,
+                &bundle,
+            )
+            .await)
+        }
         Ok(None) => {
             // Payment not found in Lightning API yet, consider it still pending
-            return Err((
-                StatusCode::PAYMENT_REQUIRED,
-                Json(json!({
-                    "payment_required": true,
-                    "invoice": pending_payment.invoice,
-                    "payment_id": pending_payment.payment_id,
-                    "amount_sats": pending_payment.amount_sats,
-                    "created_at": pending_payment.created_at,
-                    "message": "Payment processing, please wait"
-                })),
-            )
-                .into_response());
+            Err(payment_required_response(
+                &pending_payment,
+                Some(("message", "Payment processing, please wait")),
+            ))
         }
         Err(e) => {
             error!("Failed to check payment status: {}", e);
 
             // Return the existing invoice in case of error checking status
-            return Err((
-                StatusCode::PAYMENT_REQUIRED,
-                Json(json!({
-                    "payment_required": true,
-                    "invoice": pending_payment.invoice,
-                    "payment_id": pending_payment.payment_id,
-                    "amount_sats": pending_payment.amount_sats,
-                    "created_at": pending_payment.created_at,
-                    "error": "Could not verify payment status. Please try again."
-                })),
+            Err(payment_required_response(
+                &pending_payment,
+                Some((
+                    "error",
+                    "Could not verify payment status. Please try again.",
+                )),
+            ))
+        }
+    }
+}
+
+// Spend a credit on a new session, returns None when the user has no credits left
+async fn create_competition_session(
+    state: &AppState,
+    user_id: i64,
+    competition_id: i64,
+) -> Result<Option<(StatusCode, Json<NewSessionResponse>)>, Response> {
+    let session = match state
+        .game_store
+        .create_paid_session(user_id, competition_id)
+        .await
+    {
+        Ok(Some(session)) => session,
+        Ok(None) => return Ok(None),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    let credits_remaining = match state
+        .payment_store
+        .get_credit_balance(user_id, competition_id)
+        .await
+    {
+        Ok(credits) => credits,
+        Err(e) => return Err(map_error(e)),
+    };
+
+    match state.game_store.create_game_config(&session).await {
+        Ok(config) => Ok(Some((
+            StatusCode::CREATED,
+            Json(NewSessionResponse {
+                config,
+                credits_remaining: Some(credits_remaining),
+            }),
+        ))),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// Expire an invoice that can no longer be paid and hand out a fresh one in its place
+async fn replace_expired_invoice(
+    state: &AppState,
+    expired: &GamePayment,
+    user_id: i64,
+    pubkey: &str,
+    competition: &Competition,
+    bundle: &EntryBundle,
+) -> Response {
+    info!(
+        "Payment {} has expired, creating new invoice",
+        expired.payment_id
+    );
+
+    if let Err(e) = state
+        .payment_store
+        .expire_payment(&expired.payment_id)
+        .await
+    {
+        error!("Failed to expire payment {}: {}", expired.payment_id, e);
+    }
+
+    create_entry_invoice(state, user_id, pubkey, competition, bundle).await
+}
+
+// Request a new invoice for a bundle of games, always returns the response to send
+async fn create_entry_invoice(
+    state: &AppState,
+    user_id: i64,
+    pubkey: &str,
+    competition: &Competition,
+    bundle: &EntryBundle,
+) -> Response {
+    let description = format!(
+        "Asteroids Game Entry Fee - {} games - User:{}",
+        bundle.games, pubkey
+    );
+    let amount_sats = bundle.price_sats(competition.entry_fee_sats);
+
+    // Step 1: Request a new payment from Voltage
+    let payment_id = match state
+        .lightning_service
+        .create_game_invoice(amount_sats, Some(&description))
+        .await
+    {
+        Ok(id) => id,
+        Err(e) => {
+            error!("Failed to create invoice: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create payment invoice",
             )
-                .into_response());
+                .into_response();
+        }
+    };
+
+    info!(
+        "Created payment with ID: {}, polling for invoice",
+        payment_id
+    );
+
+    // Step 2: Poll for the invoice
+    let mut invoice: Option<String> = None;
+    let max_attempts = 10;
+
+    for attempt in 0..max_attempts {
+        info!("Poll attempt {} for invoice", attempt + 1);
+
+        match state
+            .lightning_service
+            .get_payment_invoice(&payment_id)
+            .await
+        {
+            Ok(Some(payment_request)) => {
+                info!("Received invoice on attempt {}", attempt + 1);
+                invoice = Some(payment_request);
+                break;
+            }
+            Ok(None) => {
+                // Invoice not available yet, wait and retry
+                info!("Invoice not available yet, waiting");
+                tokio::time::sleep(std::time::Duration::from_millis(5000)).await;
+            }
+            Err(e) => {
+                error!("Error getting payment invoice: {}", e);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to retrieve payment invoice",
+                )
+                    .into_response();
+            }
         }
     }
+
+    // Step 3: Process the invoice result
+    let Some(invoice_str) = invoice else {
+        error!("Failed to get invoice after {} attempts", max_attempts);
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to generate Lightning invoice. Please try again.",
+        )
+            .into_response();
+    };
+
+    info!("Successfully obtained invoice: {}", invoice_str);
+
+    // Invoices without a readable expiry get the BOLT11 default of an hour
+    let expires_at = invoice_expires_at(&invoice_str)
+        .and_then(|expires_at| OffsetDateTime::from_unix_timestamp(expires_at).ok())
+        .unwrap_or_else(|| OffsetDateTime::now_utc() + Duration::hours(1));
+
+    // Store the payment in the database
+    match state
+        .payment_store
+        .create_game_payment(
+            user_id,
+            competition.id,
+            &payment_id,
+            &invoice_str,
+            amount_sats,
+            bundle.games,
+            expires_at,
+        )
+        .await
+    {
+        // Return payment required response
+        Ok(payment) => payment_required_response(&payment, None),
+        Err(e) => map_error(e),
+    }
+}
+
+// The 402 telling a player which invoice to pay for their entry, with a "message" or "error"
+// when the payment could not be confirmed yet
+fn payment_required_response(payment: &GamePayment, note: Option<(&str, &str)>) -> Response {
+    let mut body = json!({
+        "payment_required": true,
+        "invoice": payment.invoice,
+        "payment_id": payment.payment_id,
+        "amount_sats": payment.amount_sats,
+        "credits": payment.credits,
+        "created_at": payment.created_at,
+        "expires_at": payment.expires_at,
+        "seconds_remaining": payment.seconds_remaining(OffsetDateTime::now_utc())
+    });
+    if let Some((key, text)) = note {
+        body[key] = json!(text);
+    }
+
+    (StatusCode::PAYMENT_REQUIRED, Json(body)).into_response()
+}
+
+// Every config handed out for one of the player's sessions, with the exact values played
+pub async fn get_session_configs(
+    auth: NostrAuth,
+    Path(session_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    let pubkey = auth.pubkey.to_string();
+    info!(
+        "Config history request for session {} from {}",
+        session_id, pubkey
+    );
+
+    let user = match state.user_store.find_by_pubkey(pubkey).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, "User not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    let session = match state.game_store.find_session(&session_id).await {
+        Ok(Some(session)) => session,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, "Session not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+    if session.user_id != user.id {
+        return Err((StatusCode::FORBIDDEN, "Session belongs to a different user").into_response());
+    }
+
+    match state.game_store.get_session_configs(&session_id).await {
+        Ok(configs) => Ok((StatusCode::OK, Json(configs))),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// Public key configs are signed with, for clients to check the configs they are handed
+pub async fn get_server_pubkey(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let pubkey = state.game_store.signer_pubkey();
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "pubkey": pubkey.to_hex(),
+            "npub": pubkey.to_bech32().ok()
+        })),
+    )
 }
 
 // Submit a score
@@ -441,6 +758,13 @@ pub async fn submit_score(
     let pubkey = auth.pubkey.to_string();
     info!("Score submission from pubkey: {}", pubkey);
 
+    // Banned players can no longer play
+    state
+        .user_store
+        .check_not_banned(&pubkey)
+        .await
+        .map_err(map_error)?;
+
     // Find user
     let user = match state.user_store.find_by_pubkey(pubkey).await {
         Ok(Some(user)) => user,
@@ -448,9 +772,6 @@ pub async fn submit_score(
         Err(e) => return Err(map_error(e)),
     };
 
-    // Debug log the session ID we're looking for
-    info!("Looking for session ID: {}", submission.session_id);
-
     // Verify session
     match state.game_store.find_session(&submission.session_id).await {
         Ok(Some(session)) => {
@@ -460,18 +781,63 @@ pub async fn submit_score(
                 );
             }
 
+            if !session.status.is_open() {
+                return Err((
+                    StatusCode::CONFLICT,
+                    format!("Session is already {}", session.status),
+                )
+                    .into_response());
+            }
+
+            // Check the score could have been played in this session
+            let now = OffsetDateTime::now_utc();
+            let facts = state
+                .game_store
+                .session_facts(&session, now)
+                .await
+                .map_err(map_error)?;
+            let assessment = assess_score(
+                submission.score,
+                submission.level,
+                submission.play_time,
+                &facts,
+            );
+            if assessment.is_rejected() {
+                warn!(
+                    "Rejected score {} from user {} for session {}: {:?}",
+                    submission.score, user.id, session.session_id, assessment.violations
+                );
+                return Err((
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    Json(json!({
+                        "error": "Score is not possible for this session",
+                        "violations": assessment.violations
+                    })),
+                )
+                    .into_response());
+            }
+            if assessment.suspicion > 0.0 {
+                warn!(
+                    "Flagged score {} from user {} for review, suspicion {:.2}: {:?}",
+                    submission.score, user.id, assessment.suspicion, assessment.violations
+                );
+            }
+
             // Submit the score
             match state
                 .game_store
                 .submit_score(
-                    user.id,
+                    &session,
                     submission.score,
                     submission.level,
                     submission.play_time,
+                    &assessment,
+                    now,
                 )
                 .await
             {
                 Ok(score) => {
+                    state.live_board.invalidate();
                     let response = ScoreResponse {
                         id: score.id,
                         score: score.score,
@@ -494,11 +860,13 @@ pub async fn submit_score(
 
 // Get top scores
 pub async fn get_top_scores(
+    Query(query): Query<ScoresQuery>,
     State(state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, Response> {
     info!("Get top scores request");
 
-    match state.game_store.get_top_scores(10).await {
+    let limit = query.limit.unwrap_or(10).clamp(1, 100);
+    match state.game_store.get_top_scores(limit).await {
         Ok(scores) => Ok((StatusCode::OK, Json(scores))),
         Err(e) => Err(map_error(e)),
     }
@@ -507,6 +875,7 @@ pub async fn get_top_scores(
 // Get user scores
 pub async fn get_user_scores(
     auth: NostrAuth,
+    Query(query): Query<ScoresQuery>,
     State(state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, Response> {
     let pubkey = auth.pubkey.to_string();
@@ -520,7 +889,8 @@ pub async fn get_user_scores(
     };
 
     // Get scores
-    match state.game_store.get_user_scores(user.id, 10).await {
+    let limit = query.limit.unwrap_or(10).clamp(1, 100);
+    match state.game_store.get_user_scores(user.id, limit).await {
         Ok(scores) => {
             let response: Vec<ScoreResponse> = scores
                 .into_iter()
@@ -0,0 +1,329 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, fs, path::Path};
+
+use super::{
+    difficulty::DifficultyCurve,
+    store::{AsteroidsConfig, BulletsConfig, ScoringConfig, ShipConfig, VerticesConfig},
+};
+use crate::domain::Error;
+
+/// Id of the ruleset built into the server, the game as it was first released
+pub const CLASSIC_RULESET: &str = "classic";
+
+/// Physics and scoring a game is played under. Loaded from TOML or JSON files using the
+/// same field names as the config handed to the game, before difficulty scaling
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Ruleset {
+    pub id: String,
+    /// Dotted version numbers, e.g. "1.2.0". Competitions keep the version they opened with
+    pub version: String,
+    pub fps: u64,
+    pub ship: ShipConfig,
+    pub bullets: BulletsConfig,
+    pub asteroids: AsteroidsConfig,
+    pub scoring: ScoringConfig,
+    /// How configs get harder as the player progresses, 10% a level up to 3x if left out
+    #[serde(default)]
+    pub difficulty: DifficultyCurve,
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        Ruleset {
+            id: CLASSIC_RULESET.to_string(),
+            version: String::from("1.0.0"),
+            fps: 60,
+            ship: ShipConfig {
+                radius: 10,
+                turn_speed: 0.1,
+                thrust: 0.1,
+                friction: 0.05,
+                invulnerability_time: 3000,
+            },
+            bullets: BulletsConfig {
+                speed: 5,
+                radius: 2,
+                max_count: 10,
+                life_time: 60,
+            },
+            asteroids: AsteroidsConfig {
+                initial_count: 5,
+                speed: 1.0,
+                size: 30,
+                vertices: VerticesConfig { min: 7, max: 15 },
+            },
+            scoring: ScoringConfig {
+                points_per_asteroid: 10,
+                level_multiplier: 1.5,
+            },
+            difficulty: DifficultyCurve::default(),
+        }
+    }
+}
+
+impl Ruleset {
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = |reason: &str| {
+            Err(Error::InvalidInput(format!(
+                "Invalid ruleset {}@{}: {}",
+                self.id, self.version, reason
+            )))
+        };
+
+        if self.id.is_empty()
+            || !self
+                .id
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+        {
+            return invalid("id must be lowercase letters, digits, '_' or '-'");
+        }
+        if parse_version(&self.version).is_none() {
+            return invalid("version must be dotted numbers, e.g. 1.0.0");
+        }
+        if self.fps == 0 {
+            return invalid("fps must be above 0");
+        }
+        if self.ship.radius == 0 || self.ship.turn_speed <= 0.0 || self.ship.thrust <= 0.0 {
+            return invalid("ship radius, turn speed and thrust must be above 0");
+        }
+        if !(0.0..1.0).contains(&self.ship.friction) {
+            return invalid("ship friction must be from 0 up to 1");
+        }
+        if self.bullets.speed == 0
+            || self.bullets.radius == 0
+            || self.bullets.max_count == 0
+            || self.bullets.life_time == 0
+        {
+            return invalid("bullet speed, radius, count and life time must be above 0");
+        }
+        if self.asteroids.initial_count == 0 || self.asteroids.size == 0 {
+            return invalid("asteroid count and size must be above 0");
+        }
+        if self.asteroids.speed.is_nan() || self.asteroids.speed <= 0.0 {
+            return invalid("asteroid speed must be above 0");
+        }
+        if self.asteroids.vertices.min < 3
+            || self.asteroids.vertices.min > self.asteroids.vertices.max
+        {
+            return invalid("asteroid vertices need a minimum of at least 3, up to the maximum");
+        }
+        if self.scoring.points_per_asteroid == 0 || self.scoring.level_multiplier <= 0.0 {
+            return invalid("points per asteroid and level multiplier must be above 0");
+        }
+        if let Err(reason) = self.difficulty.validate() {
+            return invalid(&reason);
+        }
+
+        Ok(())
+    }
+}
+
+/// Every ruleset the server knows, the built in one plus those loaded from files
+#[derive(Clone, Debug)]
+pub struct Rulesets {
+    rulesets: Vec<Ruleset>,
+    default: Ruleset,
+}
+
+impl Rulesets {
+    /// Load and validate every `.toml` and `.json` ruleset in `dir`, if given
+    pub fn load(dir: Option<&str>, default_id: &str) -> Result<Self, Error> {
+        let mut rulesets = vec![Ruleset::default()];
+
+        if let Some(dir) = dir {
+            let entries = fs::read_dir(dir).map_err(|e| {
+                Error::InvalidInput(format!("Failed to read rulesets folder {}: {}", dir, e))
+            })?;
+            let mut paths = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect::<Vec<_>>();
+            paths.sort();
+
+            for path in paths {
+                if let Some(ruleset) = read_ruleset(&path)? {
+                    info!(
+                        "Loaded ruleset {}@{} from {}",
+                        ruleset.id,
+                        ruleset.version,
+                        path.display()
+                    );
+                    rulesets.push(ruleset);
+                }
+            }
+        }
+
+        Self::new(rulesets, default_id)
+    }
+
+    pub fn new(rulesets: Vec<Ruleset>, default_id: &str) -> Result<Self, Error> {
+        for (i, ruleset) in rulesets.iter().enumerate() {
+            ruleset.validate()?;
+            if rulesets[..i]
+                .iter()
+                .any(|other| other.id == ruleset.id && other.version == ruleset.version)
+            {
+                return Err(Error::InvalidInput(format!(
+                    "Duplicate ruleset {}@{}",
+                    ruleset.id, ruleset.version
+                )));
+            }
+        }
+
+        let default = latest(&rulesets, default_id)?.clone();
+
+        Ok(Rulesets { rulesets, default })
+    }
+
+    /// Latest version of the ruleset used for practice games and competitions that do not pick one
+    pub fn default_ruleset(&self) -> &Ruleset {
+        &self.default
+    }
+
+    /// Highest version of a ruleset, the one new competitions are played under
+    pub fn latest(&self, id: &str) -> Result<&Ruleset, Error> {
+        latest(&self.rulesets, id)
+    }
+
+    /// The exact ruleset a competition or session was started with
+    pub fn find(&self, id: &str, version: &str) -> Result<&Ruleset, Error> {
+        self.rulesets
+            .iter()
+            .find(|ruleset| ruleset.id == id && ruleset.version == version)
+            .ok_or_else(|| Error::NotFound(format!("Unknown ruleset: {}@{}", id, version)))
+    }
+
+    pub fn all(&self) -> &[Ruleset] {
+        &self.rulesets
+    }
+}
+
+fn latest<'a>(rulesets: &'a [Ruleset], id: &str) -> Result<&'a Ruleset, Error> {
+    rulesets
+        .iter()
+        .filter(|ruleset| ruleset.id == id)
+        .max_by(|a, b| compare_versions(&a.version, &b.version))
+        .ok_or_else(|| Error::NotFound(format!("Unknown ruleset: {}", id)))
+}
+
+// Files with other extensions in the folder are skipped
+fn read_ruleset(path: &Path) -> Result<Option<Ruleset>, Error> {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    if !matches!(extension, Some("toml") | Some("json")) {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(path).map_err(|e| {
+        Error::InvalidInput(format!("Failed to read ruleset {}: {}", path.display(), e))
+    })?;
+    let ruleset = if extension == Some("toml") {
+        toml::from_str(&content).map_err(|e| e.to_string())
+    } else {
+        serde_json::from_str(&content).map_err(|e| e.to_string())
+    }
+    .map_err(|e| Error::InvalidInput(format!("Invalid ruleset {}: {}", path.display(), e)))?;
+
+    Ok(Some(ruleset))
+}
+
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    version.split('.').map(|part| part.parse().ok()).collect()
+}
+
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    parse_version(a).cmp(&parse_version(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(version: &str) -> Ruleset {
+        Ruleset {
+            version: version.to_string(),
+            ..Ruleset::default()
+        }
+    }
+
+    #[test]
+    fn test_latest_version_is_picked_numerically() {
+        let rulesets = Rulesets::new(
+            vec![version("1.0.0"), version("1.10.0"), version("1.9.2")],
+            CLASSIC_RULESET,
+        )
+        .unwrap();
+
+        assert_eq!(rulesets.latest(CLASSIC_RULESET).unwrap().version, "1.10.0");
+        assert_eq!(
+            rulesets.find(CLASSIC_RULESET, "1.9.2").unwrap().version,
+            "1.9.2"
+        );
+        assert!(rulesets.find(CLASSIC_RULESET, "2.0.0").is_err());
+    }
+
+    #[test]
+    fn test_invalid_rulesets_are_refused() {
+        assert!(Rulesets::new(vec![version("1.0.0"), version("1.0.0")], CLASSIC_RULESET).is_err());
+        assert!(Rulesets::new(vec![version("1.0")], "arcade").is_err());
+        assert!(Rulesets::new(vec![version("one")], CLASSIC_RULESET).is_err());
+
+        let mut ruleset = Ruleset::default();
+        ruleset.asteroids.vertices.min = 20;
+        assert!(ruleset.validate().is_err());
+    }
+
+    #[test]
+    fn test_ruleset_files_use_config_field_names() {
+        let ruleset: Ruleset = toml::from_str(
+            r#"
+            id = "arcade"
+            version = "2.0.0"
+            fps = 60
+
+            [ship]
+            radius = 12
+            turnSpeed = 0.12
+            thrust = 0.15
+            friction = 0.03
+            invulnerabilityTime = 2000
+
+            [bullets]
+            speed = 7
+            radius = 2
+            maxCount = 15
+            lifeTime = 50
+
+            [asteroids]
+            initialCount = 8
+            speed = 2
+            size = 25
+            vertices = { min = 6, max = 12 }
+
+            [scoring]
+            pointsPerAsteroid = 20
+            levelMultiplier = 2.0
+
+            [difficulty]
+            curve = "stepped"
+            everyLevels = 2
+            step = 0.5
+            max = 2.5
+            "#,
+        )
+        .unwrap();
+
+        assert!(ruleset.validate().is_ok());
+        assert_eq!(ruleset.asteroids.initial_count, 8);
+        assert_eq!(ruleset.scoring.points_per_asteroid, 20);
+        assert_eq!(
+            ruleset.difficulty,
+            DifficultyCurve::Stepped {
+                every_levels: 2,
+                step: 0.5,
+                max: 2.5
+            }
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use nostr_sdk::{EventBuilder, Keys, Kind, Tag, TagKind};
+
+use super::store::GameConfigResponse;
+use crate::domain::Error;
+
+/// Tag on a config's signature naming the session it was issued for
+pub const SESSION_TAG: &str = "session";
+
+/// Sign a config with the server's key. The signature is a NIP-78 application data event
+/// holding the config, without its signature, as content, tagged with the config id and
+/// session so it cannot be passed off as belonging to another game
+pub fn sign_game_config(
+    mut config: GameConfigResponse,
+    keys: &Keys,
+) -> Result<GameConfigResponse, Error> {
+    config.signature = None;
+    let content = serde_json::to_string(&config).map_err(|e| Error::Signing(e.to_string()))?;
+
+    let event = EventBuilder::new(Kind::ApplicationSpecificData, content)
+        .tag(Tag::identifier(config.config_id.clone()))
+        .tag(Tag::custom(
+            TagKind::custom(SESSION_TAG),
+            [config.session_id.clone()],
+        ))
+        .sign_with_keys(keys)
+        .map_err(|e| Error::Signing(e.to_string()))?;
+
+    config.signature =
+        Some(serde_json::to_string(&event).map_err(|e| Error::Signing(e.to_string()))?);
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use nostr_sdk::Event;
+
+    use super::*;
+    use crate::{build_game_config, Ruleset};
+
+    #[test]
+    fn test_signed_config_verifies_against_server_key() {
+        let keys = Keys::generate();
+        let config = build_game_config(
+            &Ruleset::default(),
+            String::from("config_1"),
+            "session_1",
+            1.5,
+            false,
+        );
+
+        let signed = sign_game_config(config.clone(), &keys).unwrap();
+        let event: Event = serde_json::from_str(signed.signature.as_ref().unwrap()).unwrap();
+
+        assert!(event.verify().is_ok());
+        assert_eq!(event.pubkey, keys.public_key());
+        assert_eq!(event.tags.identifier(), Some("config_1"));
+        let content: GameConfigResponse = serde_json::from_str(&event.content).unwrap();
+        assert_eq!(content.asteroids, config.asteroids);
+        assert_eq!(content.session_id, "session_1");
+    }
+}
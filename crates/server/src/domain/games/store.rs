@@ -1,9 +1,63 @@
+use nostr_sdk::{Keys, PublicKey};
 use serde::{Deserialize, Serialize};
-use sqlx::{Pool, Sqlite};
-use time::{Duration, OffsetDateTime};
+use sqlx::{types::Json, Pool, Sqlite};
+use std::fmt;
+use time::{macros::format_description, Duration, OffsetDateTime};
 use uuid::Uuid;
 
-use crate::domain::Error;
+use crate::{
+    domain::Error, format_time, format_time_secs, parse_time, sign_game_config, Assessment,
+    Progress, Ruleset, Rulesets, SessionFacts,
+};
+
+// Where a competition session is in its life. It is 'created' when a credit is spent on it,
+// 'active' once the game comes back for a fresh config, and ends 'finished' with its one
+// score, 'abandoned' when the player starts another game or its competition closes, or
+// 'expired' after going idle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum SessionStatus {
+    Created,
+    Active,
+    Finished,
+    Abandoned,
+    Expired,
+}
+
+impl SessionStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Active => "active",
+            Self::Finished => "finished",
+            Self::Abandoned => "abandoned",
+            Self::Expired => "expired",
+        }
+    }
+
+    pub fn can_transition_to(self, next: Self) -> bool {
+        match self {
+            Self::Created => matches!(
+                next,
+                Self::Active | Self::Finished | Self::Abandoned | Self::Expired
+            ),
+            Self::Active => matches!(next, Self::Finished | Self::Abandoned | Self::Expired),
+            Self::Finished | Self::Abandoned | Self::Expired => false,
+        }
+    }
+
+    // Whether the game can still be played and scored
+    pub fn is_open(self) -> bool {
+        matches!(self, Self::Created | Self::Active)
+    }
+}
+
+impl fmt::Display for SessionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -14,6 +68,36 @@ pub struct GameSession {
     pub start_time: String,
     pub last_active: String,
     pub difficulty_factor: f64,
+    pub competition_id: Option<i64>,
+    pub status: SessionStatus,
+    /// When the session was finished, abandoned or expired
+    pub ended_at: Option<String>,
+    /// Ruleset of the competition the session was started in
+    pub ruleset_id: String,
+    pub ruleset_version: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PracticeSession {
+    pub id: i64,
+    pub session_id: String,
+    pub user_id: Option<i64>,
+    pub start_time: String,
+    pub last_active: String,
+    pub difficulty_factor: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PracticeScore {
+    pub id: i64,
+    pub practice_session_id: i64,
+    pub user_id: Option<i64>,
+    pub score: i64,
+    pub level: i64,
+    pub play_time: i64,
+    pub created_at: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,9 +106,15 @@ pub struct GameConfig {
     pub id: i64,
     pub config_id: String,
     pub user_id: i64,
+    pub session_id: Option<String>,
+    pub ruleset_id: String,
     pub version: String,
     pub created_at: String,
     pub expiration_time: String,
+    /// Difficulty the config was scaled to
+    pub difficulty_factor: f64,
+    /// Exactly what was handed to the game, missing for configs issued before these were kept
+    pub config: Option<Json<GameConfigResponse>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,8 +124,21 @@ pub struct Score {
     pub user_id: i64,
     pub score: i64,
     pub level: i64,
+    /// Seconds the server saw the session running
     pub play_time: i64,
+    /// Seconds the client said the game lasted
+    pub reported_play_time: Option<i64>,
     pub created_at: String,
+    pub competition_id: Option<i64>,
+    /// Set once an admin has thrown the score out, it then counts towards no ranking
+    pub voided_at: Option<String>,
+    pub void_reason: Option<String>,
+    pub voided_by: Option<String>,
+    pub session_id: Option<String>,
+    /// From 0 up to 1, how unlikely the plausibility checks found the score
+    pub suspicion: f64,
+    /// JSON list of the checks the score failed
+    pub suspicion_reasons: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,18 +155,25 @@ pub struct ScoreWithUsername {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GameConfigResponse {
+    /// Ruleset the game is played under, along with its version
+    pub ruleset_id: String,
     pub version: String,
     pub config_id: String,
     pub session_id: String,
+    pub practice: bool,
     pub expiration_time: u64,
     pub fps: u64,
     pub ship: ShipConfig,
     pub bullets: BulletsConfig,
     pub asteroids: AsteroidsConfig,
     pub scoring: ScoringConfig,
+    /// Nostr event signed by the server holding this config, without the signature, as
+    /// its content. Clients check it against the server's public key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ShipConfig {
     pub radius: u64,
@@ -73,7 +183,7 @@ pub struct ShipConfig {
     pub invulnerability_time: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct BulletsConfig {
     pub speed: u64,
@@ -82,23 +192,23 @@ pub struct BulletsConfig {
     pub life_time: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AsteroidsConfig {
     pub initial_count: u64,
-    pub speed: u64,
+    pub speed: f64,
     pub size: u64,
     pub vertices: VerticesConfig,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct VerticesConfig {
     pub min: u64,
     pub max: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ScoringConfig {
     pub points_per_asteroid: u64,
@@ -108,11 +218,23 @@ pub struct ScoringConfig {
 #[derive(Debug, Clone)]
 pub struct GameStore {
     db: Pool<Sqlite>,
+    rulesets: Rulesets,
+    /// Server key every config handed out is signed with
+    keys: Keys,
 }
 
 impl GameStore {
-    pub fn new(db: Pool<Sqlite>) -> Self {
-        Self { db }
+    pub fn new(db: Pool<Sqlite>, rulesets: Rulesets, keys: Keys) -> Self {
+        Self { db, rulesets, keys }
+    }
+
+    pub fn signer_pubkey(&self) -> PublicKey {
+        self.keys.public_key()
+    }
+
+    // Every ruleset games can be played under
+    pub fn rulesets(&self) -> &Rulesets {
+        &self.rulesets
     }
 
     pub async fn ping(&self) -> Result<(), Error> {
@@ -120,42 +242,89 @@ impl GameStore {
         Ok(())
     }
 
-    pub async fn create_session(&self, user_id: i64) -> Result<GameSession, Error> {
+    // Spend one of the user's game credits on a new competition session. Both happen in
+    // one transaction so a credit can never be used twice; returns None without credits.
+    // A player plays one game at a time, so any session they left running is abandoned
+    pub async fn create_paid_session(
+        &self,
+        user_id: i64,
+        competition_id: i64,
+    ) -> Result<Option<GameSession>, Error> {
         let session_id = format!("session_{}", Uuid::now_v7());
-        let now = OffsetDateTime::now_utc().to_string();
+        let now = format_time(OffsetDateTime::now_utc())?;
 
-        let session_id_clone = session_id.clone();
+        let mut tx = self.db.begin().await?;
 
-        let id = sqlx::query!(
+        let result = sqlx::query!(
             r#"
-            INSERT INTO game_sessions (session_id, user_id, start_time, last_active, difficulty_factor)
-            VALUES (?, ?, ?, ?, ?)
+            INSERT INTO game_credits (user_id, competition_id, delta, reason, session_id, created_at)
+            SELECT ?, ?, -1, 'session', ?, ?
+            WHERE (
+                SELECT COALESCE(SUM(delta), 0) FROM game_credits
+                WHERE user_id = ? AND competition_id = ?
+            ) >= 1
             "#,
-            session_id,
             user_id,
+            competition_id,
+            session_id,
             now,
+            user_id,
+            competition_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE game_sessions
+            SET status = ?, ended_at = ?
+            WHERE user_id = ? AND status IN ('created', 'active')
+            "#,
+            SessionStatus::Abandoned,
             now,
-            1.0 // Initial difficulty
+            user_id
         )
-        .execute(&self.db)
-        .await?
-        .last_insert_rowid();
+        .execute(&mut *tx)
+        .await?;
 
-        Ok(GameSession {
-            id,
-            session_id: session_id_clone,
+        // The session is played under the rules its competition opened with
+        let session = sqlx::query_as!(
+            GameSession,
+            r#"
+            INSERT INTO game_sessions
+            (session_id, user_id, start_time, last_active, difficulty_factor, competition_id, status, ruleset_id, ruleset_version)
+            SELECT ?, ?, ?, ?, ?, id, ?, ruleset_id, rules_version
+            FROM competitions
+            WHERE id = ?
+            RETURNING id as "id!", session_id, user_id, start_time, last_active, difficulty_factor, competition_id,
+                status as "status: SessionStatus", ended_at, ruleset_id, ruleset_version
+            "#,
+            session_id,
             user_id,
-            start_time: now.clone(),
-            last_active: now,
-            difficulty_factor: 1.0,
-        })
+            now,
+            now,
+            1.0, // Initial difficulty
+            SessionStatus::Created,
+            competition_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(session))
     }
 
     pub async fn find_session(&self, session_id: &str) -> Result<Option<GameSession>, Error> {
         let session = sqlx::query_as!(
             GameSession,
             r#"
-            SELECT id, session_id, user_id, start_time, last_active, difficulty_factor
+            SELECT id, session_id, user_id, start_time, last_active, difficulty_factor, competition_id,
+                status as "status: SessionStatus", ended_at, ruleset_id, ruleset_version
             FROM game_sessions
             WHERE session_id = ?
             "#,
@@ -167,168 +336,315 @@ impl GameStore {
         Ok(session)
     }
 
-    pub async fn update_session_activity(&self, session_id: &str) -> Result<GameSession, Error> {
-        let now = OffsetDateTime::now_utc().to_string();
-
-        // Find the existing session
-        let session = match self.find_session(session_id).await? {
-            Some(s) => s,
-            None => {
-                return Err(Error::NotFound(format!(
-                    "Session not found: {}",
-                    session_id
-                )))
-            }
-        };
-
-        // Calculate how long the session has been active
-        let start = OffsetDateTime::parse(
-            &session.start_time,
-            &time::format_description::well_known::Iso8601::DEFAULT,
-        )
-        .map_err(|_| {
-            Error::InvalidInput("Invalid date format in session start time".to_string())
-        })?;
-
-        let current = OffsetDateTime::now_utc();
-        let duration_minutes = (current - start).whole_minutes() as f64;
+    // Record that a game is still being played, setting its difficulty from the ruleset's
+    // curve for how far the player has got. The first time this happens the session becomes
+    // active
+    pub async fn update_session_activity(
+        &self,
+        session: &GameSession,
+        progress: Progress,
+    ) -> Result<GameSession, Error> {
+        if !session.status.is_open() {
+            return Err(Error::Conflict(format!(
+                "Session {} is {}",
+                session.session_id, session.status
+            )));
+        }
 
-        // Calculate difficulty factor (10% increase per minute, max 3x)
-        let difficulty = (1.0 + (duration_minutes * 0.1)).min(3.0);
+        let now = format_time(OffsetDateTime::now_utc())?;
+        let difficulty = self
+            .rulesets
+            .find(&session.ruleset_id, &session.ruleset_version)?
+            .difficulty
+            .factor(progress);
 
-        // Update the session
-        sqlx::query!(
+        let result = sqlx::query!(
             r#"
             UPDATE game_sessions
-            SET last_active = ?, difficulty_factor = ?
-            WHERE session_id = ?
+            SET last_active = ?, difficulty_factor = ?, status = ?
+            WHERE session_id = ? AND status IN ('created', 'active')
             "#,
             now,
             difficulty,
-            session_id
+            SessionStatus::Active,
+            session.session_id
         )
         .execute(&self.db)
         .await?;
 
+        // It ended while the request was on its way
+        if result.rows_affected() == 0 {
+            return Err(Error::Conflict(format!(
+                "Session {} has ended",
+                session.session_id
+            )));
+        }
+
         Ok(GameSession {
-            id: session.id,
-            session_id: session.session_id,
-            user_id: session.user_id,
-            start_time: session.start_time,
             last_active: now,
             difficulty_factor: difficulty,
+            status: SessionStatus::Active,
+            ..session.clone()
         })
     }
 
+    // Expire sessions nobody has played since `idle_since`, returns how many were expired
+    pub async fn expire_idle_sessions(&self, idle_since: OffsetDateTime) -> Result<u64, Error> {
+        let now = format_time(OffsetDateTime::now_utc())?;
+        let idle_since = format_time_secs(idle_since)?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE game_sessions
+            SET status = ?, ended_at = ?
+            WHERE status IN ('created', 'active') AND last_active < ?
+            "#,
+            SessionStatus::Expired,
+            now,
+            idle_since
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
     pub async fn create_game_config(
         &self,
         session: &GameSession,
     ) -> Result<GameConfigResponse, Error> {
+        let ruleset = self
+            .rulesets
+            .find(&session.ruleset_id, &session.ruleset_version)?;
         let config_id = format!("config_{}", Uuid::now_v7());
-        let version = "1.0.0".to_string();
-        let expiration_time = (OffsetDateTime::now_utc() + Duration::minutes(5)).to_string();
-        let now = OffsetDateTime::now_utc().to_string();
+        let expiration_time = format_time(OffsetDateTime::now_utc() + Duration::minutes(5))?;
+        let now = format_time(OffsetDateTime::now_utc())?;
+
+        let config = sign_game_config(
+            build_game_config(
+                ruleset,
+                config_id.clone(),
+                &session.session_id,
+                session.difficulty_factor,
+                false,
+            ),
+            &self.keys,
+        )?;
+        let config_json =
+            serde_json::to_string(&config).map_err(|e| Error::InvalidInput(e.to_string()))?;
 
-        // Store config in database
+        // Store config in database, in full so disputes can be settled against it later
         sqlx::query!(
             r#"
-            INSERT INTO game_configs (config_id, user_id, version, created_at, expiration_time)
-            VALUES (?, ?, ?, ?, ?)
+            INSERT INTO game_configs
+            (config_id, user_id, session_id, ruleset_id, version, created_at, expiration_time, difficulty_factor, config)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
             config_id,
             session.user_id,
-            version,
+            session.session_id,
+            ruleset.id,
+            ruleset.version,
             now,
-            expiration_time
+            expiration_time,
+            session.difficulty_factor,
+            config_json
         )
         .execute(&self.db)
         .await?;
 
-        // Calculate expiration time in milliseconds
-        let expiration_ms =
-            (OffsetDateTime::now_utc() + Duration::minutes(5)).unix_timestamp() * 1000;
+        Ok(config)
+    }
+
+    // Every config handed out for a session, oldest first
+    pub async fn get_session_configs(&self, session_id: &str) -> Result<Vec<GameConfig>, Error> {
+        let configs = sqlx::query_as!(
+            GameConfig,
+            r#"
+            SELECT id, config_id, user_id, session_id, ruleset_id, version, created_at, expiration_time,
+                difficulty_factor, config as "config: Json<GameConfigResponse>"
+            FROM game_configs
+            WHERE session_id = ?
+            ORDER BY id ASC
+            "#,
+            session_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(configs)
+    }
 
-        // Apply difficulty factor from session
-        let difficulty = session.difficulty_factor;
+    // What the plausibility checks need to know about a session whose score arrived at `now`
+    pub async fn session_facts(
+        &self,
+        session: &GameSession,
+        now: OffsetDateTime,
+    ) -> Result<SessionFacts, Error> {
+        let issued = sqlx::query!(
+            r#"
+            SELECT COUNT(*) as "count!: i64", MAX(difficulty_factor) as "max_difficulty: f64"
+            FROM game_configs
+            WHERE session_id = ?
+            "#,
+            session.session_id
+        )
+        .fetch_one(&self.db)
+        .await?;
 
-        // Return config with difficulty scaling
-        Ok(GameConfigResponse {
-            version,
-            config_id,
-            session_id: session.session_id.clone(),
-            expiration_time: expiration_ms as u64,
-            fps: 60,
-            ship: ShipConfig {
-                radius: 10,
-                turn_speed: 0.1,
-                thrust: 0.1,
-                friction: 0.05,
-                invulnerability_time: 3000,
-            },
-            bullets: BulletsConfig {
-                speed: 5,
-                radius: 2,
-                max_count: 10,
-                life_time: 60,
-            },
-            asteroids: AsteroidsConfig {
-                // Scale asteroid count with difficulty
-                initial_count: (5.0 * difficulty) as u64,
-                // Scale asteroid speed with difficulty
-                speed: (1.0 * difficulty) as u64,
-                size: 30,
-                vertices: VerticesConfig { min: 7, max: 15 },
-            },
-            scoring: ScoringConfig {
-                // Make points worth more as difficulty increases
-                points_per_asteroid: (10.0 * difficulty) as u64,
-                level_multiplier: 1.5,
-            },
+        Ok(SessionFacts {
+            elapsed_secs: play_time_for(session, now)?,
+            // Sessions from before configs were kept fall back to the session's own factor
+            max_difficulty: issued
+                .max_difficulty
+                .unwrap_or(session.difficulty_factor)
+                .max(session.difficulty_factor),
+            config_refreshed: issued.count > 1 || session.last_active != session.start_time,
+            ruleset: self
+                .rulesets
+                .find(&session.ruleset_id, &session.ruleset_version)?
+                .clone(),
+            previous_scores: self.count_session_scores(&session.session_id).await?,
         })
     }
 
+    // Scores already submitted for a session
+    pub async fn count_session_scores(&self, session_id: &str) -> Result<i64, Error> {
+        let result = sqlx::query!(
+            r#"SELECT COUNT(*) as "count!: i64" FROM scores WHERE session_id = ?"#,
+            session_id
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(result.count)
+    }
+
+    // Finish a session with its one score. The play time kept is the time the server saw
+    // the session running until `now`, the client's own count is kept alongside it
     pub async fn submit_score(
         &self,
-        user_id: i64,
+        session: &GameSession,
         score: i64,
         level: i64,
-        play_time: i64,
+        reported_play_time: i64,
+        assessment: &Assessment,
+        now: OffsetDateTime,
     ) -> Result<Score, Error> {
-        let now = OffsetDateTime::now_utc().to_string();
+        let play_time = play_time_for(session, now)?;
+        let ended_at = format_time(now)?;
+        let created_at = format_time_secs(now)?;
+        let user_id = session.user_id;
+        let suspicion_reasons = if assessment.violations.is_empty() {
+            None
+        } else {
+            Some(
+                serde_json::to_string(&assessment.violations)
+                    .map_err(|e| Error::InvalidInput(e.to_string()))?,
+            )
+        };
+
+        let mut tx = self.db.begin().await?;
+
+        // A game still running when its competition closed no longer counts, the winner may
+        // already have been picked
+        let competition = sqlx::query!(
+            r#"
+            SELECT name, status, end_time
+            FROM competitions
+            WHERE id = ?
+            "#,
+            session.competition_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(competition) = competition else {
+            return Err(Error::Conflict(format!(
+                "Session {} is not part of a competition",
+                session.session_id
+            )));
+        };
+        if competition.status != "open" || now >= parse_time(&competition.end_time)? {
+            return Err(Error::Conflict(format!(
+                "{} has closed, the score can no longer be submitted",
+                competition.name
+            )));
+        }
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE game_sessions
+            SET status = ?, ended_at = ?
+            WHERE session_id = ? AND status IN ('created', 'active')
+            "#,
+            SessionStatus::Finished,
+            ended_at,
+            session.session_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::Conflict(format!(
+                "Session {} has already ended",
+                session.session_id
+            )));
+        }
 
-        // Save the score
+        // Save the score against the competition the session was started for
         let id = sqlx::query!(
             r#"
-            INSERT INTO scores (user_id, score, level, play_time, created_at)
-            VALUES (?, ?, ?, ?, ?)
+            INSERT INTO scores
+            (user_id, score, level, play_time, reported_play_time, created_at, competition_id, session_id, suspicion, suspicion_reasons)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
             user_id,
             score,
             level,
             play_time,
-            now
+            reported_play_time,
+            created_at,
+            session.competition_id,
+            session.session_id,
+            assessment.suspicion,
+            suspicion_reasons
         )
-        .execute(&self.db)
+        .execute(&mut *tx)
         .await?
         .last_insert_rowid();
 
+        tx.commit().await?;
+
         Ok(Score {
             id,
             user_id,
             score,
             level,
             play_time,
-            created_at: now,
+            reported_play_time: Some(reported_play_time),
+            created_at,
+            competition_id: session.competition_id,
+            voided_at: None,
+            void_reason: None,
+            voided_by: None,
+            session_id: Some(session.session_id.clone()),
+            suspicion: assessment.suspicion,
+            suspicion_reasons,
         })
     }
 
+    // Highest scores ever, leaving out players banned for the time being
     pub async fn get_top_scores(&self, limit: i64) -> Result<Vec<ScoreWithUsername>, Error> {
         let scores = sqlx::query!(
             r#"
             SELECT s.id, s.user_id, s.score, s.level, s.play_time, s.created_at, u.username
             FROM scores s
             JOIN users u ON s.user_id = u.id
-            ORDER BY s.score DESC
+            WHERE s.voided_at IS NULL
+                AND NOT EXISTS (
+                    SELECT 1 FROM active_bans x WHERE x.pubkey = u.nostr_pubkey
+                )
+            ORDER BY s.score DESC, s.id ASC
             LIMIT ?
             "#,
             limit
@@ -353,7 +669,8 @@ impl GameStore {
         let scores = sqlx::query_as!(
             Score,
             r#"
-            SELECT id, user_id, score, level, play_time, created_at
+            SELECT id, user_id, score, level, play_time, reported_play_time, created_at, competition_id,
+                voided_at, void_reason, voided_by, session_id, suspicion, suspicion_reasons
             FROM scores
             WHERE user_id = ?
             ORDER BY score DESC
@@ -367,4 +684,504 @@ impl GameStore {
 
         Ok(scores)
     }
+
+    // Throw out a score, returns None if it does not exist or was already voided
+    pub async fn void_score(
+        &self,
+        score_id: i64,
+        reason: &str,
+        voided_by: &str,
+    ) -> Result<Option<Score>, Error> {
+        let now = format_time(OffsetDateTime::now_utc())?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE scores
+            SET voided_at = ?, void_reason = ?, voided_by = ?
+            WHERE id = ? AND voided_at IS NULL
+            "#,
+            now,
+            reason,
+            voided_by,
+            score_id
+        )
+        .execute(&self.db)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let score = sqlx::query_as!(
+            Score,
+            r#"
+            SELECT id, user_id, score, level, play_time, reported_play_time, created_at, competition_id,
+                voided_at, void_reason, voided_by, session_id, suspicion, suspicion_reasons
+            FROM scores
+            WHERE id = ?
+            "#,
+            score_id
+        )
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(score)
+    }
+
+    // Scores the plausibility checks found suspicious, most suspicious first
+    pub async fn get_suspicious_scores(
+        &self,
+        min_suspicion: f64,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Score>, Error> {
+        let scores = sqlx::query_as!(
+            Score,
+            r#"
+            SELECT id, user_id, score, level, play_time, reported_play_time, created_at, competition_id,
+                voided_at, void_reason, voided_by, session_id, suspicion, suspicion_reasons
+            FROM scores
+            WHERE suspicion > 0 AND suspicion >= ?
+            ORDER BY suspicion DESC, id DESC
+            LIMIT ? OFFSET ?
+            "#,
+            min_suspicion,
+            limit,
+            offset
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(scores)
+    }
+
+    pub async fn get_sessions_for_user(&self, user_id: i64) -> Result<Vec<GameSession>, Error> {
+        let sessions = sqlx::query_as!(
+            GameSession,
+            r#"
+            SELECT id, session_id, user_id, start_time, last_active, difficulty_factor, competition_id,
+                status as "status: SessionStatus", ended_at, ruleset_id, ruleset_version
+            FROM game_sessions
+            WHERE user_id = ?
+            ORDER BY start_time ASC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    pub async fn get_configs_for_user(&self, user_id: i64) -> Result<Vec<GameConfig>, Error> {
+        let configs = sqlx::query_as!(
+            GameConfig,
+            r#"
+            SELECT id, config_id, user_id, session_id, ruleset_id, version, created_at, expiration_time,
+                difficulty_factor, config as "config: Json<GameConfigResponse>"
+            FROM game_configs
+            WHERE user_id = ?
+            ORDER BY created_at ASC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(configs)
+    }
+
+    pub async fn get_all_user_scores(&self, user_id: i64) -> Result<Vec<Score>, Error> {
+        let scores = sqlx::query_as!(
+            Score,
+            r#"
+            SELECT id, user_id, score, level, play_time, reported_play_time, created_at, competition_id,
+                voided_at, void_reason, voided_by, session_id, suspicion, suspicion_reasons
+            FROM scores
+            WHERE user_id = ?
+            ORDER BY created_at ASC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(scores)
+    }
+
+    pub async fn create_practice_session(
+        &self,
+        user_id: Option<i64>,
+    ) -> Result<PracticeSession, Error> {
+        let session_id = format!("practice_{}", Uuid::now_v7());
+        let now = format_time(OffsetDateTime::now_utc())?;
+
+        let id = sqlx::query!(
+            r#"
+            INSERT INTO practice_sessions (session_id, user_id, start_time, last_active, difficulty_factor)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+            session_id,
+            user_id,
+            now,
+            now,
+            1.0 // Initial difficulty
+        )
+        .execute(&self.db)
+        .await?
+        .last_insert_rowid();
+
+        Ok(PracticeSession {
+            id,
+            session_id,
+            user_id,
+            start_time: now.clone(),
+            last_active: now,
+            difficulty_factor: 1.0,
+        })
+    }
+
+    pub async fn find_practice_session(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<PracticeSession>, Error> {
+        let session = sqlx::query_as!(
+            PracticeSession,
+            r#"
+            SELECT id, session_id, user_id, start_time, last_active, difficulty_factor
+            FROM practice_sessions
+            WHERE session_id = ?
+            "#,
+            session_id
+        )
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(session)
+    }
+
+    pub async fn update_practice_session_activity(
+        &self,
+        session: &PracticeSession,
+        progress: Progress,
+    ) -> Result<PracticeSession, Error> {
+        let now = format_time(OffsetDateTime::now_utc())?;
+        let difficulty = self.rulesets.default_ruleset().difficulty.factor(progress);
+
+        sqlx::query!(
+            r#"
+            UPDATE practice_sessions
+            SET last_active = ?, difficulty_factor = ?
+            WHERE session_id = ?
+            "#,
+            now,
+            difficulty,
+            session.session_id
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(PracticeSession {
+            last_active: now,
+            difficulty_factor: difficulty,
+            ..session.clone()
+        })
+    }
+
+    // Practice configs are not stored, anonymous players have no user to store them against
+    pub fn create_practice_config(
+        &self,
+        session: &PracticeSession,
+    ) -> Result<GameConfigResponse, Error> {
+        // Practice games follow the latest version of the default rules
+        let config = build_game_config(
+            self.rulesets.default_ruleset(),
+            format!("config_{}", Uuid::now_v7()),
+            &session.session_id,
+            session.difficulty_factor,
+            true,
+        );
+
+        sign_game_config(config, &self.keys)
+    }
+
+    pub async fn submit_practice_score(
+        &self,
+        session: &PracticeSession,
+        score: i64,
+        level: i64,
+        play_time: i64,
+    ) -> Result<PracticeScore, Error> {
+        let now = format_time(OffsetDateTime::now_utc())?;
+
+        let id = sqlx::query!(
+            r#"
+            INSERT INTO practice_scores (practice_session_id, user_id, score, level, play_time, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+            session.id,
+            session.user_id,
+            score,
+            level,
+            play_time,
+            now
+        )
+        .execute(&self.db)
+        .await?
+        .last_insert_rowid();
+
+        Ok(PracticeScore {
+            id,
+            practice_session_id: session.id,
+            user_id: session.user_id,
+            score,
+            level,
+            play_time,
+            created_at: now,
+        })
+    }
+
+    pub async fn get_practice_scores_for_user(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<PracticeScore>, Error> {
+        let scores = sqlx::query_as!(
+            PracticeScore,
+            r#"
+            SELECT id, practice_session_id, user_id, score, level, play_time, created_at
+            FROM practice_scores
+            WHERE user_id = ?
+            ORDER BY created_at ASC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(scores)
+    }
+}
+
+// Game settings for a session from its ruleset, scaled by its difficulty
+pub fn build_game_config(
+    ruleset: &Ruleset,
+    config_id: String,
+    session_id: &str,
+    difficulty: f64,
+    practice: bool,
+) -> GameConfigResponse {
+    // Calculate expiration time in milliseconds
+    let expiration_ms = (OffsetDateTime::now_utc() + Duration::minutes(5)).unix_timestamp() * 1000;
+
+    // Return config with difficulty scaling
+    GameConfigResponse {
+        ruleset_id: ruleset.id.clone(),
+        version: ruleset.version.clone(),
+        config_id,
+        session_id: session_id.to_string(),
+        practice,
+        expiration_time: expiration_ms as u64,
+        fps: ruleset.fps,
+        ship: ruleset.ship.clone(),
+        bullets: ruleset.bullets.clone(),
+        asteroids: AsteroidsConfig {
+            // Scale asteroid count with difficulty
+            initial_count: (ruleset.asteroids.initial_count as f64 * difficulty).round() as u64,
+            // Scale asteroid speed with difficulty, left fractional so it grows smoothly
+            speed: ruleset.asteroids.speed * difficulty,
+            ..ruleset.asteroids.clone()
+        },
+        scoring: ScoringConfig {
+            // Make points worth more as difficulty increases
+            points_per_asteroid: (ruleset.scoring.points_per_asteroid as f64 * difficulty).round()
+                as u64,
+            level_multiplier: ruleset.scoring.level_multiplier,
+        },
+        signature: None,
+    }
+}
+
+// Whole seconds from the start of a session until `now`
+fn play_time_for(session: &GameSession, now: OffsetDateTime) -> Result<i64, Error> {
+    let start = parse_start_time(&session.start_time)?;
+    Ok((now - start).whole_seconds().max(0))
+}
+
+// Sessions started before times were stored as RFC 3339 hold `OffsetDateTime`'s display
+// format, e.g. "2025-04-10 12:34:56.123456789 +00:00:00"
+fn parse_start_time(value: &str) -> Result<OffsetDateTime, Error> {
+    parse_time(value).or_else(|e| {
+        OffsetDateTime::parse(
+            value,
+            format_description!(
+                "[year]-[month]-[day] [hour padding:none]:[minute]:[second].[subsecond] \
+                 [offset_hour sign:mandatory]:[offset_minute]:[offset_second]"
+            ),
+        )
+        .map_err(|_| e)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::testing::{
+        create_test_user, pay_entry, test_competition_store, test_db, test_game_store,
+    };
+
+    #[test]
+    fn ended_sessions_do_not_change() {
+        for next in [
+            SessionStatus::Created,
+            SessionStatus::Active,
+            SessionStatus::Finished,
+            SessionStatus::Abandoned,
+            SessionStatus::Expired,
+        ] {
+            assert!(!SessionStatus::Finished.can_transition_to(next));
+            assert!(!SessionStatus::Abandoned.can_transition_to(next));
+            assert!(!SessionStatus::Expired.can_transition_to(next));
+        }
+        assert!(SessionStatus::Created.can_transition_to(SessionStatus::Finished));
+        assert!(SessionStatus::Active.can_transition_to(SessionStatus::Expired));
+        assert!(!SessionStatus::Active.can_transition_to(SessionStatus::Created));
+    }
+
+    #[test]
+    fn start_times_are_read_in_either_stored_format() {
+        let start = time::macros::datetime!(2025-04-10 9:04:56.123456789 UTC);
+        assert_eq!(parse_start_time(&start.to_string()).unwrap(), start);
+        assert_eq!(
+            parse_start_time(&format_time(start).unwrap()).unwrap(),
+            start
+        );
+        assert_eq!(
+            parse_start_time("2025-04-10 12:34:56.5 +00:00:00").unwrap(),
+            time::macros::datetime!(2025-04-10 12:34:56.5 UTC)
+        );
+        assert!(parse_start_time("yesterday").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_issued_configs_are_kept_in_full() {
+        let db = test_db().await;
+        let games = test_game_store(&db);
+        let competitions = test_competition_store(&db);
+        let competition = competitions.current_daily().await.unwrap();
+        let user = create_test_user(&db).await;
+        pay_entry(&db, user.id, competition.id, 500, 1).await;
+        let session = games
+            .create_paid_session(user.id, competition.id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let first = games.create_game_config(&session).await.unwrap();
+        let second = games.create_game_config(&session).await.unwrap();
+
+        let kept = games
+            .get_session_configs(&session.session_id)
+            .await
+            .unwrap();
+        assert_eq!(kept.len(), 2);
+        for (row, issued) in kept.iter().zip([&first, &second]) {
+            assert_eq!(row.config_id, issued.config_id);
+            assert_eq!(row.user_id, user.id);
+            assert_eq!(row.ruleset_id, issued.ruleset_id);
+            assert_eq!(row.version, issued.version);
+            assert_eq!(row.difficulty_factor, session.difficulty_factor);
+            // Reloaded exactly as it was signed and handed to the game
+            let config = row.config.as_ref().unwrap();
+            assert_eq!(
+                serde_json::to_value(&config.0).unwrap(),
+                serde_json::to_value(issued).unwrap()
+            );
+        }
+        assert!(first.signature.is_some());
+
+        let history = games.get_configs_for_user(user.id).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(history.iter().all(|row| row.config.is_some()));
+    }
+
+    #[tokio::test]
+    async fn test_scores_are_refused_once_the_competition_closes() {
+        let db = test_db().await;
+        let games = test_game_store(&db);
+        let competitions = test_competition_store(&db);
+        let competition = competitions.current_daily().await.unwrap();
+        let user = create_test_user(&db).await;
+        pay_entry(&db, user.id, competition.id, 500, 2).await;
+
+        let finished = games
+            .create_paid_session(user.id, competition.id)
+            .await
+            .unwrap()
+            .unwrap();
+        let now = OffsetDateTime::now_utc();
+        let score = games
+            .submit_score(&finished, 1000, 3, 60, &Assessment::default(), now)
+            .await
+            .unwrap();
+        assert_eq!(score.competition_id, Some(competition.id));
+
+        let running = games
+            .create_paid_session(user.id, competition.id)
+            .await
+            .unwrap()
+            .unwrap();
+        competitions
+            .update_status(competition.id, "closed")
+            .await
+            .unwrap();
+
+        // Closing the competition ends the game still being played
+        let abandoned = games
+            .find_session(&running.session_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(abandoned.status, SessionStatus::Abandoned);
+        let finished = games
+            .find_session(&finished.session_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(finished.status, SessionStatus::Finished);
+
+        let result = games
+            .submit_score(&running, 2000, 4, 60, &Assessment::default(), now)
+            .await;
+        assert!(matches!(result, Err(Error::Conflict(_))));
+    }
+
+    #[tokio::test]
+    async fn test_scores_are_refused_after_the_competition_ends() {
+        let db = test_db().await;
+        let games = test_game_store(&db);
+        let competitions = test_competition_store(&db);
+        let competition = competitions.current_daily().await.unwrap();
+        let user = create_test_user(&db).await;
+        pay_entry(&db, user.id, competition.id, 500, 1).await;
+
+        let session = games
+            .create_paid_session(user.id, competition.id)
+            .await
+            .unwrap()
+            .unwrap();
+        // Still open, but the cutoff has passed before the settlement got to it
+        let after_cutoff = competition.ends_at().unwrap() + Duration::seconds(1);
+        let result = games
+            .submit_score(&session, 1000, 3, 60, &Assessment::default(), after_cutoff)
+            .await;
+
+        assert!(matches!(result, Err(Error::Conflict(_))));
+        assert_eq!(
+            games
+                .count_session_scores(&session.session_id)
+                .await
+                .unwrap(),
+            0
+        );
+    }
 }
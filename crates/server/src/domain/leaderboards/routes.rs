@@ -0,0 +1,160 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+use crate::{map_error, nostr_extractor::OptionalNostrAuth, parse_date, startup::AppState};
+
+use super::store::{
+    LeaderboardCursor, LeaderboardFilter, LeaderboardRow, LeaderboardTotals, LeaderboardWindow,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct LeaderboardPageQuery {
+    #[serde(default)]
+    pub window: LeaderboardWindow,
+    /// Day (YYYY-MM-DD) inside the window to show, today if not given
+    pub date: Option<String>,
+    pub competition_id: Option<i64>,
+    /// One row per player with their best score, on unless turned off
+    pub best_only: Option<bool>,
+    pub limit: Option<i64>,
+    /// `next_cursor` of the page before
+    pub cursor: Option<String>,
+    /// Rows shown above and below the caller's own
+    pub neighbours: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MyStanding {
+    pub rank: i64,
+    /// The caller's row with the rows around it
+    pub neighbours: Vec<LeaderboardRow>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardPage {
+    pub window: LeaderboardWindow,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub competition_id: Option<i64>,
+    pub best_only: bool,
+    pub entries: Vec<LeaderboardRow>,
+    /// Set when there are more rows after this page
+    pub next_cursor: Option<String>,
+    pub totals: LeaderboardTotals,
+    /// Only for signed requests from players on the leaderboard
+    pub me: Option<MyStanding>,
+}
+
+// Ranked scores for a time window or competition, a page at a time
+pub async fn get_leaderboard(
+    OptionalNostrAuth(auth): OptionalNostrAuth,
+    Query(query): Query<LeaderboardPageQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    info!("Get leaderboard request: {:?}", query);
+
+    let days = state.competition_store.day_boundary();
+    let at = match query.date.as_deref().map(parse_date).transpose() {
+        Ok(Some(date)) => days.start_of(date),
+        Ok(None) => OffsetDateTime::now_utc(),
+        Err(e) => return Err(map_error(e)),
+    };
+    let (from, to) = match query.window.bounds(at, &days) {
+        Ok(bounds) => bounds.unzip(),
+        Err(e) => return Err(map_error(e)),
+    };
+    let cursor = match query.cursor.as_deref().map(LeaderboardCursor::parse) {
+        Some(Ok(cursor)) => Some(cursor),
+        Some(Err(e)) => return Err(map_error(e)),
+        None => None,
+    };
+    let limit = query.limit.unwrap_or(25).clamp(1, 100);
+    let neighbours = query.neighbours.unwrap_or(2).clamp(0, 10);
+
+    let filter = LeaderboardFilter {
+        competition_id: query.competition_id,
+        from,
+        to,
+        best_only: query.best_only.unwrap_or(true),
+    };
+
+    // Fetch one extra row to know whether another page follows
+    let mut entries = match state
+        .leaderboard_store
+        .get_page(&filter, cursor, limit + 1)
+        .await
+    {
+        Ok(entries) => entries,
+        Err(e) => return Err(map_error(e)),
+    };
+    let next_cursor = if entries.len() as i64 > limit {
+        entries.truncate(limit as usize);
+        entries.last().map(|row| row.cursor().to_string())
+    } else {
+        None
+    };
+
+    let totals = match state.leaderboard_store.get_totals(&filter).await {
+        Ok(totals) => totals,
+        Err(e) => return Err(map_error(e)),
+    };
+
+    let me = match auth {
+        Some(auth) => {
+            let user = match state
+                .user_store
+                .find_by_pubkey(auth.pubkey.to_string())
+                .await
+            {
+                Ok(user) => user,
+                Err(e) => return Err(map_error(e)),
+            };
+            match user {
+                Some(user) => match state
+                    .leaderboard_store
+                    .get_user_standing(&filter, user.id, neighbours)
+                    .await
+                {
+                    Ok(rows) => {
+                        let rank = rows
+                            .iter()
+                            .find(|row| row.user_id == user.id)
+                            .map(|row| row.rank);
+                        rank.map(|rank| MyStanding {
+                            rank,
+                            neighbours: rows,
+                        })
+                    }
+                    Err(e) => return Err(map_error(e)),
+                },
+                None => None,
+            }
+        }
+        None => None,
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(LeaderboardPage {
+            window: query.window,
+            from: filter.from,
+            to: filter.to,
+            competition_id: filter.competition_id,
+            best_only: filter.best_only,
+            entries,
+            next_cursor,
+            totals,
+            me,
+        }),
+    ))
+}
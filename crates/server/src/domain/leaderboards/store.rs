@@ -0,0 +1,347 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+use std::fmt;
+use time::{error::ComponentRange, Duration, OffsetDateTime};
+
+use crate::{domain::Error, format_time_secs, DayBoundary};
+
+/// Stretch of time a leaderboard covers, in the same days as the daily competition
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardWindow {
+    Day,
+    /// Monday to Monday
+    Week,
+    Month,
+    #[default]
+    All,
+}
+
+impl LeaderboardWindow {
+    /// Start and end of the window containing `at`, as compared with score times, None for all
+    /// time. Days start and end where the daily competition's do
+    pub fn bounds(
+        &self,
+        at: OffsetDateTime,
+        days: &DayBoundary,
+    ) -> Result<Option<(String, String)>, Error> {
+        let day = days.day_of(at);
+        let (first, next) = match self {
+            LeaderboardWindow::Day => (day, day + Duration::days(1)),
+            LeaderboardWindow::Week => {
+                let monday = day - Duration::days(day.weekday().number_days_from_monday() as i64);
+                (monday, monday + Duration::weeks(1))
+            }
+            LeaderboardWindow::Month => {
+                let first = day.replace_day(1).map_err(invalid_date)?;
+                // 31 days on from the 1st is always in the next month
+                let next = (first + Duration::days(31))
+                    .replace_day(1)
+                    .map_err(invalid_date)?;
+                (first, next)
+            }
+            LeaderboardWindow::All => return Ok(None),
+        };
+
+        Ok(Some((
+            format_time_secs(days.start_of(first))?,
+            format_time_secs(days.start_of(next))?,
+        )))
+    }
+}
+
+fn invalid_date(e: ComponentRange) -> Error {
+    Error::InvalidInput(format!("Invalid date: {}", e))
+}
+
+/// Where the next page of a leaderboard starts, the last row of the page before
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LeaderboardCursor {
+    pub score: i64,
+    pub score_id: i64,
+}
+
+impl LeaderboardCursor {
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidInput(format!("Invalid leaderboard cursor: {}", value));
+
+        let (score, score_id) = value.split_once('_').ok_or_else(invalid)?;
+        Ok(LeaderboardCursor {
+            score: score.parse().map_err(|_| invalid())?,
+            score_id: score_id.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl fmt::Display for LeaderboardCursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}_{}", self.score, self.score_id)
+    }
+}
+
+/// Which scores a leaderboard ranks
+#[derive(Clone, Debug, Default)]
+pub struct LeaderboardFilter {
+    pub competition_id: Option<i64>,
+    /// Scores from this time on
+    pub from: Option<String>,
+    /// Scores before this time
+    pub to: Option<String>,
+    /// Only each player's best score, otherwise every score
+    pub best_only: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardRow {
+    /// Position on the whole leaderboard, ties go to the earlier score
+    pub rank: i64,
+    pub score_id: i64,
+    pub user_id: i64,
+    pub username: String,
+    pub score: i64,
+    pub level: i64,
+    pub play_time: i64,
+    pub created_at: String,
+}
+
+impl LeaderboardRow {
+    pub fn cursor(&self) -> LeaderboardCursor {
+        LeaderboardCursor {
+            score: self.score,
+            score_id: self.score_id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardTotals {
+    pub participants: i64,
+    pub scores: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct LeaderboardStore {
+    db: Pool<Sqlite>,
+}
+
+impl LeaderboardStore {
+    pub fn new(db: Pool<Sqlite>) -> Self {
+        Self { db }
+    }
+
+    // One page of the leaderboard, starting after `cursor`. Players banned for the time
+    // being are left out of every leaderboard
+    pub async fn get_page(
+        &self,
+        filter: &LeaderboardFilter,
+        cursor: Option<LeaderboardCursor>,
+        limit: i64,
+    ) -> Result<Vec<LeaderboardRow>, Error> {
+        let cursor_score = cursor.map(|cursor| cursor.score);
+        let cursor_id = cursor.map(|cursor| cursor.score_id);
+        let rows = sqlx::query_as!(
+            LeaderboardRow,
+            r#"
+            WITH filtered AS (
+                SELECT id, user_id, score, level, play_time, created_at,
+                    ROW_NUMBER() OVER (PARTITION BY user_id ORDER BY score DESC, id ASC) as user_position
+                FROM scores
+                WHERE voided_at IS NULL
+                    AND (?1 IS NULL OR competition_id = ?1)
+                    AND (?2 IS NULL OR created_at >= ?2)
+                    AND (?3 IS NULL OR created_at < ?3)
+                    AND user_id NOT IN (
+                        SELECT u.id FROM users u
+                        JOIN active_bans x ON x.pubkey = u.nostr_pubkey
+                    )
+            ),
+            ranked AS (
+                SELECT id, user_id, score, level, play_time, created_at,
+                    ROW_NUMBER() OVER (ORDER BY score DESC, id ASC) as position
+                FROM filtered
+                WHERE ?4 = 0 OR user_position = 1
+            )
+            SELECT r.position as "rank!: i64", r.id as "score_id!: i64", r.user_id as "user_id!: i64",
+                u.username as "username!", r.score as "score!: i64", r.level as "level!: i64",
+                r.play_time as "play_time!: i64", r.created_at as "created_at!"
+            FROM ranked r
+            JOIN users u ON u.id = r.user_id
+            WHERE ?5 IS NULL OR r.score < ?5 OR (r.score = ?5 AND r.id > ?6)
+            ORDER BY r.position ASC
+            LIMIT ?7
+            "#,
+            filter.competition_id,
+            filter.from,
+            filter.to,
+            filter.best_only,
+            cursor_score,
+            cursor_id,
+            limit
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(rows)
+    }
+
+    // The user's best placed row with up to `neighbours` rows either side of it, empty if
+    // they have no score on the leaderboard
+    pub async fn get_user_standing(
+        &self,
+        filter: &LeaderboardFilter,
+        user_id: i64,
+        neighbours: i64,
+    ) -> Result<Vec<LeaderboardRow>, Error> {
+        let rows = sqlx::query_as!(
+            LeaderboardRow,
+            r#"
+            WITH filtered AS (
+                SELECT id, user_id, score, level, play_time, created_at,
+                    ROW_NUMBER() OVER (PARTITION BY user_id ORDER BY score DESC, id ASC) as user_position
+                FROM scores
+                WHERE voided_at IS NULL
+                    AND (?1 IS NULL OR competition_id = ?1)
+                    AND (?2 IS NULL OR created_at >= ?2)
+                    AND (?3 IS NULL OR created_at < ?3)
+                    AND user_id NOT IN (
+                        SELECT u.id FROM users u
+                        JOIN active_bans x ON x.pubkey = u.nostr_pubkey
+                    )
+            ),
+            ranked AS (
+                SELECT id, user_id, score, level, play_time, created_at,
+                    ROW_NUMBER() OVER (ORDER BY score DESC, id ASC) as position
+                FROM filtered
+                WHERE ?4 = 0 OR user_position = 1
+            ),
+            mine AS (
+                SELECT MIN(position) as position FROM ranked WHERE user_id = ?5
+            )
+            SELECT r.position as "rank!: i64", r.id as "score_id!: i64", r.user_id as "user_id!: i64",
+                u.username as "username!", r.score as "score!: i64", r.level as "level!: i64",
+                r.play_time as "play_time!: i64", r.created_at as "created_at!"
+            FROM ranked r
+            JOIN users u ON u.id = r.user_id
+            JOIN mine m ON r.position BETWEEN m.position - ?6 AND m.position + ?6
+            ORDER BY r.position ASC
+            "#,
+            filter.competition_id,
+            filter.from,
+            filter.to,
+            filter.best_only,
+            user_id,
+            neighbours
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(rows)
+    }
+
+    pub async fn get_totals(&self, filter: &LeaderboardFilter) -> Result<LeaderboardTotals, Error> {
+        let totals = sqlx::query_as!(
+            LeaderboardTotals,
+            r#"
+            SELECT COUNT(DISTINCT user_id) as "participants!: i64", COUNT(*) as "scores!: i64"
+            FROM scores
+            WHERE voided_at IS NULL
+                AND (?1 IS NULL OR competition_id = ?1)
+                AND (?2 IS NULL OR created_at >= ?2)
+                AND (?3 IS NULL OR created_at < ?3)
+                AND user_id NOT IN (
+                    SELECT u.id FROM users u
+                    JOIN active_bans x ON x.pubkey = u.nostr_pubkey
+                )
+            "#,
+            filter.competition_id,
+            filter.from,
+            filter.to
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(totals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_window_bounds_cover_the_calendar_period() {
+        let at = datetime!(2025-07-03 18:30 UTC);
+        let utc = DayBoundary::default();
+
+        let window = |from: &str, to: &str| Some((String::from(from), String::from(to)));
+        assert_eq!(
+            LeaderboardWindow::Day.bounds(at, &utc).unwrap(),
+            window("2025-07-03T00:00:00Z", "2025-07-04T00:00:00Z")
+        );
+        assert_eq!(
+            LeaderboardWindow::Week.bounds(at, &utc).unwrap(),
+            window("2025-06-30T00:00:00Z", "2025-07-07T00:00:00Z")
+        );
+        assert_eq!(
+            LeaderboardWindow::Month.bounds(at, &utc).unwrap(),
+            window("2025-07-01T00:00:00Z", "2025-08-01T00:00:00Z")
+        );
+        assert_eq!(LeaderboardWindow::All.bounds(at, &utc).unwrap(), None);
+
+        // Scores are stored to the whole second, so they sort inside their window
+        let (from, to) = LeaderboardWindow::Day.bounds(at, &utc).unwrap().unwrap();
+        let first = format_time_secs(datetime!(2025-07-03 00:00:00.5 UTC)).unwrap();
+        let last = format_time_secs(datetime!(2025-07-03 23:59:59.9 UTC)).unwrap();
+        assert!(first >= from && first < to);
+        assert!(last >= from && last < to);
+    }
+
+    #[test]
+    fn test_windows_follow_the_daily_competition() {
+        // 14:30 in New York on Thursday the 3rd is still in Wednesday's competition, which
+        // started at 20:00 on Wednesday
+        let at = datetime!(2025-07-03 18:30 UTC);
+        let days = DayBoundary::parse("America/New_York", "20:00").unwrap();
+
+        let window = |from: &str, to: &str| Some((String::from(from), String::from(to)));
+        assert_eq!(
+            LeaderboardWindow::Day.bounds(at, &days).unwrap(),
+            window("2025-07-03T00:00:00Z", "2025-07-04T00:00:00Z")
+        );
+        assert_eq!(
+            LeaderboardWindow::Week.bounds(at, &days).unwrap(),
+            window("2025-07-01T00:00:00Z", "2025-07-08T00:00:00Z")
+        );
+        assert_eq!(
+            LeaderboardWindow::Month.bounds(at, &days).unwrap(),
+            window("2025-07-02T00:00:00Z", "2025-08-02T00:00:00Z")
+        );
+
+        let (start, end) = days.schedule().unwrap().period_containing(at).unwrap();
+        assert_eq!(
+            LeaderboardWindow::Day.bounds(at, &days).unwrap(),
+            window(
+                &format_time_secs(start).unwrap(),
+                &format_time_secs(end).unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_cursor_round_trips() {
+        let cursor = LeaderboardCursor {
+            score: 1200,
+            score_id: 42,
+        };
+
+        assert_eq!(
+            LeaderboardCursor::parse(&cursor.to_string()).unwrap(),
+            cursor
+        );
+        assert!(LeaderboardCursor::parse("1200").is_err());
+        assert!(LeaderboardCursor::parse("a_b").is_err());
+    }
+}
@@ -0,0 +1,535 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use time::OffsetDateTime;
+
+use crate::{domain::Error, format_time, Balance};
+
+// Where sats can sit. Debits are stored as positive amounts and credits as negative,
+// so every transaction sums to zero and so does the whole ledger
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedgerAccount {
+    /// Sats held by the Lightning backend
+    Lightning,
+    /// Rake kept from competition pots
+    House,
+    /// Routing fees paid when sending sats out
+    Fees,
+    /// Entry fees collected for a competition
+    Pot(i64),
+    /// Sats owed to a player
+    User(i64),
+    /// Sats the operator put in, e.g. what the wallet held before the ledger was started
+    Equity,
+}
+
+impl LedgerAccount {
+    pub fn code(&self) -> String {
+        match self {
+            LedgerAccount::Lightning => String::from("lightning"),
+            LedgerAccount::House => String::from("house"),
+            LedgerAccount::Fees => String::from("fees"),
+            LedgerAccount::Pot(competition_id) => format!("pot:{}", competition_id),
+            LedgerAccount::User(user_id) => format!("user:{}", user_id),
+            LedgerAccount::Equity => String::from("equity"),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LedgerAccount::Lightning => "asset",
+            LedgerAccount::House => "revenue",
+            LedgerAccount::Fees => "expense",
+            LedgerAccount::Pot(_) | LedgerAccount::User(_) => "liability",
+            LedgerAccount::Equity => "equity",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Posting {
+    pub account: LedgerAccount,
+    pub amount_sats: i64,
+}
+
+impl Posting {
+    pub fn debit(account: LedgerAccount, amount_sats: i64) -> Self {
+        Self {
+            account,
+            amount_sats,
+        }
+    }
+
+    pub fn credit(account: LedgerAccount, amount_sats: i64) -> Self {
+        Self {
+            account,
+            amount_sats: -amount_sats,
+        }
+    }
+}
+
+// A transaction must move sats between at least two accounts and balance to zero
+pub fn validate_postings(postings: &[Posting]) -> Result<(), Error> {
+    if postings.len() < 2 {
+        return Err(Error::InvalidInput(String::from(
+            "A ledger transaction needs at least two entries",
+        )));
+    }
+    if postings.iter().any(|posting| posting.amount_sats == 0) {
+        return Err(Error::InvalidInput(String::from(
+            "Ledger entries cannot be zero",
+        )));
+    }
+
+    let total: i64 = postings.iter().map(|posting| posting.amount_sats).sum();
+    if total != 0 {
+        return Err(Error::InvalidInput(format!(
+            "Ledger transaction is out of balance by {} sats",
+            total
+        )));
+    }
+
+    Ok(())
+}
+
+// Record a transaction on an open database connection so it can share a database
+// transaction with the change that caused it. Returns false if it was already recorded
+pub async fn record_transaction(
+    conn: &mut SqliteConnection,
+    kind: &str,
+    reference: &str,
+    description: &str,
+    postings: &[Posting],
+) -> Result<bool, Error> {
+    validate_postings(postings)?;
+
+    let now = format_time(OffsetDateTime::now_utc())?;
+
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO ledger_transactions (kind, reference, description, created_at)
+        VALUES (?, ?, ?, ?)
+        ON CONFLICT(kind, reference) DO NOTHING
+        "#,
+        kind,
+        reference,
+        description,
+        now
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+    let transaction_id = result.last_insert_rowid();
+
+    for posting in postings {
+        let code = posting.account.code();
+        let account_kind = posting.account.kind();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO ledger_accounts (code, kind, created_at)
+            VALUES (?, ?, ?)
+            ON CONFLICT(code) DO NOTHING
+            "#,
+            code,
+            account_kind,
+            now
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO ledger_entries (transaction_id, account_id, amount_sats, created_at)
+            SELECT ?, id, ?, ? FROM ledger_accounts WHERE code = ?
+            "#,
+            transaction_id,
+            posting.amount_sats,
+            now,
+            code
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(true)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountBalance {
+    pub code: String,
+    pub kind: String,
+    pub balance_sats: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconciliationReport {
+    pub checked_at: String,
+    /// Sats the ledger says the Lightning backend should hold
+    pub ledger_sats: i64,
+    pub backend_available_sats: i64,
+    pub backend_total_sats: i64,
+    /// Backend total minus what the ledger expects, anything but 0 needs looking into
+    pub difference_sats: i64,
+    pub invariant_violations: Vec<String>,
+}
+
+impl ReconciliationReport {
+    pub fn new(ledger_sats: i64, backend: &Balance, invariant_violations: Vec<String>) -> Self {
+        // The backend reports balances in msats
+        let backend_total_sats = backend.total / 1000;
+
+        Self {
+            checked_at: OffsetDateTime::now_utc().to_string(),
+            ledger_sats,
+            backend_available_sats: backend.available / 1000,
+            backend_total_sats,
+            difference_sats: backend_total_sats - ledger_sats,
+            invariant_violations,
+        }
+    }
+
+    pub fn is_balanced(&self) -> bool {
+        self.difference_sats == 0 && self.invariant_violations.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LedgerStore {
+    db: Pool<Sqlite>,
+}
+
+impl LedgerStore {
+    pub fn new(db: Pool<Sqlite>) -> Self {
+        Self { db }
+    }
+
+    // Record a transaction on its own
+    pub async fn record(
+        &self,
+        kind: &str,
+        reference: &str,
+        description: &str,
+        postings: &[Posting],
+    ) -> Result<bool, Error> {
+        let mut tx = self.db.begin().await?;
+        let recorded = record_transaction(&mut tx, kind, reference, description, postings).await?;
+        tx.commit().await?;
+
+        Ok(recorded)
+    }
+
+    pub async fn account_balance(&self, account: LedgerAccount) -> Result<i64, Error> {
+        let code = account.code();
+
+        let result = sqlx::query!(
+            r#"
+            SELECT COALESCE(SUM(e.amount_sats), 0) as "balance!: i64"
+            FROM ledger_entries e
+            JOIN ledger_accounts a ON e.account_id = a.id
+            WHERE a.code = ?
+            "#,
+            code
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(result.balance)
+    }
+
+    // Balance of every account, debits positive and credits negative
+    pub async fn balances(&self) -> Result<Vec<AccountBalance>, Error> {
+        let balances = sqlx::query_as!(
+            AccountBalance,
+            r#"
+            SELECT a.code, a.kind, COALESCE(SUM(e.amount_sats), 0) as "balance_sats!: i64"
+            FROM ledger_accounts a
+            LEFT JOIN ledger_entries e ON e.account_id = a.id
+            GROUP BY a.id
+            ORDER BY a.code ASC
+            "#
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(balances)
+    }
+
+    // Look for anything that breaks the ledger's rules, an empty list means it is healthy
+    pub async fn check_invariants(&self) -> Result<Vec<String>, Error> {
+        let mut violations = vec![];
+
+        let unbalanced = sqlx::query!(
+            r#"
+            SELECT t.kind, t.reference, SUM(e.amount_sats) as "total!: i64"
+            FROM ledger_transactions t
+            JOIN ledger_entries e ON e.transaction_id = t.id
+            GROUP BY t.id
+            HAVING SUM(e.amount_sats) != 0
+            "#
+        )
+        .fetch_all(&self.db)
+        .await?;
+        for row in unbalanced {
+            violations.push(format!(
+                "Transaction {} {} is out of balance by {} sats",
+                row.kind, row.reference, row.total
+            ));
+        }
+
+        let empty = sqlx::query!(
+            r#"
+            SELECT t.kind, t.reference
+            FROM ledger_transactions t
+            LEFT JOIN ledger_entries e ON e.transaction_id = t.id
+            WHERE e.id IS NULL
+            "#
+        )
+        .fetch_all(&self.db)
+        .await?;
+        for row in empty {
+            violations.push(format!(
+                "Transaction {} {} has no entries",
+                row.kind, row.reference
+            ));
+        }
+
+        // Liabilities can never turn into debts owed to us
+        let overdrawn = sqlx::query!(
+            r#"
+            SELECT a.code, SUM(e.amount_sats) as "balance!: i64"
+            FROM ledger_accounts a
+            JOIN ledger_entries e ON e.account_id = a.id
+            WHERE a.kind = 'liability'
+            GROUP BY a.id
+            HAVING SUM(e.amount_sats) > 0
+            "#
+        )
+        .fetch_all(&self.db)
+        .await?;
+        for row in overdrawn {
+            violations.push(format!(
+                "Account {} is overdrawn by {} sats",
+                row.code, row.balance
+            ));
+        }
+
+        // Pots of running or voided competitions must match the entry fees they still hold,
+        // settling a competition with a winner empties its pot into the prize and rake
+        let pots = sqlx::query!(
+            r#"
+            SELECT
+                c.id,
+                CASE
+                    WHEN c.status = 'settled'
+                        AND EXISTS (SELECT 1 FROM prize_payouts p WHERE p.competition_id = c.id)
+                    THEN 0
+                    ELSE c.pot_sats
+                END as "expected_pot!: i64",
+                COALESCE((
+                    SELECT -SUM(e.amount_sats)
+                    FROM ledger_entries e
+                    JOIN ledger_accounts a ON e.account_id = a.id
+                    WHERE a.code = 'pot:' || c.id
+                ), 0) as "ledger_pot!: i64"
+            FROM competitions c
+            WHERE c.status IN ('open', 'closed', 'settling', 'settled', 'voided')
+            "#
+        )
+        .fetch_all(&self.db)
+        .await?;
+        for row in pots {
+            if row.expected_pot != row.ledger_pot {
+                violations.push(format!(
+                    "Competition {} pot should hold {} sats but the ledger holds {} sats",
+                    row.id, row.expected_pot, row.ledger_pot
+                ));
+            }
+        }
+
+        Ok(violations)
+    }
+
+    // Compare what the ledger expects the Lightning backend to hold with what it reports.
+    // The first reconciliation opens the books, posting whatever the wallet held beyond
+    // the pots and balances carried over by the migration to equity
+    pub async fn reconcile(&self, backend: &Balance) -> Result<ReconciliationReport, Error> {
+        self.open_books(backend).await?;
+
+        let ledger_sats = self.account_balance(LedgerAccount::Lightning).await?;
+        let violations = self.check_invariants().await?;
+
+        Ok(ReconciliationReport::new(ledger_sats, backend, violations))
+    }
+
+    // Post what the wallet holds beyond the ledger's own opening balances to equity, as
+    // long as nothing else has been recorded yet. Returns false if there was nothing to post
+    async fn open_books(&self, backend: &Balance) -> Result<bool, Error> {
+        let mut tx = self.db.begin().await?;
+
+        let result = sqlx::query!(
+            r#"
+            SELECT
+                EXISTS (
+                    SELECT 1 FROM ledger_transactions WHERE description != 'Opening balance'
+                ) as "started!: bool",
+                COALESCE((
+                    SELECT SUM(e.amount_sats)
+                    FROM ledger_entries e
+                    JOIN ledger_accounts a ON e.account_id = a.id
+                    WHERE a.code = 'lightning'
+                ), 0) as "ledger_sats!: i64"
+            "#
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let opening_sats = backend.total / 1000 - result.ledger_sats;
+        if result.started || opening_sats == 0 {
+            return Ok(false);
+        }
+
+        let recorded = record_transaction(
+            &mut tx,
+            "opening_balance",
+            "lightning",
+            "Opening balance",
+            &[
+                Posting::debit(LedgerAccount::Lightning, opening_sats),
+                Posting::credit(LedgerAccount::Equity, opening_sats),
+            ],
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(recorded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        domain::testing::{create_test_user, pay_entry, test_competition_store, test_db},
+        Currency, Network, PaymentStore,
+    };
+
+    fn wallet(total_sats: i64) -> Balance {
+        Balance {
+            id: String::from("balance"),
+            wallet_id: String::from("wallet"),
+            effective_time: OffsetDateTime::now_utc().to_string(),
+            available: total_sats * 1000,
+            total: total_sats * 1000,
+            network: Network::Signet,
+            currency: Currency::Btc,
+        }
+    }
+
+    #[test]
+    fn test_balanced_postings_are_valid() {
+        let postings = [
+            Posting::debit(LedgerAccount::Lightning, 500),
+            Posting::credit(LedgerAccount::Pot(1), 500),
+        ];
+
+        assert!(validate_postings(&postings).is_ok());
+    }
+
+    #[test]
+    fn test_unbalanced_postings_are_rejected() {
+        let postings = [
+            Posting::debit(LedgerAccount::Pot(1), 500),
+            Posting::credit(LedgerAccount::User(2), 450),
+        ];
+
+        assert!(validate_postings(&postings).is_err());
+    }
+
+    #[test]
+    fn test_single_and_zero_postings_are_rejected() {
+        assert!(validate_postings(&[Posting::debit(LedgerAccount::House, 0)]).is_err());
+        assert!(validate_postings(&[
+            Posting::debit(LedgerAccount::Lightning, 0),
+            Posting::credit(LedgerAccount::Pot(1), 0),
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_account_codes() {
+        assert_eq!(LedgerAccount::Pot(7).code(), "pot:7");
+        assert_eq!(LedgerAccount::User(3).code(), "user:3");
+        assert_eq!(LedgerAccount::Lightning.kind(), "asset");
+    }
+
+    #[tokio::test]
+    async fn test_first_reconciliation_opens_the_books() {
+        let db = test_db().await;
+        let ledger = LedgerStore::new(db.clone());
+
+        let report = ledger.reconcile(&wallet(10_000)).await.unwrap();
+        assert!(report.is_balanced());
+        assert_eq!(
+            ledger.account_balance(LedgerAccount::Equity).await.unwrap(),
+            -10_000
+        );
+
+        // Once the books are open a difference is a discrepancy, not more equity
+        let report = ledger.reconcile(&wallet(12_000)).await.unwrap();
+        assert_eq!(report.difference_sats, 2_000);
+        assert_eq!(
+            ledger.account_balance(LedgerAccount::Equity).await.unwrap(),
+            -10_000
+        );
+    }
+
+    #[tokio::test]
+    async fn test_settled_pots_are_checked() {
+        let db = test_db().await;
+        let ledger = LedgerStore::new(db.clone());
+        let payments = PaymentStore::new(db.clone());
+        let competitions = test_competition_store(&db);
+        let competition = competitions.current_daily().await.unwrap();
+        let user = create_test_user(&db).await;
+        pay_entry(&db, user.id, competition.id, 500, 1).await;
+
+        // A settled competition nobody won still holds its entry fees
+        competitions
+            .update_status(competition.id, "settled")
+            .await
+            .unwrap();
+        assert!(ledger.check_invariants().await.unwrap().is_empty());
+
+        // Once a winner is recorded the pot has to be empty
+        let competition = competitions
+            .find_by_id(competition.id)
+            .await
+            .unwrap()
+            .unwrap();
+        sqlx::query!(
+            "INSERT INTO prize_payouts (user_id, date, score, amount_sats, status, created_at, updated_at, competition_id) VALUES (?, '2025-01-01', 1000, 450, 'pending', '', '', ?)",
+            user.id,
+            competition.id
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        assert_eq!(ledger.check_invariants().await.unwrap().len(), 1);
+
+        sqlx::query!("DELETE FROM prize_payouts")
+            .execute(&db)
+            .await
+            .unwrap();
+        payments
+            .record_winner(competition.id, "2025-01-01", user.id, 1000)
+            .await
+            .unwrap();
+        assert!(ledger.check_invariants().await.unwrap().is_empty());
+    }
+}
@@ -1,9 +1,21 @@
+mod admin;
+mod competitions;
 mod games;
+mod leaderboards;
+mod ledger;
 mod payments;
+mod payouts;
+#[cfg(test)]
+pub(crate) mod testing;
 mod users;
 
+pub use admin::*;
+pub use competitions::*;
 pub use games::*;
+pub use leaderboards::*;
+pub use ledger::*;
 pub use payments::*;
+pub use payouts::*;
 pub use users::*;
 
 use axum::response::{IntoResponse, Response};
@@ -24,8 +36,17 @@ pub enum Error {
     #[error("Authentication error: {0}")]
     Authentication(String),
 
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("Thread error: {0}")]
     Thread(String),
+
+    #[error("Signing error: {0}")]
+    Signing(String),
 }
 
 pub fn map_error(err: Error) -> Response {
@@ -33,6 +54,8 @@ pub fn map_error(err: Error) -> Response {
         Error::NotFound(msg) => (StatusCode::NOT_FOUND, msg).into_response(),
         Error::InvalidInput(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
         Error::Authentication(msg) => (StatusCode::UNAUTHORIZED, msg).into_response(),
+        Error::Forbidden(msg) => (StatusCode::FORBIDDEN, msg).into_response(),
+        Error::Conflict(msg) => (StatusCode::CONFLICT, msg).into_response(),
         _ => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error").into_response(),
     }
 }
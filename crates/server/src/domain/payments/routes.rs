@@ -10,7 +10,11 @@ use serde_json::json;
 use std::sync::Arc;
 use time::OffsetDateTime;
 
-use crate::{lightning::PaymentStatus, map_error, nostr_extractor::NostrAuth, startup::AppState};
+use crate::{
+    invoice_amount_msats, map_error, nostr_extractor::NostrAuth, parse_date, startup::AppState,
+    Competition, Error, GamePayment, GamePaymentStatus, PaymentStatus, PayoutKind, PayoutStatus,
+    TopScorer,
+};
 
 // Get the status of a payment
 pub async fn check_payment_status(
@@ -45,13 +49,16 @@ pub async fn check_payment_status(
         Err(e) => return Err(map_error(e)),
     };
 
-    // If payment is already marked as paid in our database
-    if payment.status == "paid" {
+    let now = OffsetDateTime::now_utc();
+
+    // Payments that are no longer pending will not change any more
+    if payment.status != GamePaymentStatus::Pending {
         return Ok((
             StatusCode::OK,
             Json(json!({
-                "status": "paid",
-                "payment_id": payment.payment_id
+                "status": payment.status,
+                "payment_id": payment.payment_id,
+                "expires_at": payment.expires_at
             })),
         ));
     }
@@ -63,65 +70,50 @@ pub async fn check_payment_status(
         .await
     {
         Ok(Some(api_payment)) => {
-            let status = api_payment["status"].as_str().unwrap_or("unknown");
-
-            match status {
-                "completed" => {
-                    // Update our record
-                    if let Err(e) = state
-                        .payment_store
-                        .update_payment_status(&payment_id, "paid")
-                        .await
-                    {
-                        error!("Failed to update payment status: {}", e);
-                    }
+            match api_payment.status {
+                PaymentStatus::Completed => {
+                    // Update our record and the competition pot, or the balance if it closed
+                    let status = match state.payment_store.mark_payment_paid(&payment_id).await {
+                        Ok(Some(paid)) => {
+                            state.live_board.invalidate();
+                            paid.status
+                        }
+                        Ok(None) => GamePaymentStatus::Paid,
+                        Err(e) => {
+                            error!("Failed to update payment status: {}", e);
+                            GamePaymentStatus::Paid
+                        }
+                    };
 
                     Ok((
                         StatusCode::OK,
                         Json(json!({
-                            "status": "paid",
+                            "status": status,
                             "payment_id": payment_id
                         })),
                     ))
                 }
-                "failed" => {
+                PaymentStatus::Failed => {
                     // Update our record
-                    if let Err(e) = state
-                        .payment_store
-                        .update_payment_status(&payment_id, "failed")
-                        .await
-                    {
+                    if let Err(e) = state.payment_store.fail_payment(&payment_id).await {
                         error!("Failed to update payment status: {}", e);
                     }
 
                     Ok((
                         StatusCode::OK,
                         Json(json!({
-                            "status": "failed",
+                            "status": GamePaymentStatus::Failed,
                             "payment_id": payment_id
                         })),
                     ))
                 }
-                _ => Ok((
-                    StatusCode::OK,
-                    Json(json!({
-                        "status": "pending",
-                        "payment_id": payment_id
-                    })),
-                )),
+                PaymentStatus::Sending | PaymentStatus::Receiving => {
+                    Ok(pending_or_expired(&state, &payment, now).await)
+                }
             }
         }
-        Ok(None) => {
-            // Payment not found in Lightning API, consider it pending
-            Ok((
-                StatusCode::OK,
-                Json(json!({
-                    "status": "pending",
-                    "payment_id": payment_id,
-                    "message": "Payment not found in Lightning API yet"
-                })),
-            ))
-        }
+        // Payment not found in Lightning API yet, consider it pending until it expires
+        Ok(None) => Ok(pending_or_expired(&state, &payment, now).await),
         Err(e) => {
             error!("Error checking payment status with Lightning API: {}", e);
 
@@ -131,6 +123,8 @@ pub async fn check_payment_status(
                 Json(json!({
                     "status": payment.status,
                     "payment_id": payment_id,
+                    "expires_at": payment.expires_at,
+                    "seconds_remaining": payment.seconds_remaining(now),
                     "error": "Could not verify payment status with payment provider."
                 })),
             ))
@@ -138,6 +132,66 @@ pub async fn check_payment_status(
     }
 }
 
+// Status of an unpaid payment, expiring it once its invoice can no longer be paid
+async fn pending_or_expired(
+    state: &AppState,
+    payment: &GamePayment,
+    now: OffsetDateTime,
+) -> (StatusCode, Json<serde_json::Value>) {
+    if payment.is_expired(now) {
+        if let Err(e) = state
+            .payment_store
+            .expire_payment(&payment.payment_id)
+            .await
+        {
+            error!("Failed to expire payment {}: {}", payment.payment_id, e);
+        }
+
+        return (
+            StatusCode::OK,
+            Json(json!({
+                "status": GamePaymentStatus::Expired,
+                "payment_id": payment.payment_id,
+                "expires_at": payment.expires_at
+            })),
+        );
+    }
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "status": GamePaymentStatus::Pending,
+            "payment_id": payment.payment_id,
+            "expires_at": payment.expires_at,
+            "seconds_remaining": payment.seconds_remaining(now)
+        })),
+    )
+}
+
+// Find the daily competition for a YYYY-MM-DD date if the user had its top score
+async fn check_daily_top_scorer(
+    state: &AppState,
+    user_id: i64,
+    date: &str,
+) -> Result<Option<(Competition, TopScorer)>, Error> {
+    let Some(competition) = state
+        .competition_store
+        .find_daily(parse_date(date)?)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    match state
+        .competition_store
+        .get_top_scorer(competition.id)
+        .await?
+    {
+        Some(top_scorer) if top_scorer.user_id == user_id => Ok(Some((competition, top_scorer))),
+        _ => Ok(None),
+    }
+}
+
 // Structure for the winning player information
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DailyWinnerInfo {
@@ -151,7 +205,8 @@ pub struct DailyWinnerInfo {
 #[derive(Debug, Deserialize)]
 pub struct ClaimPrizeRequest {
     pub invoice: String,
-    pub date: String,
+    pub date: Option<String>,
+    pub competition_id: Option<i64>,
 }
 
 // Return info about prize eligibility
@@ -169,74 +224,54 @@ pub async fn check_prize_eligibility(
         Err(e) => return Err(map_error(e)),
     };
 
-    // Get yesterday's date in YYYY-MM-DD format for completed day calculation
-    let yesterday = (OffsetDateTime::now_utc() - time::Duration::days(1))
-        .format(&time::format_description::well_known::Iso8601::DEFAULT)
-        .unwrap()
-        .chars()
-        .take(10) // Take just YYYY-MM-DD part
-        .collect::<String>();
+    // Yesterday's date in YYYY-MM-DD format, counted the way the daily competition counts days
+    let today = state
+        .competition_store
+        .day_boundary()
+        .day_of(OffsetDateTime::now_utc());
+    let yesterday = today.previous_day().unwrap_or(today).to_string();
 
     // Check if user was a top scorer for yesterday
-    let was_top_scorer = match state
-        .payment_store
-        .check_top_scorer(user.id, &yesterday)
-        .await
+    let (competition, top_scorer) = match check_daily_top_scorer(&state, user.id, &yesterday).await
     {
-        Ok(is_top) => is_top,
+        Ok(Some(winner)) => winner,
+        Ok(None) => {
+            return Ok((
+                StatusCode::OK,
+                Json(json!({
+                    "eligible": false,
+                    "message": "You were not the top scorer for yesterday's games"
+                })),
+            ));
+        }
         Err(e) => {
             error!("Failed to check top scorer: {}", e);
             return Err(map_error(e));
         }
     };
 
-    if !was_top_scorer {
-        return Ok((
-            StatusCode::OK,
-            Json(json!({
-                "eligible": false,
-                "message": "You were not the top scorer for yesterday's games"
-            })),
-        ));
-    }
-
-    // Check if prize was already claimed
-    let already_claimed = match state
+    // Check if prize was already recorded, claimed or paid
+    match state
         .payment_store
-        .check_prize_claimed(user.id, &yesterday)
+        .get_prize_for_competition(user.id, competition.id)
         .await
     {
-        Ok(claimed) => claimed,
-        Err(e) => {
-            error!("Failed to check if prize was claimed: {}", e);
-            return Err(map_error(e));
+        Ok(Some(prize)) if prize.status.is_claimable() => {
+            // Prize is waiting for an invoice, or a new one after its payment failed
+            return Ok((
+                StatusCode::OK,
+                Json(json!({
+                    "eligible": true,
+                    "date": yesterday,
+                    "competition_id": competition.id,
+                    "amount": prize.amount_sats,
+                    "message": "You can claim your prize by submitting a Lightning invoice",
+                    "status": prize.status,
+                    "has_payment_request": prize.payment_request.is_some()
+                })),
+            ));
         }
-    };
-
-    if already_claimed {
-        // Check if it was already paid or is pending
-        let prize = match state
-            .payment_store
-            .get_pending_prize_for_user(user.id, &yesterday)
-            .await
-        {
-            Ok(Some(prize)) => prize,
-            Ok(None) => {
-                return Ok((
-                    StatusCode::OK,
-                    Json(json!({
-                        "eligible": false,
-                        "message": "You have already claimed your prize for yesterday"
-                    })),
-                ));
-            }
-            Err(e) => {
-                error!("Failed to get pending prize: {}", e);
-                return Err(map_error(e));
-            }
-        };
-
-        if prize.status == "paid" {
+        Ok(Some(prize)) if prize.status == PayoutStatus::Paid => {
             return Ok((
                 StatusCode::OK,
                 Json(json!({
@@ -244,32 +279,25 @@ pub async fn check_prize_eligibility(
                     "message": "Your prize has already been paid"
                 })),
             ));
-        } else {
-            // Prize is pending payment
+        }
+        Ok(Some(_)) => {
             return Ok((
                 StatusCode::OK,
                 Json(json!({
-                    "eligible": true,
-                    "date": yesterday,
-                    "amount": prize.amount_sats,
-                    "message": "You can claim your prize by submitting a Lightning invoice",
-                    "status": "pending",
-                    "has_payment_request": prize.payment_request.is_some()
+                    "eligible": false,
+                    "message": "You have already claimed your prize for yesterday"
                 })),
             ));
         }
-    }
-
-    // Calculate prize amount (90% of all entry fees for that day)
-    let total_games = match state.payment_store.count_games_for_date(&yesterday).await {
-        Ok(count) => count,
+        Ok(None) => (),
         Err(e) => {
-            error!("Failed to count games: {}", e);
+            error!("Failed to check if prize was claimed: {}", e);
             return Err(map_error(e));
         }
     };
 
-    let prize_amount = (total_games * 450) as i64; // 90% of 500 sats * number of games
+    // Prize is the competition pot less the house rake
+    let prize_amount = competition.prize_sats();
 
     if prize_amount <= 0 {
         return Ok((
@@ -284,12 +312,7 @@ pub async fn check_prize_eligibility(
     // Record the winner if not already recorded
     match state
         .payment_store
-        .record_daily_winner(
-            user.id,
-            &yesterday,
-            0, // We don't have the score here, it will be updated later
-            prize_amount,
-        )
+        .record_winner(competition.id, &yesterday, user.id, top_scorer.score)
         .await
     {
         Ok(_) => (),
@@ -305,6 +328,7 @@ pub async fn check_prize_eligibility(
         Json(json!({
             "eligible": true,
             "date": yesterday,
+            "competition_id": competition.id,
             "amount": prize_amount,
             "message": "You can claim your prize by submitting a Lightning invoice"
         })),
@@ -319,8 +343,8 @@ pub async fn claim_prize(
 ) -> Result<impl IntoResponse, Response> {
     let pubkey = auth.pubkey.to_string();
     info!(
-        "Prize claim request from pubkey: {}, date: {}",
-        pubkey, request.date
+        "Prize claim request from pubkey: {}, date: {:?}, competition: {:?}",
+        pubkey, request.date, request.competition_id
     );
 
     // Find user
@@ -335,156 +359,364 @@ pub async fn claim_prize(
         return Err((StatusCode::BAD_REQUEST, "Invalid Lightning invoice").into_response());
     }
 
-    // Verify eligibility
-    let was_top_scorer = match state
+    // Tournament prizes are claimed by competition, the daily prize by date
+    let competition_id = match (request.competition_id, &request.date) {
+        (Some(competition_id), _) => competition_id,
+        (None, Some(date)) => match check_daily_top_scorer(&state, user.id, date).await {
+            Ok(Some((competition, _))) => competition.id,
+            Ok(None) => {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    "You were not the top scorer for this date",
+                )
+                    .into_response());
+            }
+            Err(e) => {
+                error!("Failed to check top scorer: {}", e);
+                return Err(map_error(e));
+            }
+        },
+        (None, None) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "A date or competition_id is required",
+            )
+                .into_response());
+        }
+    };
+
+    // Verify eligibility, the prize is only recorded for the competition winner
+    let prize = match state
         .payment_store
-        .check_top_scorer(user.id, &request.date)
+        .get_prize_for_competition(user.id, competition_id)
         .await
     {
-        Ok(is_top) => is_top,
+        Ok(Some(p)) => p,
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                "No eligible prize found for this competition",
+            )
+                .into_response());
+        }
         Err(e) => {
-            error!("Failed to check top scorer: {}", e);
+            error!("Failed to get pending prize: {}", e);
             return Err(map_error(e));
         }
     };
 
-    if !was_top_scorer {
+    // Check if prize has already been paid or is being paid
+    if !prize.status.is_claimable() {
         return Err((
             StatusCode::FORBIDDEN,
-            "You were not the top scorer for this date",
+            format!("Prize has already been claimed, status: {}", prize.status),
         )
             .into_response());
     }
 
-    // Get or create the prize record
-    let prize = match state
-        .payment_store
-        .get_pending_prize_for_user(user.id, &request.date)
+    if invoice_amount_msats(&request.invoice) != Some(prize.amount_sats * 1000) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Invoice amount must match the prize amount",
+        )
+            .into_response());
+    }
+
+    // Queue the payment, it is sent in the background and retried until it goes through.
+    // Winnings sit in the balance until claimed, so they cannot be claimed once spent or withdrawn
+    match state
+        .payout_store
+        .enqueue_prize(prize.id, &request.invoice)
         .await
     {
-        Ok(Some(p)) => p,
-        Ok(None) => {
-            // No pending prize found, check if one was already paid
-            return Err((
-                StatusCode::NOT_FOUND,
-                "No eligible prize found for this date",
-            )
-                .into_response());
+        Ok(Some(queued)) => {
+            info!(
+                "Prize {} queued for payment to user_id: {}, amount: {}",
+                queued.id, user.id, queued.amount_sats
+            );
+
+            Ok((
+                StatusCode::ACCEPTED,
+                Json(json!({
+                    "success": true,
+                    "message": "Prize payment queued",
+                    "prize_id": queued.id,
+                    "status": queued.status,
+                    "amount": queued.amount_sats
+                })),
+            ))
         }
+        Ok(None) => Err((
+            StatusCode::CONFLICT,
+            "Prize has already been claimed, spent or withdrawn from your balance",
+        )
+            .into_response()),
         Err(e) => {
-            error!("Failed to get pending prize: {}", e);
-            return Err(map_error(e));
+            error!("Failed to queue prize payment: {}", e);
+            Err(map_error(e))
         }
+    }
+}
+
+// Follow the payment of a claimed prize
+pub async fn get_prize_status(
+    auth: NostrAuth,
+    Path(prize_id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    let pubkey = auth.pubkey.to_string();
+    info!(
+        "Prize status request from pubkey: {}, prize: {}",
+        pubkey, prize_id
+    );
+
+    // Find user
+    let user = match state.user_store.find_by_pubkey(pubkey).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "User not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    let prizes = match state.payment_store.get_prizes_for_user(user.id).await {
+        Ok(prizes) => prizes,
+        Err(e) => return Err(map_error(e)),
+    };
+    let Some(prize) = prizes.into_iter().find(|prize| prize.id == prize_id) else {
+        return Err((StatusCode::NOT_FOUND, "Prize not found").into_response());
     };
 
-    // Check if prize has already been paid
-    if prize.status == "paid" {
-        return Err((StatusCode::FORBIDDEN, "Prize has already been paid").into_response());
+    match state
+        .payout_store
+        .get_job(PayoutKind::Prize, prize.id)
+        .await
+    {
+        Ok(job) => Ok((
+            StatusCode::OK,
+            Json(json!({
+                "prize_id": prize.id,
+                "status": prize.status,
+                "amount": prize.amount_sats,
+                "payment_id": prize.payment_id,
+                "paid_at": prize.paid_at,
+                "attempts": job.as_ref().map(|job| job.attempts),
+                "next_attempt_at": job.as_ref().map(|job| job.next_attempt_at.clone()),
+                "last_error": job.and_then(|job| job.last_error)
+            })),
+        )),
+        Err(e) => Err(map_error(e)),
     }
+}
 
-    // If prize already has an invoice but no payment yet, update it
-    let updated_prize = if prize.payment_request.is_some() {
-        match state
-            .payment_store
-            .update_prize_with_invoice(user.id, &request.date, &request.invoice)
-            .await
-        {
-            Ok(Some(p)) => p,
-            Ok(None) => {
-                return Err(
-                    (StatusCode::NOT_FOUND, "Failed to update prize with invoice").into_response(),
-                );
+// List the prizes the user has won but not claimed yet
+pub async fn get_pending_prizes(
+    auth: NostrAuth,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    let pubkey = auth.pubkey.to_string();
+    info!("Pending prizes request from pubkey: {}", pubkey);
+
+    // Find user
+    let user = match state.user_store.find_by_pubkey(pubkey).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "User not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    match state.payment_store.get_prizes_for_user(user.id).await {
+        Ok(prizes) => {
+            let pending: Vec<_> = prizes
+                .into_iter()
+                .filter(|prize| prize.status != PayoutStatus::Paid)
+                .collect();
+            Ok((StatusCode::OK, Json(pending)))
+        }
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// Get the game credits the user has left and the bundles on offer
+pub async fn get_credit_balance(
+    auth: NostrAuth,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    let pubkey = auth.pubkey.to_string();
+    info!("Credit balance request from pubkey: {}", pubkey);
+
+    // Find user
+    let user = match state.user_store.find_by_pubkey(pubkey).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "User not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    match state.payment_store.get_credit_balances(user.id).await {
+        Ok(balances) => Ok((
+            StatusCode::OK,
+            Json(json!({
+                "balances": balances,
+                "bundles": state.competition_store.bundles()
+            })),
+        )),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// Structure for withdrawing from the balance, paid to either an invoice or a Lightning Address
+#[derive(Debug, Deserialize)]
+pub struct WithdrawRequest {
+    pub amount_sats: i64,
+    pub invoice: Option<String>,
+    pub lightning_address: Option<String>,
+}
+
+// Get the user's sats balance, the withdrawal rules and past withdrawals
+pub async fn get_wallet(
+    auth: NostrAuth,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    let pubkey = auth.pubkey.to_string();
+    info!("Wallet request from pubkey: {}", pubkey);
+
+    // Find user
+    let user = match state.user_store.find_by_pubkey(pubkey).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "User not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    let balance = match state.payment_store.get_user_balance(user.id).await {
+        Ok(balance) => balance,
+        Err(e) => return Err(map_error(e)),
+    };
+
+    match state.payment_store.get_withdrawals_for_user(user.id).await {
+        Ok(withdrawals) => Ok((
+            StatusCode::OK,
+            Json(json!({
+                "balance_sats": balance,
+                "min_withdrawal_sats": state.wallet_settings.min_withdrawal_sats,
+                "withdrawal_fee_sats": state.wallet_settings.withdrawal_fee_sats,
+                "withdrawal_fee_percent": state.wallet_settings.withdrawal_fee_percent,
+                "withdrawals": withdrawals
+            })),
+        )),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// Withdraw sats from the user's balance
+pub async fn withdraw(
+    auth: NostrAuth,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<WithdrawRequest>,
+) -> Result<impl IntoResponse, Response> {
+    let pubkey = auth.pubkey.to_string();
+    info!(
+        "Withdrawal request from pubkey: {}, amount: {}",
+        pubkey, request.amount_sats
+    );
+
+    // Find user
+    let user = match state.user_store.find_by_pubkey(pubkey).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "User not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    let settings = &state.wallet_settings;
+    if request.amount_sats < settings.min_withdrawal_sats {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "The minimum withdrawal is {} sats",
+                settings.min_withdrawal_sats
+            ),
+        )
+            .into_response());
+    }
+
+    // Fetch an invoice for Lightning Addresses, pasted invoices must be for the exact amount
+    let (destination, invoice) = match (request.invoice, request.lightning_address) {
+        (Some(invoice), None) => {
+            if !invoice.starts_with("lnbc") {
+                return Err((StatusCode::BAD_REQUEST, "Invalid Lightning invoice").into_response());
             }
-            Err(e) => {
-                error!("Failed to update prize with invoice: {}", e);
-                return Err(map_error(e));
+            if invoice_amount_msats(&invoice) != Some(request.amount_sats * 1000) {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    "Invoice amount must match the amount withdrawn",
+                )
+                    .into_response());
             }
+            (String::from("bolt11"), invoice)
         }
-    } else {
-        // First time adding an invoice
-        match state
-            .payment_store
-            .update_prize_with_invoice(user.id, &request.date, &request.invoice)
+        (None, Some(address)) => match state
+            .lightning_service
+            .resolve_lightning_address(&address, request.amount_sats)
             .await
         {
-            Ok(Some(p)) => p,
-            Ok(None) => {
-                return Err(
-                    (StatusCode::NOT_FOUND, "Failed to update prize with invoice").into_response(),
-                );
-            }
+            Ok(invoice) => (address, invoice),
             Err(e) => {
-                error!("Failed to update prize with invoice: {}", e);
-                return Err(map_error(e));
+                error!("Failed to resolve Lightning Address {}: {}", address, e);
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("Failed to get an invoice from {}: {}", address, e),
+                )
+                    .into_response());
             }
+        },
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Either an invoice or a lightning_address is required",
+            )
+                .into_response());
         }
     };
 
-    // Process payment (could be done asynchronously in production)
-    // For now, we'll do it synchronously
+    let fee_sats = settings.withdrawal_fee(request.amount_sats);
+
+    // Take the sats out of the balance, the payout queue sends them
     match state
-        .lightning_service
-        .pay_winner_invoice(
-            &request.invoice,
-            updated_prize.amount_sats * 1000, // Convert to msats
+        .payment_store
+        .reserve_withdrawal(
+            user.id,
+            request.amount_sats,
+            fee_sats,
+            &destination,
+            &invoice,
         )
         .await
     {
-        Ok(payment_result) => {
-            // Updated for Value type: extract payment ID
-            let payment_id = payment_result["id"]
-                .as_str()
-                .unwrap_or("unknown")
-                .to_string();
-
-            // Update the prize record
-            match state
-                .payment_store
-                .update_prize_status(updated_prize.id, "paid", Some(&payment_id))
-                .await
-            {
-                Ok(_) => {
-                    info!(
-                        "Prize payment successful for user_id: {}, amount: {}",
-                        user.id, updated_prize.amount_sats
-                    );
+        Ok(Some(withdrawal)) => {
+            info!(
+                "Withdrawal {} queued for user_id: {}, amount: {}",
+                withdrawal.id, user.id, request.amount_sats
+            );
 
-                    Ok((
-                        StatusCode::OK,
-                        Json(json!({
-                            "success": true,
-                            "message": "Prize payment sent successfully",
-                            "payment_id": payment_id,
-                            "amount": updated_prize.amount_sats
-                        })),
-                    ))
-                }
-                Err(e) => {
-                    error!("Failed to update prize status: {}", e);
-                    return Err(map_error(e));
-                }
-            }
+            Ok((
+                StatusCode::ACCEPTED,
+                Json(json!({
+                    "success": true,
+                    "withdrawal_id": withdrawal.id,
+                    "status": withdrawal.status,
+                    "amount_sats": request.amount_sats,
+                    "fee_sats": fee_sats
+                })),
+            ))
         }
+        Ok(None) => Err((
+            StatusCode::PAYMENT_REQUIRED,
+            Json(json!({
+                "error": "Insufficient balance",
+                "amount_sats": request.amount_sats,
+                "fee_sats": fee_sats
+            })),
+        )
+            .into_response()),
         Err(e) => {
-            error!("Failed to send prize payment: {}", e);
-
-            // Update the prize status to reflect the payment failure
-            if let Err(update_err) = state
-                .payment_store
-                .update_prize_status(updated_prize.id, "failed", None)
-                .await
-            {
-                error!(
-                    "Failed to update prize status after payment failure: {}",
-                    update_err
-                );
-            }
-
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to send payment: {}", e),
-            )
-                .into_response());
+            error!("Failed to reserve withdrawal: {}", e);
+            Err(map_error(e))
         }
     }
 }
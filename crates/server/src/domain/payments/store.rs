@@ -1,8 +1,56 @@
 use serde::{Deserialize, Serialize};
-use sqlx::{Pool, Row, Sqlite};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::fmt;
 use time::OffsetDateTime;
+use uuid::Uuid;
 
-use crate::domain::Error;
+use crate::{
+    domain::Error, format_time, format_time_secs, parse_time, record_transaction, upsert_job,
+    Competition, LedgerAccount, PayoutKind, PayoutStatus, Posting,
+};
+
+// Where an entry fee payment is. An invoice paid after it expired still counts, since
+// the sats have arrived. One paid after its competition closed is 'credited' to the
+// player's balance instead. Nothing moves on from 'paid', 'credited' or 'failed'
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum GamePaymentStatus {
+    Pending,
+    Paid,
+    Expired,
+    Failed,
+    Credited,
+}
+
+impl GamePaymentStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Paid => "paid",
+            Self::Expired => "expired",
+            Self::Failed => "failed",
+            Self::Credited => "credited",
+        }
+    }
+
+    pub fn can_transition_to(self, next: Self) -> bool {
+        match self {
+            Self::Pending => matches!(
+                next,
+                Self::Paid | Self::Expired | Self::Failed | Self::Credited
+            ),
+            Self::Expired => matches!(next, Self::Paid | Self::Credited),
+            Self::Paid | Self::Failed | Self::Credited => false,
+        }
+    }
+}
+
+impl fmt::Display for GamePaymentStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -12,10 +60,47 @@ pub struct GamePayment {
     pub payment_id: String,
     pub invoice: String,
     pub amount_sats: i64,
-    pub status: String,
+    pub status: GamePaymentStatus,
     pub created_at: String,
     pub updated_at: String,
     pub paid_at: Option<String>,
+    pub competition_id: Option<i64>,
+    pub credits: i64,
+    pub expires_at: Option<String>,
+}
+
+impl GamePayment {
+    // Seconds left to pay the invoice, None if its expiry is unknown
+    pub fn seconds_remaining(&self, now: OffsetDateTime) -> Option<i64> {
+        let expires_at = parse_time(self.expires_at.as_deref()?).ok()?;
+        Some((expires_at - now).whole_seconds().max(0))
+    }
+
+    pub fn is_expired(&self, now: OffsetDateTime) -> bool {
+        self.seconds_remaining(now) == Some(0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameCredit {
+    pub id: i64,
+    pub user_id: i64,
+    pub competition_id: i64,
+    pub delta: i64,
+    pub reason: String,
+    pub payment_id: Option<String>,
+    pub session_id: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreditBalance {
+    pub competition_id: i64,
+    pub competition_name: String,
+    pub kind: String,
+    pub credits: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,19 +113,28 @@ pub struct PrizePayout {
     pub amount_sats: i64,
     pub payment_request: Option<String>,
     pub payment_id: Option<String>,
-    pub status: String,
+    pub status: PayoutStatus,
     pub created_at: String,
     pub updated_at: String,
     pub paid_at: Option<String>,
+    pub competition_id: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct TopScorer {
+pub struct Withdrawal {
+    pub id: i64,
     pub user_id: i64,
-    pub score: i64,
-    pub games_played: i64,
-    pub username: String,
+    pub amount_sats: i64,
+    pub fee_sats: i64,
+    pub destination: String,
+    pub invoice: String,
+    pub payment_id: Option<String>,
+    /// Withdrawals are sent by the payout queue and move through its statuses
+    pub status: PayoutStatus,
+    pub created_at: String,
+    pub updated_at: String,
+    pub paid_at: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -59,28 +153,36 @@ impl PaymentStore {
     }
 
     // Create a new payment record for a game
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_game_payment(
         &self,
         user_id: i64,
+        competition_id: i64,
         payment_id: &str,
         invoice: &str,
         amount_sats: i64,
+        credits: i64,
+        expires_at: OffsetDateTime,
     ) -> Result<GamePayment, Error> {
-        let now = OffsetDateTime::now_utc().to_string();
+        let now = format_time(OffsetDateTime::now_utc())?;
+        let expires_at = format_time_secs(expires_at)?;
 
         let id = sqlx::query!(
             r#"
             INSERT INTO game_payments
-            (user_id, payment_id, invoice, amount_sats, status, created_at, updated_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?)
+            (user_id, payment_id, invoice, amount_sats, status, created_at, updated_at, competition_id, credits, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
             user_id,
             payment_id,
             invoice,
             amount_sats,
-            "pending", // Initial status
+            GamePaymentStatus::Pending,
             now,
-            now
+            now,
+            competition_id,
+            credits,
+            expires_at
         )
         .execute(&self.db)
         .await?
@@ -92,10 +194,13 @@ impl PaymentStore {
             payment_id: payment_id.to_string(),
             invoice: invoice.to_string(),
             amount_sats,
-            status: "pending".to_string(),
+            status: GamePaymentStatus::Pending,
             created_at: now.clone(),
             updated_at: now,
             paid_at: None,
+            competition_id: Some(competition_id),
+            credits,
+            expires_at: Some(expires_at),
         })
     }
 
@@ -104,7 +209,7 @@ impl PaymentStore {
         let payment = sqlx::query_as!(
             GamePayment,
             r#"
-            SELECT id, user_id, payment_id, invoice, amount_sats, status, created_at, updated_at, paid_at
+            SELECT id, user_id, payment_id, invoice, amount_sats, status as "status: GamePaymentStatus", created_at, updated_at, paid_at, competition_id, credits, expires_at
             FROM game_payments
             WHERE payment_id = ?
             "#,
@@ -116,55 +221,210 @@ impl PaymentStore {
         Ok(payment)
     }
 
-    // Update a payment status
-    pub async fn update_payment_status(
+    // Mark a pending payment whose invoice can no longer be paid as expired,
+    // returns false if it was settled in the meantime
+    pub async fn expire_payment(&self, payment_id: &str) -> Result<bool, Error> {
+        self.set_payment_status(payment_id, GamePaymentStatus::Expired)
+            .await
+    }
+
+    // Mark a payment the Lightning backend gave up on as failed,
+    // returns false if it was settled in the meantime
+    pub async fn fail_payment(&self, payment_id: &str) -> Result<bool, Error> {
+        self.set_payment_status(payment_id, GamePaymentStatus::Failed)
+            .await
+    }
+
+    // Move a payment to a status that has nothing else to update with it
+    async fn set_payment_status(
         &self,
         payment_id: &str,
-        status: &str,
-    ) -> Result<Option<GamePayment>, Error> {
-        let now = OffsetDateTime::now_utc().to_string();
-        let paid_at = if status == "paid" {
-            Some(now.clone())
-        } else {
-            None
+        status: GamePaymentStatus,
+    ) -> Result<bool, Error> {
+        let now = format_time(OffsetDateTime::now_utc())?;
+
+        let mut conn = self.db.acquire().await?;
+
+        let Some(from) = check_payment_transition(&mut conn, payment_id, status).await? else {
+            return Ok(false);
         };
 
         let result = sqlx::query!(
             r#"
             UPDATE game_payments
-            SET status = ?, updated_at = ?, paid_at = ?
-            WHERE payment_id = ?
+            SET status = ?, updated_at = ?
+            WHERE payment_id = ? AND status = ?
             "#,
             status,
             now,
-            paid_at,
+            payment_id,
+            from
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Every payment still waiting for its invoice to be paid, oldest expiry first
+    pub async fn get_pending_payments(&self) -> Result<Vec<GamePayment>, Error> {
+        let payments = sqlx::query_as!(
+            GamePayment,
+            r#"
+            SELECT id, user_id, payment_id, invoice, amount_sats, status as "status: GamePaymentStatus", created_at, updated_at, paid_at, competition_id, credits, expires_at
+            FROM game_payments
+            WHERE status = 'pending'
+            ORDER BY expires_at ASC
+            "#
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(payments)
+    }
+
+    // Mark a pending payment as paid, add its entry fee to the competition pot and
+    // credit the games it bought. A payment landing after its competition closed is
+    // credited to the player's balance instead, as the games could no longer be played.
+    // Returns None if the payment was already settled, so it is only counted once
+    pub async fn mark_payment_paid(&self, payment_id: &str) -> Result<Option<GamePayment>, Error> {
+        let now = OffsetDateTime::now_utc();
+        let now_str = format_time(now)?;
+
+        let mut tx = self.db.begin().await?;
+
+        let Some(payment) = sqlx::query!(
+            r#"
+            SELECT p.user_id, p.amount_sats, p.competition_id, c.status as "competition_status?", c.end_time as "end_time?"
+            FROM game_payments p
+            LEFT JOIN competitions c ON c.id = p.competition_id
+            WHERE p.payment_id = ?
+            "#,
             payment_id
         )
-        .execute(&self.db)
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let is_open = payment.competition_status.as_deref() == Some("open")
+            && payment
+                .end_time
+                .as_deref()
+                .and_then(|end_time| parse_time(end_time).ok())
+                .is_some_and(|end_time| now < end_time);
+        let to = match payment.competition_id {
+            Some(_) if !is_open => GamePaymentStatus::Credited,
+            _ => GamePaymentStatus::Paid,
+        };
+
+        let Some(from) = check_payment_transition(&mut tx, payment_id, to).await? else {
+            return Ok(None);
+        };
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE game_payments
+            SET status = ?, updated_at = ?, paid_at = ?
+            WHERE payment_id = ? AND status = ?
+            "#,
+            to,
+            now_str,
+            now_str,
+            payment_id,
+            from
+        )
+        .execute(&mut *tx)
         .await?;
 
         if result.rows_affected() == 0 {
             return Ok(None);
         }
 
+        match (payment.competition_id, to) {
+            (Some(_), GamePaymentStatus::Credited) => {
+                record_transaction(
+                    &mut tx,
+                    "late_entry_fee",
+                    payment_id,
+                    "Entry fee received after the competition closed, credited to balance",
+                    &[
+                        Posting::debit(LedgerAccount::Lightning, payment.amount_sats),
+                        Posting::credit(LedgerAccount::User(payment.user_id), payment.amount_sats),
+                    ],
+                )
+                .await?;
+            }
+            (Some(competition_id), _) => {
+                sqlx::query!(
+                    r#"
+                    UPDATE competitions
+                    SET pot_sats = pot_sats + ?, updated_at = ?
+                    WHERE id = ?
+                    "#,
+                    payment.amount_sats,
+                    now_str,
+                    competition_id
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                record_transaction(
+                    &mut tx,
+                    "entry_fee",
+                    payment_id,
+                    "Entry fee received",
+                    &[
+                        Posting::debit(LedgerAccount::Lightning, payment.amount_sats),
+                        Posting::credit(LedgerAccount::Pot(competition_id), payment.amount_sats),
+                    ],
+                )
+                .await?;
+
+                sqlx::query!(
+                    r#"
+                    INSERT INTO game_credits (user_id, competition_id, delta, reason, payment_id, created_at)
+                    SELECT user_id, competition_id, credits, 'purchase', payment_id, ?
+                    FROM game_payments
+                    WHERE payment_id = ?
+                    "#,
+                    now_str,
+                    payment_id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            (None, _) => {}
+        }
+
+        tx.commit().await?;
+
         self.get_payment_by_id(payment_id).await
     }
 
-    // Get pending payment for a user
+    // Get a user's pending payment for a bundle of games at its current price
     pub async fn get_pending_payment_for_user(
         &self,
         user_id: i64,
+        competition_id: i64,
+        credits: i64,
+        amount_sats: i64,
     ) -> Result<Option<GamePayment>, Error> {
         let payment = sqlx::query_as!(
             GamePayment,
             r#"
-            SELECT id, user_id, payment_id, invoice, amount_sats, status, created_at, updated_at, paid_at
+            SELECT id, user_id, payment_id, invoice, amount_sats, status as "status: GamePaymentStatus", created_at, updated_at, paid_at, competition_id, credits, expires_at
             FROM game_payments
-            WHERE user_id = ? AND status = 'pending'
+            WHERE user_id = ? AND competition_id = ? AND credits = ? AND amount_sats = ?
+                AND status = 'pending'
             ORDER BY created_at DESC
             LIMIT 1
             "#,
-            user_id
+            user_id,
+            competition_id,
+            credits,
+            amount_sats
         )
         .fetch_optional(&self.db)
         .await?;
@@ -172,182 +432,185 @@ impl PaymentStore {
         Ok(payment)
     }
 
-    // Check if a user has a valid paid payment in the last hour (to allow multiple game sessions)
-    pub async fn has_valid_payment(&self, user_id: i64) -> Result<bool, Error> {
-        // Get timestamp for one hour ago
-        let one_hour_ago = (OffsetDateTime::now_utc() - time::Duration::hours(1)).to_string();
-
+    // Games a user has left to play in a competition
+    pub async fn get_credit_balance(
+        &self,
+        user_id: i64,
+        competition_id: i64,
+    ) -> Result<i64, Error> {
         let result = sqlx::query!(
             r#"
-            SELECT COUNT(*) as count
-            FROM game_payments
-            WHERE user_id = ? AND status = 'paid' AND paid_at > ?
+            SELECT COALESCE(SUM(delta), 0) as "credits!: i64"
+            FROM game_credits
+            WHERE user_id = ? AND competition_id = ?
             "#,
             user_id,
-            one_hour_ago
+            competition_id
         )
         .fetch_one(&self.db)
         .await?;
 
-        Ok(result.count > 0)
+        Ok(result.credits)
     }
 
-    // Get the count of paid games for a specific date
-    pub async fn count_games_for_date(&self, date: &str) -> Result<i64, Error> {
-        // Create date range for the given date
-        let start_date = format!("{} 00:00:00", date);
-        let end_date = format!("{} 23:59:59", date);
-
-        let result = sqlx::query!(
+    // Unused credits in every competition that is still open
+    pub async fn get_credit_balances(&self, user_id: i64) -> Result<Vec<CreditBalance>, Error> {
+        let balances = sqlx::query_as!(
+            CreditBalance,
             r#"
-            SELECT COUNT(*) as count
-            FROM game_payments
-            WHERE status = 'paid' AND paid_at >= ? AND paid_at <= ?
+            SELECT
+                c.id as "competition_id!: i64",
+                c.name as competition_name,
+                c.kind,
+                SUM(g.delta) as "credits!: i64"
+            FROM game_credits g
+            JOIN competitions c ON g.competition_id = c.id
+            WHERE g.user_id = ? AND c.status = 'open'
+            GROUP BY c.id
+            HAVING SUM(g.delta) > 0
+            ORDER BY c.end_time ASC
             "#,
-            start_date,
-            end_date
+            user_id
         )
-        .fetch_one(&self.db)
+        .fetch_all(&self.db)
         .await?;
 
-        Ok(result.count)
-    }
-
-    // Find the top scorer for a given date
-    // TODO( @tee8z): clean up query
-    pub async fn get_top_scorer_for_date(&self, date: &str) -> Result<Option<TopScorer>, Error> {
-        // Create date range for the given date
-        let start_date = format!("{} 00:00:00", date);
-        let end_date = format!("{} 23:59:59", date);
-
-        // Use raw query to avoid macro issues
-        let query = format!(
-            "SELECT
-                s.user_id,
-                COALESCE(MAX(s.score), 0) as top_score,
-                COUNT(*) as games_played,
-                u.username
-            FROM scores s
-            JOIN users u ON s.user_id = u.id
-            WHERE s.created_at >= '{}' AND s.created_at <= '{}'
-            GROUP BY s.user_id
-            ORDER BY top_score DESC
-            LIMIT 1",
-            start_date, end_date
-        );
-
-        let row = sqlx::query(&query).fetch_optional(&self.db).await?;
-
-        match row {
-            Some(row) => {
-                let user_id: i64 = row.try_get("user_id")?;
-                let top_score: i64 = row.try_get("top_score")?;
-                let games_played: i64 = row.try_get("games_played")?;
-                let username: String = row.try_get("username")?;
-
-                Ok(Some(TopScorer {
-                    user_id,
-                    score: top_score,
-                    games_played,
-                    username,
-                }))
-            }
-            None => Ok(None),
-        }
+        Ok(balances)
     }
 
-    // Check if a user was the top scorer for a given date
-    pub async fn check_top_scorer(&self, user_id: i64, date: &str) -> Result<bool, Error> {
-        let top_scorer = self.get_top_scorer_for_date(date).await?;
-
-        match top_scorer {
-            Some(ts) => Ok(ts.user_id == user_id),
-            None => Ok(false),
-        }
-    }
-
-    // Check if a prize has already been claimed for a user and date
-    pub async fn check_prize_claimed(&self, user_id: i64, date: &str) -> Result<bool, Error> {
-        let result = sqlx::query!(
+    // Every credit bought or spent by a user
+    pub async fn get_credits_for_user(&self, user_id: i64) -> Result<Vec<GameCredit>, Error> {
+        let credits = sqlx::query_as!(
+            GameCredit,
             r#"
-            SELECT COUNT(*) as count
-            FROM prize_payouts
-            WHERE user_id = ? AND date = ?
+            SELECT id, user_id, competition_id, delta, reason, payment_id, session_id, created_at
+            FROM game_credits
+            WHERE user_id = ?
+            ORDER BY id ASC
             "#,
-            user_id,
-            date
+            user_id
         )
-        .fetch_one(&self.db)
+        .fetch_all(&self.db)
         .await?;
 
-        Ok(result.count > 0)
+        Ok(credits)
     }
 
-    // Record a daily winner
-    pub async fn record_daily_winner(
+    // Record the winner of a competition that ran on a YYYY-MM-DD date, awarding them the pot
+    // less the rake. Returns None when the competition already has a winner, which is left
+    // as it was along with the pot, or when there is no prize to award
+    pub async fn record_winner(
         &self,
-        user_id: i64,
+        competition_id: i64,
         date: &str,
+        user_id: i64,
         score: i64,
-        amount_sats: i64,
-    ) -> Result<PrizePayout, Error> {
-        let now = OffsetDateTime::now_utc().to_string();
+    ) -> Result<Option<PrizePayout>, Error> {
+        let now = format_time(OffsetDateTime::now_utc())?;
 
-        let id = sqlx::query!(
+        let mut tx = self.db.begin().await?;
+
+        // The pot as it stands now, payments may have settled since the caller read it
+        let competition = sqlx::query_as!(
+            Competition,
+            r#"
+            SELECT id, name, kind, start_time, end_time, entry_fee_sats, rake_percent,
+                ruleset_id, rules_version, seed, status, pot_sats, created_at, updated_at
+            FROM competitions
+            WHERE id = ?
+            "#,
+            competition_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| Error::NotFound(format!("Competition not found: {}", competition_id)))?;
+        let amount_sats = competition.prize_sats();
+        if amount_sats <= 0 {
+            return Ok(None);
+        }
+
+        let prize = sqlx::query_as!(
+            PrizePayout,
             r#"
             INSERT INTO prize_payouts
-            (user_id, date, score, amount_sats, status, created_at, updated_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?)
-            ON CONFLICT(user_id, date) DO NOTHING
+            (user_id, date, score, amount_sats, status, created_at, updated_at, competition_id)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(competition_id) DO NOTHING
+            RETURNING id as "id!: i64", user_id, date, score, amount_sats, payment_request,
+                payment_id, status as "status: PayoutStatus", created_at, updated_at, paid_at,
+                competition_id
             "#,
             user_id,
             date,
             score,
             amount_sats,
-            "pending",
+            PayoutStatus::Pending,
             now,
-            now
+            now,
+            competition.id
         )
-        .execute(&self.db)
-        .await?
-        .last_insert_rowid();
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(prize) = prize else {
+            return Ok(None);
+        };
 
-        Ok(PrizePayout {
-            id,
-            user_id,
-            date: date.to_string(),
-            score,
-            amount_sats,
-            payment_request: None,
-            payment_id: None,
-            status: "pending".to_string(),
-            created_at: now.clone(),
-            updated_at: now,
-            paid_at: None,
-        })
+        // Empty the pot into the winner's balance, the rake goes to the house
+        let mut postings = vec![
+            Posting::debit(LedgerAccount::Pot(competition.id), competition.pot_sats),
+            Posting::credit(LedgerAccount::User(user_id), amount_sats),
+        ];
+        let rake = competition.pot_sats - amount_sats;
+        if rake > 0 {
+            postings.push(Posting::credit(LedgerAccount::House, rake));
+        }
+
+        record_transaction(
+            &mut tx,
+            "prize",
+            &competition.id.to_string(),
+            &format!("Prize for {}", competition.name),
+            &postings,
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(prize))
     }
 
-    // Update a prize payout with an invoice
-    pub async fn update_prize_with_invoice(
+    // Mark a prize as paid out and record the sats leaving the Lightning backend,
+    // along with any routing fee paid to send them and the preimage proving the payment
+    pub async fn mark_prize_paid(
         &self,
-        user_id: i64,
-        date: &str,
-        invoice: &str,
+        id: i64,
+        payment_id: &str,
+        fee_sats: i64,
+        preimage: Option<&str>,
     ) -> Result<Option<PrizePayout>, Error> {
-        let now = OffsetDateTime::now_utc().to_string();
+        let now = format_time(OffsetDateTime::now_utc())?;
+
+        let mut tx = self.db.begin().await?;
+
+        let Some(from) = check_prize_transition(&mut tx, id, PayoutStatus::Paid).await? else {
+            return Ok(None);
+        };
 
         let result = sqlx::query!(
             r#"
             UPDATE prize_payouts
-            SET payment_request = ?, updated_at = ?
-            WHERE user_id = ? AND date = ? AND status = 'pending'
+            SET status = ?, payment_id = ?, payment_preimage = ?, updated_at = ?, paid_at = ?
+            WHERE id = ? AND status = ?
             "#,
-            invoice,
+            PayoutStatus::Paid,
+            payment_id,
+            preimage,
             now,
-            user_id,
-            date
+            now,
+            id,
+            from
         )
-        .execute(&self.db)
+        .execute(&mut *tx)
         .await?;
 
         if result.rows_affected() == 0 {
@@ -357,12 +620,61 @@ impl PaymentStore {
         let payout = sqlx::query_as!(
             PrizePayout,
             r#"
-            SELECT id, user_id, date, score, amount_sats, payment_request, payment_id, status, created_at, updated_at, paid_at
+            SELECT id, user_id, date, score, amount_sats, payment_request, payment_id, status as "status: PayoutStatus", created_at, updated_at, paid_at, competition_id
             FROM prize_payouts
-            WHERE user_id = ? AND date = ?
+            WHERE id = ?
+            "#,
+            id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        record_transaction(
+            &mut tx,
+            "payout",
+            payment_id,
+            "Prize paid out",
+            &[
+                Posting::debit(LedgerAccount::User(payout.user_id), payout.amount_sats),
+                Posting::credit(LedgerAccount::Lightning, payout.amount_sats),
+            ],
+        )
+        .await?;
+
+        if fee_sats > 0 {
+            record_transaction(
+                &mut tx,
+                "routing_fee",
+                payment_id,
+                "Routing fee for prize payout",
+                &[
+                    Posting::debit(LedgerAccount::Fees, fee_sats),
+                    Posting::credit(LedgerAccount::Lightning, fee_sats),
+                ],
+            )
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(Some(payout))
+    }
+
+    // Get the prize a user won in a competition, whatever its status
+    pub async fn get_prize_for_competition(
+        &self,
+        user_id: i64,
+        competition_id: i64,
+    ) -> Result<Option<PrizePayout>, Error> {
+        let payout = sqlx::query_as!(
+            PrizePayout,
+            r#"
+            SELECT id, user_id, date, score, amount_sats, payment_request, payment_id, status as "status: PayoutStatus", created_at, updated_at, paid_at, competition_id
+            FROM prize_payouts
+            WHERE user_id = ? AND competition_id = ?
             "#,
             user_id,
-            date
+            competition_id
         )
         .fetch_optional(&self.db)
         .await?;
@@ -370,73 +682,775 @@ impl PaymentStore {
         Ok(payout)
     }
 
-    // Update a prize payout status
-    pub async fn update_prize_status(
+    // Get the prize awarded in a competition, whoever won it
+    pub async fn get_competition_prize(
         &self,
-        id: i64,
-        status: &str,
-        payment_id: Option<&str>,
+        competition_id: i64,
     ) -> Result<Option<PrizePayout>, Error> {
-        let now = OffsetDateTime::now_utc().to_string();
-        let paid_at = if status == "paid" {
-            Some(now.clone())
-        } else {
-            None
-        };
+        let payout = sqlx::query_as!(
+            PrizePayout,
+            r#"
+            SELECT id, user_id, date, score, amount_sats, payment_request, payment_id, status as "status: PayoutStatus", created_at, updated_at, paid_at, competition_id
+            FROM prize_payouts
+            WHERE competition_id = ?
+            "#,
+            competition_id
+        )
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(payout)
+    }
+
+    // Hand a prize over to another player after the winning score was voided, moving
+    // the winnings between their balances. Returns None once the prize has been claimed
+    // or its winnings spent or withdrawn, as they can no longer be taken back here
+    pub async fn reassign_prize(
+        &self,
+        prize: &PrizePayout,
+        user_id: i64,
+        score: i64,
+    ) -> Result<Option<PrizePayout>, Error> {
+        let now = format_time(OffsetDateTime::now_utc())?;
 
+        let mut tx = self.db.begin().await?;
+
+        // The status stays as it is, both pending and failed prizes can be claimed by the new winner
         let result = sqlx::query!(
             r#"
             UPDATE prize_payouts
-            SET status = ?, payment_id = ?, updated_at = ?, paid_at = ?
-            WHERE id = ?
+            SET user_id = ?, score = ?, payment_request = NULL, payment_id = NULL, updated_at = ?
+            WHERE id = ? AND user_id = ? AND status IN ('pending', 'failed')
+            AND (SELECT balance_sats FROM user_balances WHERE user_id = prize_payouts.user_id) >= amount_sats
             "#,
-            status,
-            payment_id,
+            user_id,
+            score,
             now,
-            paid_at,
-            id
+            prize.id,
+            prize.user_id
         )
-        .execute(&self.db)
+        .execute(&mut *tx)
         .await?;
 
         if result.rows_affected() == 0 {
             return Ok(None);
         }
 
+        // Voids only ever take scores away, so a prize never moves between the same two players twice
+        record_transaction(
+            &mut tx,
+            "prize_reassigned",
+            &format!("{}:{}:{}", prize.id, prize.user_id, user_id),
+            &format!("Prize {} reassigned after a voided score", prize.id),
+            &[
+                Posting::debit(LedgerAccount::User(prize.user_id), prize.amount_sats),
+                Posting::credit(LedgerAccount::User(user_id), prize.amount_sats),
+            ],
+        )
+        .await?;
+        credit_spent_prizes(&mut tx, prize.user_id).await?;
+
         let payout = sqlx::query_as!(
             PrizePayout,
             r#"
-            SELECT id, user_id, date, score, amount_sats, payment_request, payment_id, status, created_at, updated_at, paid_at
+            SELECT id, user_id, date, score, amount_sats, payment_request, payment_id, status as "status: PayoutStatus", created_at, updated_at, paid_at, competition_id
             FROM prize_payouts
             WHERE id = ?
             "#,
-            id
+            prize.id
         )
-        .fetch_optional(&self.db)
+        .fetch_one(&mut *tx)
         .await?;
 
-        Ok(payout)
+        tx.commit().await?;
+
+        Ok(Some(payout))
     }
 
-    // Get pending prize for a user
-    pub async fn get_pending_prize_for_user(
+    // Get every entry fee payment a user has made
+    pub async fn get_payments_for_user(&self, user_id: i64) -> Result<Vec<GamePayment>, Error> {
+        let payments = sqlx::query_as!(
+            GamePayment,
+            r#"
+            SELECT id, user_id, payment_id, invoice, amount_sats, status as "status: GamePaymentStatus", created_at, updated_at, paid_at, competition_id, credits, expires_at
+            FROM game_payments
+            WHERE user_id = ?
+            ORDER BY created_at ASC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(payments)
+    }
+
+    // Entry fee payments of every player, newest first, optionally only those in one status
+    pub async fn get_payments(
         &self,
-        user_id: i64,
-        date: &str,
-    ) -> Result<Option<PrizePayout>, Error> {
-        let payout = sqlx::query_as!(
+        status: Option<GamePaymentStatus>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<GamePayment>, Error> {
+        let payments = sqlx::query_as!(
+            GamePayment,
+            r#"
+            SELECT id, user_id, payment_id, invoice, amount_sats, status as "status: GamePaymentStatus", created_at, updated_at, paid_at, competition_id, credits, expires_at
+            FROM game_payments
+            WHERE ? IS NULL OR status = ?
+            ORDER BY id DESC
+            LIMIT ? OFFSET ?
+            "#,
+            status,
+            status,
+            limit,
+            offset
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(payments)
+    }
+
+    // Get every prize a user has won, paid or not
+    pub async fn get_prizes_for_user(&self, user_id: i64) -> Result<Vec<PrizePayout>, Error> {
+        let payouts = sqlx::query_as!(
             PrizePayout,
             r#"
-            SELECT id, user_id, date, score, amount_sats, payment_request, payment_id, status, created_at, updated_at, paid_at
+            SELECT id, user_id, date, score, amount_sats, payment_request, payment_id, status as "status: PayoutStatus", created_at, updated_at, paid_at, competition_id
             FROM prize_payouts
-            WHERE user_id = ? AND date = ? AND status = 'pending'
+            WHERE user_id = ?
+            ORDER BY date ASC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(payouts)
+    }
+
+    // Sats a player has won and not yet withdrawn, read from their ledger account.
+    // Prizes claimed to an invoice are held back until their payout settles
+    pub async fn get_user_balance(&self, user_id: i64) -> Result<i64, Error> {
+        let result = sqlx::query!(
+            r#"
+            SELECT COALESCE((SELECT balance_sats FROM user_balances WHERE user_id = ?), 0) as "balance!: i64"
+            "#,
+            user_id
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(result.balance)
+    }
+
+    // Pay for a bundle of games out of a player's balance. The payment is settled
+    // straight away, so the pot and credits are topped up like a paid invoice.
+    // Returns None if the balance does not cover the price
+    pub async fn buy_credits_with_balance(
+        &self,
+        user_id: i64,
+        competition_id: i64,
+        amount_sats: i64,
+        credits: i64,
+    ) -> Result<Option<GamePayment>, Error> {
+        let now = format_time(OffsetDateTime::now_utc())?;
+        let payment_id = format!("balance_{}", Uuid::now_v7());
+
+        let mut tx = self.db.begin().await?;
+
+        // The balance check and the insert run as one statement so concurrent
+        // purchases cannot spend the same sats twice
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO game_payments
+            (user_id, payment_id, invoice, amount_sats, status, created_at, updated_at, paid_at, competition_id, credits)
+            SELECT ?, ?, '', ?, 'paid', ?, ?, ?, ?, ?
+            WHERE (SELECT balance_sats FROM user_balances WHERE user_id = ?) >= ?
+            "#,
+            user_id,
+            payment_id,
+            amount_sats,
+            now,
+            now,
+            now,
+            competition_id,
+            credits,
+            user_id,
+            amount_sats
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE competitions
+            SET pot_sats = pot_sats + ?, updated_at = ?
+            WHERE id = ?
+            "#,
+            amount_sats,
+            now,
+            competition_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        record_transaction(
+            &mut tx,
+            "entry_fee",
+            &payment_id,
+            "Entry fee paid from balance",
+            &[
+                Posting::debit(LedgerAccount::User(user_id), amount_sats),
+                Posting::credit(LedgerAccount::Pot(competition_id), amount_sats),
+            ],
+        )
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO game_credits (user_id, competition_id, delta, reason, payment_id, created_at)
+            VALUES (?, ?, ?, 'purchase', ?, ?)
+            "#,
+            user_id,
+            competition_id,
+            credits,
+            payment_id,
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        credit_spent_prizes(&mut tx, user_id).await?;
+
+        tx.commit().await?;
+
+        self.get_payment_by_id(&payment_id).await
+    }
+
+    // Take a withdrawal and its fee out of a player's balance and queue the sats to be sent.
+    // Returns None if the balance does not cover both
+    pub async fn reserve_withdrawal(
+        &self,
+        user_id: i64,
+        amount_sats: i64,
+        fee_sats: i64,
+        destination: &str,
+        invoice: &str,
+    ) -> Result<Option<Withdrawal>, Error> {
+        let now = format_time(OffsetDateTime::now_utc())?;
+        let total_sats = amount_sats + fee_sats;
+
+        let mut tx = self.db.begin().await?;
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO withdrawals
+            (user_id, amount_sats, fee_sats, destination, invoice, status, created_at, updated_at)
+            SELECT ?, ?, ?, ?, ?, 'invoice_received', ?, ?
+            WHERE (SELECT balance_sats FROM user_balances WHERE user_id = ?) >= ?
             "#,
             user_id,
-            date
+            amount_sats,
+            fee_sats,
+            destination,
+            invoice,
+            now,
+            now,
+            user_id,
+            total_sats
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let id = result.last_insert_rowid();
+
+        let mut postings = vec![
+            Posting::debit(LedgerAccount::User(user_id), total_sats),
+            Posting::credit(LedgerAccount::Lightning, amount_sats),
+        ];
+        if fee_sats > 0 {
+            postings.push(Posting::credit(LedgerAccount::House, fee_sats));
+        }
+
+        record_transaction(
+            &mut tx,
+            "withdrawal",
+            &id.to_string(),
+            "Balance withdrawn",
+            &postings,
+        )
+        .await?;
+
+        upsert_job(&mut tx, PayoutKind::Withdrawal, id, invoice, amount_sats).await?;
+        credit_spent_prizes(&mut tx, user_id).await?;
+
+        tx.commit().await?;
+
+        self.get_withdrawal(id).await
+    }
+
+    // Mark a withdrawal as sent and record the routing fee paid to send it. Returns None
+    // if the withdrawal was not being paid
+    pub async fn complete_withdrawal(
+        &self,
+        id: i64,
+        payment_id: &str,
+        routing_fee_sats: i64,
+    ) -> Result<Option<Withdrawal>, Error> {
+        let now = format_time(OffsetDateTime::now_utc())?;
+
+        let mut tx = self.db.begin().await?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE withdrawals
+            SET status = ?, payment_id = ?, updated_at = ?, paid_at = ?
+            WHERE id = ? AND status IN ('sending', 'needs_attention')
+            "#,
+            PayoutStatus::Paid,
+            payment_id,
+            now,
+            now,
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        if routing_fee_sats > 0 {
+            record_transaction(
+                &mut tx,
+                "routing_fee",
+                &format!("withdrawal:{}", id),
+                "Routing fee for withdrawal",
+                &[
+                    Posting::debit(LedgerAccount::Fees, routing_fee_sats),
+                    Posting::credit(LedgerAccount::Lightning, routing_fee_sats),
+                ],
+            )
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        self.get_withdrawal(id).await
+    }
+
+    // Get a withdrawal by id
+    pub async fn get_withdrawal(&self, id: i64) -> Result<Option<Withdrawal>, Error> {
+        let withdrawal = sqlx::query_as!(
+            Withdrawal,
+            r#"
+            SELECT id, user_id, amount_sats, fee_sats, destination, invoice, payment_id, status as "status: PayoutStatus", created_at, updated_at, paid_at
+            FROM withdrawals
+            WHERE id = ?
+            "#,
+            id
         )
         .fetch_optional(&self.db)
         .await?;
 
-        Ok(payout)
+        Ok(withdrawal)
+    }
+
+    // Get every withdrawal a user has made, newest first
+    pub async fn get_withdrawals_for_user(&self, user_id: i64) -> Result<Vec<Withdrawal>, Error> {
+        let withdrawals = sqlx::query_as!(
+            Withdrawal,
+            r#"
+            SELECT id, user_id, amount_sats, fee_sats, destination, invoice, payment_id, status as "status: PayoutStatus", created_at, updated_at, paid_at
+            FROM withdrawals
+            WHERE user_id = ?
+            ORDER BY created_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(withdrawals)
+    }
+}
+
+// The status a payment would move from to reach a new one. None if the payment does not
+// exist or is not allowed to make the move, e.g. because it was settled in the meantime.
+// Writers update only while the payment is still in the returned status
+async fn check_payment_transition(
+    conn: &mut SqliteConnection,
+    payment_id: &str,
+    to: GamePaymentStatus,
+) -> Result<Option<GamePaymentStatus>, Error> {
+    let status = sqlx::query_scalar!(
+        r#"SELECT status as "status: GamePaymentStatus" FROM game_payments WHERE payment_id = ?"#,
+        payment_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(status.filter(|status| status.can_transition_to(to)))
+}
+
+// The status a prize would move from to reach a new one, like check_payment_transition
+pub(crate) async fn check_prize_transition(
+    conn: &mut SqliteConnection,
+    id: i64,
+    to: PayoutStatus,
+) -> Result<Option<PayoutStatus>, Error> {
+    let status = sqlx::query_scalar!(
+        r#"SELECT status as "status: PayoutStatus" FROM prize_payouts WHERE id = ?"#,
+        id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(status.filter(|status| status.can_transition_to(to)))
+}
+
+// Settle the prizes a player can no longer claim to an invoice, as their winnings have been
+// spent or withdrawn from the balance since. Runs after anything that takes from the balance
+pub(crate) async fn credit_spent_prizes(
+    conn: &mut SqliteConnection,
+    user_id: i64,
+) -> Result<(), Error> {
+    let now = format_time(OffsetDateTime::now_utc())?;
+
+    sqlx::query!(
+        r#"
+        UPDATE prize_payouts
+        SET status = ?, updated_at = ?
+        WHERE user_id = ? AND status IN ('pending', 'failed')
+        AND (SELECT balance_sats FROM user_balances WHERE user_id = prize_payouts.user_id) < amount_sats
+        "#,
+        PayoutStatus::Credited,
+        now,
+        user_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+// Give the amount and fee of a withdrawal that could not be sent back to the player
+pub(crate) async fn reverse_withdrawal(conn: &mut SqliteConnection, id: i64) -> Result<(), Error> {
+    let withdrawal = sqlx::query!(
+        r#"
+        SELECT user_id, amount_sats, fee_sats
+        FROM withdrawals
+        WHERE id = ?
+        "#,
+        id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let mut postings = vec![
+        Posting::credit(
+            LedgerAccount::User(withdrawal.user_id),
+            withdrawal.amount_sats + withdrawal.fee_sats,
+        ),
+        Posting::debit(LedgerAccount::Lightning, withdrawal.amount_sats),
+    ];
+    if withdrawal.fee_sats > 0 {
+        postings.push(Posting::debit(LedgerAccount::House, withdrawal.fee_sats));
+    }
+
+    record_transaction(
+        conn,
+        "withdrawal_reversal",
+        &id.to_string(),
+        "Failed withdrawal returned to balance",
+        &postings,
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        domain::testing::{create_test_user, pay_entry, test_competition_store, test_db},
+        LedgerStore, PayoutStore,
+    };
+    use time::Duration;
+
+    #[test]
+    fn settled_payments_do_not_change() {
+        for next in [
+            GamePaymentStatus::Pending,
+            GamePaymentStatus::Paid,
+            GamePaymentStatus::Expired,
+            GamePaymentStatus::Failed,
+            GamePaymentStatus::Credited,
+        ] {
+            assert!(!GamePaymentStatus::Paid.can_transition_to(next));
+            assert!(!GamePaymentStatus::Failed.can_transition_to(next));
+            assert!(!GamePaymentStatus::Credited.can_transition_to(next));
+        }
+        assert!(GamePaymentStatus::Expired.can_transition_to(GamePaymentStatus::Paid));
+        assert!(!GamePaymentStatus::Expired.can_transition_to(GamePaymentStatus::Pending));
+    }
+
+    #[test]
+    fn prizes_are_only_paid_after_sending() {
+        assert!(PayoutStatus::Sending.can_transition_to(PayoutStatus::Paid));
+        assert!(PayoutStatus::NeedsAttention.can_transition_to(PayoutStatus::Paid));
+        assert!(!PayoutStatus::Pending.can_transition_to(PayoutStatus::Paid));
+        assert!(!PayoutStatus::InvoiceReceived.can_transition_to(PayoutStatus::Paid));
+        assert!(!PayoutStatus::Paid.can_transition_to(PayoutStatus::InvoiceReceived));
+        assert!(PayoutStatus::Failed.is_claimable());
+        assert!(!PayoutStatus::NeedsAttention.is_claimable());
+    }
+
+    #[tokio::test]
+    async fn test_late_payments_go_to_the_balance() {
+        let db = test_db().await;
+        let payments = PaymentStore::new(db.clone());
+        let competitions = test_competition_store(&db);
+        let competition = competitions.current_daily().await.unwrap();
+        let user = create_test_user(&db).await;
+        pay_entry(&db, user.id, competition.id, 500, 1).await;
+
+        // An invoice that expired unpaid and is only settled after the competition closed
+        payments
+            .create_game_payment(
+                user.id,
+                competition.id,
+                "late_payment",
+                "lnbc1test",
+                500,
+                1,
+                OffsetDateTime::now_utc() + Duration::hours(1),
+            )
+            .await
+            .unwrap();
+        assert!(payments.expire_payment("late_payment").await.unwrap());
+        competitions
+            .update_status(competition.id, "settled")
+            .await
+            .unwrap();
+
+        let payment = payments
+            .mark_payment_paid("late_payment")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(payment.status, GamePaymentStatus::Credited);
+
+        let competition = competitions
+            .find_by_id(competition.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(competition.pot_sats, 500);
+        assert_eq!(
+            payments
+                .get_credit_balance(user.id, competition.id)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(payments.get_user_balance(user.id).await.unwrap(), 500);
+
+        // Paying it again does not credit it twice
+        assert!(payments
+            .mark_payment_paid("late_payment")
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(payments.get_user_balance(user.id).await.unwrap(), 500);
+    }
+
+    #[tokio::test]
+    async fn test_withdrawals_are_sent_by_the_payout_queue() {
+        let db = test_db().await;
+        let payments = PaymentStore::new(db.clone());
+        let payouts = PayoutStore::new(db.clone());
+        let user = create_test_user(&db).await;
+        LedgerStore::new(db.clone())
+            .record(
+                "prize",
+                "test",
+                "Test winnings",
+                &[
+                    Posting::debit(LedgerAccount::Lightning, 1010),
+                    Posting::credit(LedgerAccount::User(user.id), 1010),
+                ],
+            )
+            .await
+            .unwrap();
+
+        let withdrawal = payments
+            .reserve_withdrawal(user.id, 1000, 10, "bolt11", "lnbc10u1test")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(withdrawal.status, PayoutStatus::InvoiceReceived);
+        assert_eq!(payments.get_user_balance(user.id).await.unwrap(), 0);
+        assert!(payments
+            .reserve_withdrawal(user.id, 1000, 10, "bolt11", "lnbc10u1test")
+            .await
+            .unwrap()
+            .is_none());
+
+        // A payment that fails for good hands the sats back
+        let job = payouts
+            .get_job(PayoutKind::Withdrawal, withdrawal.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(job.status, PayoutStatus::InvoiceReceived);
+        let now = OffsetDateTime::now_utc();
+        assert!(payouts.start_attempt(&job, "payment_1", now).await.unwrap());
+        let job = payouts
+            .get_job(PayoutKind::Withdrawal, withdrawal.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(job.status, PayoutStatus::Sending);
+        payouts
+            .stop(&job, PayoutStatus::Failed, "No route")
+            .await
+            .unwrap();
+        let failed = payments
+            .get_withdrawal(withdrawal.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(failed.status, PayoutStatus::Failed);
+        assert_eq!(payments.get_user_balance(user.id).await.unwrap(), 1010);
+        assert!(payments
+            .complete_withdrawal(withdrawal.id, "payment_1", 0)
+            .await
+            .unwrap()
+            .is_none());
+
+        // Only a payment in flight can complete
+        let withdrawal = payments
+            .reserve_withdrawal(user.id, 1000, 10, "bolt11", "lnbc10u1test")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(payments
+            .complete_withdrawal(withdrawal.id, "payment_2", 0)
+            .await
+            .unwrap()
+            .is_none());
+        let job = payouts
+            .get_job(PayoutKind::Withdrawal, withdrawal.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(payouts.start_attempt(&job, "payment_2", now).await.unwrap());
+        let paid = payments
+            .complete_withdrawal(withdrawal.id, "payment_2", 2)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(paid.status, PayoutStatus::Paid);
+        assert_eq!(paid.payment_id.as_deref(), Some("payment_2"));
+        assert_eq!(payments.get_user_balance(user.id).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_winners_are_recorded_once() {
+        let db = test_db().await;
+        let payments = PaymentStore::new(db.clone());
+        let competitions = test_competition_store(&db);
+        let competition = competitions.current_daily().await.unwrap();
+        let winner = create_test_user(&db).await;
+        let runner_up = create_test_user(&db).await;
+        pay_entry(&db, winner.id, competition.id, 500, 1).await;
+        pay_entry(&db, runner_up.id, competition.id, 500, 1).await;
+        let competition = competitions
+            .find_by_id(competition.id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let prize = payments
+            .record_winner(competition.id, "2025-01-01", winner.id, 1000)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(prize.user_id, winner.id);
+        assert_eq!(prize.amount_sats, competition.prize_sats());
+        assert_eq!(
+            payments.get_user_balance(winner.id).await.unwrap(),
+            competition.prize_sats()
+        );
+
+        // Recording it again changes nothing and says so
+        assert!(payments
+            .record_winner(competition.id, "2025-01-01", runner_up.id, 900)
+            .await
+            .unwrap()
+            .is_none());
+        let kept = payments
+            .get_competition_prize(competition.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(kept.id, prize.id);
+        assert_eq!(kept.user_id, winner.id);
+        assert_eq!(payments.get_user_balance(runner_up.id).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_prizes_spent_from_the_balance_are_credited() {
+        let db = test_db().await;
+        let payments = PaymentStore::new(db.clone());
+        let payouts = PayoutStore::new(db.clone());
+        let competitions = test_competition_store(&db);
+        let competition = competitions.current_daily().await.unwrap();
+        let user = create_test_user(&db).await;
+        pay_entry(&db, user.id, competition.id, 500, 1).await;
+        let competition = competitions
+            .find_by_id(competition.id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let prize = payments
+            .record_winner(competition.id, "2025-01-01", user.id, 1000)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(prize.status, PayoutStatus::Pending);
+
+        // Spending part of the winnings leaves too little to claim the prize to an invoice
+        payments
+            .buy_credits_with_balance(user.id, competition.id, 100, 1)
+            .await
+            .unwrap()
+            .unwrap();
+        let prize = payments
+            .get_competition_prize(competition.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(prize.status, PayoutStatus::Credited);
+        assert!(prize.status.is_settled());
+        assert!(payouts
+            .enqueue_prize(prize.id, "lnbc4500n1test")
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(payments.get_user_balance(user.id).await.unwrap(), 350);
     }
 }
@@ -0,0 +1,5 @@
+mod routes;
+mod store;
+
+pub use routes::*;
+pub use store::*;
@@ -0,0 +1,178 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use log::{error, info};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+
+use crate::{
+    invoice_amount_msats, map_error, nostr_extractor::NostrAuth, startup::AppState, PayoutKind,
+};
+
+// Structure for claiming a refund, to an invoice, a Lightning Address or the user's balance
+#[derive(Debug, Deserialize)]
+pub struct ClaimRefundRequest {
+    pub invoice: Option<String>,
+    pub lightning_address: Option<String>,
+    pub to_balance: Option<bool>,
+}
+
+// List the refunds owed or paid to the user
+pub async fn get_refunds(
+    auth: NostrAuth,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    let pubkey = auth.pubkey.to_string();
+    info!("Refunds request from pubkey: {}", pubkey);
+
+    // Find user
+    let user = match state.user_store.find_by_pubkey(pubkey).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "User not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    let refunds = match state.payout_store.get_refunds_for_user(user.id).await {
+        Ok(refunds) => refunds,
+        Err(e) => return Err(map_error(e)),
+    };
+
+    // Show how far along the payment of each refund claimed to an invoice is
+    let mut results = vec![];
+    for refund in refunds {
+        let job = match state
+            .payout_store
+            .get_job(PayoutKind::Refund, refund.id)
+            .await
+        {
+            Ok(job) => job,
+            Err(e) => return Err(map_error(e)),
+        };
+        results.push(json!({
+            "refund": refund,
+            "attempts": job.as_ref().map(|job| job.attempts),
+            "last_error": job.and_then(|job| job.last_error)
+        }));
+    }
+
+    Ok((StatusCode::OK, Json(results)))
+}
+
+// Claim a refund
+pub async fn claim_refund(
+    auth: NostrAuth,
+    Path(refund_id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ClaimRefundRequest>,
+) -> Result<impl IntoResponse, Response> {
+    let pubkey = auth.pubkey.to_string();
+    info!(
+        "Refund claim request from pubkey: {}, refund: {}",
+        pubkey, refund_id
+    );
+
+    // Find user
+    let user = match state.user_store.find_by_pubkey(pubkey).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "User not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    let refund = match state.payout_store.get_refund(refund_id).await {
+        Ok(Some(refund)) if refund.user_id == user.id => refund,
+        Ok(_) => return Err((StatusCode::NOT_FOUND, "Refund not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    if !refund.status.is_claimable() {
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!("Refund has already been claimed, status: {}", refund.status),
+        )
+            .into_response());
+    }
+
+    // Crediting the balance needs no Lightning payment, so it is done straight away
+    if request.to_balance.unwrap_or(false) {
+        return match state.payout_store.credit_refund(refund.id).await {
+            Ok(Some(credited)) => Ok((
+                StatusCode::OK,
+                Json(json!({
+                    "success": true,
+                    "message": "Refund added to your balance",
+                    "refund": credited
+                })),
+            )),
+            Ok(None) => {
+                Err((StatusCode::CONFLICT, "Refund has already been claimed").into_response())
+            }
+            Err(e) => {
+                error!("Failed to credit refund {}: {}", refund.id, e);
+                Err(map_error(e))
+            }
+        };
+    }
+
+    let (destination, invoice) = match (request.invoice, request.lightning_address) {
+        (Some(invoice), None) => {
+            if !invoice.starts_with("lnbc") {
+                return Err((StatusCode::BAD_REQUEST, "Invalid Lightning invoice").into_response());
+            }
+            if invoice_amount_msats(&invoice) != Some(refund.amount_sats * 1000) {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    "Invoice amount must match the refund amount",
+                )
+                    .into_response());
+            }
+            (String::from("bolt11"), invoice)
+        }
+        (None, Some(address)) => match state
+            .lightning_service
+            .resolve_lightning_address(&address, refund.amount_sats)
+            .await
+        {
+            Ok(invoice) => (address, invoice),
+            Err(e) => {
+                error!("Failed to resolve Lightning Address {}: {}", address, e);
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("Failed to get an invoice from {}: {}", address, e),
+                )
+                    .into_response());
+            }
+        },
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "One of invoice, lightning_address or to_balance is required",
+            )
+                .into_response());
+        }
+    };
+
+    // Paid in the background by the payout queue, like prizes
+    match state
+        .payout_store
+        .enqueue_refund(refund.id, &destination, &invoice)
+        .await
+    {
+        Ok(Some(queued)) => Ok((
+            StatusCode::ACCEPTED,
+            Json(json!({
+                "success": true,
+                "message": "Refund payment queued",
+                "refund": queued
+            })),
+        )),
+        Ok(None) => Err((StatusCode::CONFLICT, "Refund has already been claimed").into_response()),
+        Err(e) => {
+            error!("Failed to queue refund {}: {}", refund.id, e);
+            Err(map_error(e))
+        }
+    }
+}
@@ -0,0 +1,1159 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use std::fmt;
+use time::OffsetDateTime;
+
+use crate::{
+    check_prize_transition, credit_spent_prizes, domain::Error, format_time, format_time_secs,
+    record_transaction, reverse_withdrawal, LedgerAccount, Posting, PrizePayout,
+};
+
+// Where a prize, refund or withdrawal is on its way to the player. Prizes and refunds wait in
+// 'pending' until claimed to an invoice, then the payout queue moves them through 'sending' to
+// 'paid', back to 'invoice_received' for a retry, or stops at 'failed' or 'needs_attention'.
+// Prizes and refunds that end up in the player's balance rather than an invoice are 'credited'
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum PayoutStatus {
+    Pending,
+    InvoiceReceived,
+    Sending,
+    Paid,
+    Failed,
+    NeedsAttention,
+    Credited,
+}
+
+impl PayoutStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::InvoiceReceived => "invoice_received",
+            Self::Sending => "sending",
+            Self::Paid => "paid",
+            Self::Failed => "failed",
+            Self::NeedsAttention => "needs_attention",
+            Self::Credited => "credited",
+        }
+    }
+
+    pub fn can_transition_to(self, next: Self) -> bool {
+        match self {
+            Self::Pending | Self::Failed => {
+                matches!(next, Self::InvoiceReceived | Self::Credited)
+            }
+            Self::InvoiceReceived => {
+                matches!(next, Self::Sending | Self::Failed | Self::NeedsAttention)
+            }
+            Self::Sending => matches!(
+                next,
+                Self::Paid | Self::InvoiceReceived | Self::Failed | Self::NeedsAttention
+            ),
+            Self::NeedsAttention => {
+                matches!(next, Self::Paid | Self::InvoiceReceived | Self::Failed)
+            }
+            Self::Paid | Self::Credited => false,
+        }
+    }
+
+    // Whether the player can still claim it to an invoice
+    pub fn is_claimable(self) -> bool {
+        matches!(self, Self::Pending | Self::Failed)
+    }
+
+    // Whether the sats have reached the player, one way or the other
+    pub fn is_settled(self) -> bool {
+        matches!(self, Self::Paid | Self::Credited)
+    }
+}
+
+impl fmt::Display for PayoutStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// What a payout job is paying out, names the table its target_id points into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum PayoutKind {
+    Prize,
+    Refund,
+    Withdrawal,
+}
+
+impl PayoutKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Prize => "prize",
+            Self::Refund => "refund",
+            Self::Withdrawal => "withdrawal",
+        }
+    }
+}
+
+impl fmt::Display for PayoutKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Sats sent out of the game wallet by the payout queue. Prizes, refunds and withdrawals all
+// move through 'invoice_received' -> 'sending' -> 'paid' | 'failed' | 'needs_attention'
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PayoutJob {
+    pub id: i64,
+    pub kind: PayoutKind,
+    /// Id of the prize payout, refund or withdrawal being paid
+    pub target_id: i64,
+    pub invoice: String,
+    pub amount_sats: i64,
+    pub payment_id: Option<String>,
+    pub attempts: i64,
+    pub next_attempt_at: String,
+    pub last_error: Option<String>,
+    pub sent_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Status of the prize, refund or withdrawal being paid
+    pub status: PayoutStatus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Refund {
+    pub id: i64,
+    pub user_id: i64,
+    pub competition_id: i64,
+    pub amount_sats: i64,
+    pub reason: String,
+    pub destination: Option<String>,
+    pub payment_request: Option<String>,
+    pub payment_id: Option<String>,
+    pub status: PayoutStatus,
+    pub created_at: String,
+    pub updated_at: String,
+    pub paid_at: Option<String>,
+}
+
+// Move the prize, refund or withdrawal behind a job from one of the given statuses to another,
+// returns false if it was in none of them or the move is not allowed
+async fn transition(
+    conn: &mut SqliteConnection,
+    job: &PayoutJob,
+    from: &[PayoutStatus],
+    to: PayoutStatus,
+    payment_id: Option<&str>,
+) -> Result<bool, Error> {
+    let now = format_time(OffsetDateTime::now_utc())?;
+
+    let result = match job.kind {
+        PayoutKind::Prize => {
+            let Some(current) = check_prize_transition(conn, job.target_id, to)
+                .await?
+                .filter(|current| from.contains(current))
+            else {
+                return Ok(false);
+            };
+
+            sqlx::query!(
+                r#"
+                UPDATE prize_payouts
+                SET status = ?, payment_id = COALESCE(?, payment_id), updated_at = ?
+                WHERE id = ? AND status = ?
+                "#,
+                to,
+                payment_id,
+                now,
+                job.target_id,
+                current
+            )
+            .execute(&mut *conn)
+            .await?
+        }
+        PayoutKind::Refund => {
+            let Some(current) = check_refund_transition(conn, job.target_id, to)
+                .await?
+                .filter(|current| from.contains(current))
+            else {
+                return Ok(false);
+            };
+
+            sqlx::query!(
+                r#"
+                UPDATE refunds
+                SET status = ?, payment_id = COALESCE(?, payment_id), updated_at = ?
+                WHERE id = ? AND status = ?
+                "#,
+                to,
+                payment_id,
+                now,
+                job.target_id,
+                current
+            )
+            .execute(&mut *conn)
+            .await?
+        }
+        PayoutKind::Withdrawal => {
+            let Some(current) = check_withdrawal_transition(conn, job.target_id, to)
+                .await?
+                .filter(|current| from.contains(current))
+            else {
+                return Ok(false);
+            };
+
+            sqlx::query!(
+                r#"
+                UPDATE withdrawals
+                SET status = ?, payment_id = COALESCE(?, payment_id), updated_at = ?
+                WHERE id = ? AND status = ?
+                "#,
+                to,
+                payment_id,
+                now,
+                job.target_id,
+                current
+            )
+            .execute(&mut *conn)
+            .await?
+        }
+    };
+
+    Ok(result.rows_affected() > 0)
+}
+
+// The status a refund would move from to reach a new one. None if the refund does not
+// exist or is not allowed to make the move
+async fn check_refund_transition(
+    conn: &mut SqliteConnection,
+    id: i64,
+    to: PayoutStatus,
+) -> Result<Option<PayoutStatus>, Error> {
+    let status = sqlx::query_scalar!(
+        r#"SELECT status as "status: PayoutStatus" FROM refunds WHERE id = ?"#,
+        id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(status.filter(|status| status.can_transition_to(to)))
+}
+
+// The status a withdrawal would move from to reach a new one, like check_refund_transition
+async fn check_withdrawal_transition(
+    conn: &mut SqliteConnection,
+    id: i64,
+    to: PayoutStatus,
+) -> Result<Option<PayoutStatus>, Error> {
+    let status = sqlx::query_scalar!(
+        r#"SELECT status as "status: PayoutStatus" FROM withdrawals WHERE id = ?"#,
+        id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(status.filter(|status| status.can_transition_to(to)))
+}
+
+// Create or restart the job paying a prize, refund or withdrawal. Claiming again after a
+// failure points the job at the new invoice and starts its attempts over
+pub(crate) async fn upsert_job(
+    conn: &mut SqliteConnection,
+    kind: PayoutKind,
+    target_id: i64,
+    invoice: &str,
+    amount_sats: i64,
+) -> Result<(), Error> {
+    let now = OffsetDateTime::now_utc();
+    let updated_at = format_time(now)?;
+    let next_attempt_at = format_time_secs(now)?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO payout_jobs
+        (kind, target_id, invoice, amount_sats, attempts, next_attempt_at, created_at, updated_at)
+        VALUES (?, ?, ?, ?, 0, ?, ?, ?)
+        ON CONFLICT (kind, target_id) DO UPDATE
+        SET invoice = excluded.invoice, amount_sats = excluded.amount_sats, payment_id = NULL,
+            attempts = 0, next_attempt_at = excluded.next_attempt_at, last_error = NULL,
+            sent_at = NULL, updated_at = excluded.updated_at
+        "#,
+        kind,
+        target_id,
+        invoice,
+        amount_sats,
+        next_attempt_at,
+        updated_at,
+        updated_at
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct PayoutStore {
+    db: Pool<Sqlite>,
+}
+
+impl PayoutStore {
+    pub fn new(db: Pool<Sqlite>) -> Self {
+        Self { db }
+    }
+
+    // Queue a claimed prize for payment to the given invoice. Returns None if the prize
+    // cannot be claimed or its winnings were already spent or withdrawn from the balance
+    pub async fn enqueue_prize(
+        &self,
+        prize_id: i64,
+        invoice: &str,
+    ) -> Result<Option<PrizePayout>, Error> {
+        let now = format_time(OffsetDateTime::now_utc())?;
+
+        let mut tx = self.db.begin().await?;
+
+        let Some(from) = check_prize_transition(&mut tx, prize_id, PayoutStatus::InvoiceReceived)
+            .await?
+            .filter(|from| from.is_claimable())
+        else {
+            return Ok(None);
+        };
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE prize_payouts
+            SET status = ?, payment_request = ?, payment_id = NULL, updated_at = ?
+            WHERE id = ? AND status = ?
+            AND (SELECT balance_sats FROM user_balances WHERE user_id = prize_payouts.user_id) >= amount_sats
+            "#,
+            PayoutStatus::InvoiceReceived,
+            invoice,
+            now,
+            prize_id,
+            from
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let payout = sqlx::query_as!(
+            PrizePayout,
+            r#"
+            SELECT id, user_id, date, score, amount_sats, payment_request, payment_id, status as "status: PayoutStatus", created_at, updated_at, paid_at, competition_id
+            FROM prize_payouts
+            WHERE id = ?
+            "#,
+            prize_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        upsert_job(
+            &mut tx,
+            PayoutKind::Prize,
+            payout.id,
+            invoice,
+            payout.amount_sats,
+        )
+        .await?;
+        credit_spent_prizes(&mut tx, payout.user_id).await?;
+
+        tx.commit().await?;
+
+        Ok(Some(payout))
+    }
+
+    // Give every player who paid into a voided competition a refund of what they paid.
+    // Running it again picks up entry fees paid since, for refunds not claimed yet
+    pub async fn create_refunds(&self, competition_id: i64, reason: &str) -> Result<u64, Error> {
+        let now = format_time(OffsetDateTime::now_utc())?;
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO refunds (user_id, competition_id, amount_sats, reason, status, created_at, updated_at)
+            SELECT user_id, competition_id, SUM(amount_sats), ?, 'pending', ?, ?
+            FROM game_payments
+            WHERE competition_id = ? AND status = 'paid'
+            GROUP BY user_id
+            ON CONFLICT (user_id, competition_id) DO UPDATE
+            SET amount_sats = excluded.amount_sats, updated_at = excluded.updated_at
+            WHERE refunds.status = 'pending' AND refunds.amount_sats != excluded.amount_sats
+            "#,
+            reason,
+            now,
+            now,
+            competition_id
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // Get a refund by id
+    pub async fn get_refund(&self, id: i64) -> Result<Option<Refund>, Error> {
+        let refund = sqlx::query_as!(
+            Refund,
+            r#"
+            SELECT id, user_id, competition_id, amount_sats, reason, destination, payment_request,
+                payment_id, status as "status: PayoutStatus", created_at, updated_at, paid_at
+            FROM refunds
+            WHERE id = ?
+            "#,
+            id
+        )
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(refund)
+    }
+
+    // Get every refund owed or paid to a user, newest first
+    pub async fn get_refunds_for_user(&self, user_id: i64) -> Result<Vec<Refund>, Error> {
+        let refunds = sqlx::query_as!(
+            Refund,
+            r#"
+            SELECT id, user_id, competition_id, amount_sats, reason, destination, payment_request,
+                payment_id, status as "status: PayoutStatus", created_at, updated_at, paid_at
+            FROM refunds
+            WHERE user_id = ?
+            ORDER BY created_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(refunds)
+    }
+
+    // Pay a refund into the player's balance, taking it out of the voided pot.
+    // Returns None if the refund was already claimed
+    pub async fn credit_refund(&self, id: i64) -> Result<Option<Refund>, Error> {
+        let now = format_time(OffsetDateTime::now_utc())?;
+
+        let mut tx = self.db.begin().await?;
+
+        let Some(from) = check_refund_transition(&mut tx, id, PayoutStatus::Credited).await? else {
+            return Ok(None);
+        };
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE refunds
+            SET status = ?, destination = 'balance', updated_at = ?, paid_at = ?
+            WHERE id = ? AND status = ?
+            "#,
+            PayoutStatus::Credited,
+            now,
+            now,
+            id,
+            from
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let refund = release_refund(&mut tx, id, &now).await?;
+
+        record_transaction(
+            &mut tx,
+            "refund",
+            &id.to_string(),
+            "Refund credited to balance",
+            &[
+                Posting::debit(
+                    LedgerAccount::Pot(refund.competition_id),
+                    refund.amount_sats,
+                ),
+                Posting::credit(LedgerAccount::User(refund.user_id), refund.amount_sats),
+            ],
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        self.get_refund(id).await
+    }
+
+    // Queue a refund for payment to an invoice. Returns None if it was already claimed
+    pub async fn enqueue_refund(
+        &self,
+        id: i64,
+        destination: &str,
+        invoice: &str,
+    ) -> Result<Option<Refund>, Error> {
+        let now = format_time(OffsetDateTime::now_utc())?;
+
+        let mut tx = self.db.begin().await?;
+
+        let Some(from) = check_refund_transition(&mut tx, id, PayoutStatus::InvoiceReceived)
+            .await?
+            .filter(|from| from.is_claimable())
+        else {
+            return Ok(None);
+        };
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE refunds
+            SET status = ?, destination = ?, payment_request = ?, payment_id = NULL, updated_at = ?
+            WHERE id = ? AND status = ?
+            "#,
+            PayoutStatus::InvoiceReceived,
+            destination,
+            invoice,
+            now,
+            id,
+            from
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let amount = sqlx::query!("SELECT amount_sats FROM refunds WHERE id = ?", id)
+            .fetch_one(&mut *tx)
+            .await?
+            .amount_sats;
+
+        upsert_job(&mut tx, PayoutKind::Refund, id, invoice, amount).await?;
+
+        tx.commit().await?;
+
+        self.get_refund(id).await
+    }
+
+    // Mark a refund as paid out and record the sats leaving the Lightning backend
+    pub async fn mark_refund_paid(
+        &self,
+        id: i64,
+        payment_id: &str,
+        fee_sats: i64,
+    ) -> Result<Option<Refund>, Error> {
+        let now = format_time(OffsetDateTime::now_utc())?;
+
+        let mut tx = self.db.begin().await?;
+
+        let Some(from) = check_refund_transition(&mut tx, id, PayoutStatus::Paid).await? else {
+            return Ok(None);
+        };
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE refunds
+            SET status = ?, payment_id = ?, updated_at = ?, paid_at = ?
+            WHERE id = ? AND status = ?
+            "#,
+            PayoutStatus::Paid,
+            payment_id,
+            now,
+            now,
+            id,
+            from
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let refund = release_refund(&mut tx, id, &now).await?;
+
+        record_transaction(
+            &mut tx,
+            "refund",
+            &id.to_string(),
+            "Refund paid out",
+            &[
+                Posting::debit(
+                    LedgerAccount::Pot(refund.competition_id),
+                    refund.amount_sats,
+                ),
+                Posting::credit(LedgerAccount::Lightning, refund.amount_sats),
+            ],
+        )
+        .await?;
+
+        if fee_sats > 0 {
+            record_transaction(
+                &mut tx,
+                "routing_fee",
+                payment_id,
+                "Routing fee for refund",
+                &[
+                    Posting::debit(LedgerAccount::Fees, fee_sats),
+                    Posting::credit(LedgerAccount::Lightning, fee_sats),
+                ],
+            )
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        self.get_refund(id).await
+    }
+
+    // Jobs waiting to be sent or with a payment in flight that is due a check
+    pub async fn get_due_jobs(&self, now: OffsetDateTime) -> Result<Vec<PayoutJob>, Error> {
+        let now = format_time_secs(now)?;
+
+        let jobs = sqlx::query_as!(
+            PayoutJob,
+            r#"
+            SELECT j.id, j.kind as "kind: PayoutKind", j.target_id, j.invoice, j.amount_sats,
+                j.payment_id, j.attempts, j.next_attempt_at, j.last_error, j.sent_at, j.created_at, j.updated_at,
+                CASE WHEN r.status = 'credited' THEN 'paid' ELSE COALESCE(p.status, r.status, w.status) END
+                    as "status!: PayoutStatus"
+            FROM payout_jobs j
+            LEFT JOIN prize_payouts p ON j.kind = 'prize' AND p.id = j.target_id
+            LEFT JOIN refunds r ON j.kind = 'refund' AND r.id = j.target_id
+            LEFT JOIN withdrawals w ON j.kind = 'withdrawal' AND w.id = j.target_id
+            WHERE COALESCE(p.status, r.status, w.status) IN ('invoice_received', 'sending')
+                AND j.next_attempt_at <= ?
+            ORDER BY j.next_attempt_at ASC
+            "#,
+            now
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(jobs)
+    }
+
+    // Get the job paying a prize, refund or withdrawal, if it was ever sent to an invoice
+    pub async fn get_job(
+        &self,
+        kind: PayoutKind,
+        target_id: i64,
+    ) -> Result<Option<PayoutJob>, Error> {
+        let job = sqlx::query_as!(
+            PayoutJob,
+            r#"
+            SELECT j.id, j.kind as "kind: PayoutKind", j.target_id, j.invoice, j.amount_sats,
+                j.payment_id, j.attempts, j.next_attempt_at, j.last_error, j.sent_at, j.created_at, j.updated_at,
+                CASE WHEN r.status = 'credited' THEN 'paid' ELSE COALESCE(p.status, r.status, w.status) END
+                    as "status!: PayoutStatus"
+            FROM payout_jobs j
+            LEFT JOIN prize_payouts p ON j.kind = 'prize' AND p.id = j.target_id
+            LEFT JOIN refunds r ON j.kind = 'refund' AND r.id = j.target_id
+            LEFT JOIN withdrawals w ON j.kind = 'withdrawal' AND w.id = j.target_id
+            WHERE j.kind = ? AND j.target_id = ?
+            "#,
+            kind,
+            target_id
+        )
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(job)
+    }
+
+    // Payout jobs of every player, newest first, optionally only those in one status
+    pub async fn get_jobs(
+        &self,
+        status: Option<PayoutStatus>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<PayoutJob>, Error> {
+        let jobs = sqlx::query_as!(
+            PayoutJob,
+            r#"
+            SELECT j.id, j.kind as "kind: PayoutKind", j.target_id, j.invoice, j.amount_sats,
+                j.payment_id, j.attempts, j.next_attempt_at, j.last_error, j.sent_at, j.created_at, j.updated_at,
+                CASE WHEN r.status = 'credited' THEN 'paid' ELSE COALESCE(p.status, r.status, w.status) END
+                    as "status!: PayoutStatus"
+            FROM payout_jobs j
+            LEFT JOIN prize_payouts p ON j.kind = 'prize' AND p.id = j.target_id
+            LEFT JOIN refunds r ON j.kind = 'refund' AND r.id = j.target_id
+            LEFT JOIN withdrawals w ON j.kind = 'withdrawal' AND w.id = j.target_id
+            WHERE ? IS NULL
+                OR CASE WHEN r.status = 'credited' THEN 'paid' ELSE COALESCE(p.status, r.status, w.status) END = ?
+            ORDER BY j.id DESC
+            LIMIT ? OFFSET ?
+            "#,
+            status,
+            status,
+            limit,
+            offset
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(jobs)
+    }
+
+    // Move a job to 'sending' under a fresh payment id before handing it to the Lightning
+    // backend, so a restart can always find out what happened to it. Returns false if the
+    // job was picked up elsewhere
+    pub async fn start_attempt(
+        &self,
+        job: &PayoutJob,
+        payment_id: &str,
+        next_check_at: OffsetDateTime,
+    ) -> Result<bool, Error> {
+        let now = OffsetDateTime::now_utc();
+        let updated_at = format_time(now)?;
+        let sent_at = format_time_secs(now)?;
+        let next_check_at = format_time_secs(next_check_at)?;
+
+        let mut tx = self.db.begin().await?;
+
+        if !transition(
+            &mut tx,
+            job,
+            &[PayoutStatus::InvoiceReceived],
+            PayoutStatus::Sending,
+            Some(payment_id),
+        )
+        .await?
+        {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE payout_jobs
+            SET payment_id = ?, attempts = attempts + 1, sent_at = ?, next_attempt_at = ?, updated_at = ?
+            WHERE id = ?
+            "#,
+            payment_id,
+            sent_at,
+            next_check_at,
+            updated_at,
+            job.id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    // Check on an in flight payment again later
+    pub async fn schedule_check(
+        &self,
+        job_id: i64,
+        next_check_at: OffsetDateTime,
+        last_error: Option<&str>,
+    ) -> Result<(), Error> {
+        let updated_at = format_time(OffsetDateTime::now_utc())?;
+        let next_check_at = format_time_secs(next_check_at)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE payout_jobs
+            SET next_attempt_at = ?, last_error = COALESCE(?, last_error), updated_at = ?
+            WHERE id = ?
+            "#,
+            next_check_at,
+            last_error,
+            updated_at,
+            job_id
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    // Put a job whose payment definitely did not go through back in the queue
+    pub async fn retry(
+        &self,
+        job: &PayoutJob,
+        next_attempt_at: OffsetDateTime,
+        error: &str,
+    ) -> Result<(), Error> {
+        let updated_at = format_time(OffsetDateTime::now_utc())?;
+        let next_attempt_at = format_time_secs(next_attempt_at)?;
+
+        let mut tx = self.db.begin().await?;
+
+        transition(
+            &mut tx,
+            job,
+            &[PayoutStatus::Sending],
+            PayoutStatus::InvoiceReceived,
+            None,
+        )
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE payout_jobs
+            SET next_attempt_at = ?, last_error = ?, updated_at = ?
+            WHERE id = ?
+            "#,
+            next_attempt_at,
+            error,
+            updated_at,
+            job.id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // Send a job an operator has looked at again, starting its attempts over. Only safe once
+    // they made sure its last payment did not go through, or the player is paid twice.
+    // Returns false if the job does not need attention
+    pub async fn requeue(&self, job: &PayoutJob) -> Result<bool, Error> {
+        let now = OffsetDateTime::now_utc();
+        let updated_at = format_time(now)?;
+        let next_attempt_at = format_time_secs(now)?;
+
+        let mut tx = self.db.begin().await?;
+
+        if !transition(
+            &mut tx,
+            job,
+            &[PayoutStatus::NeedsAttention],
+            PayoutStatus::InvoiceReceived,
+            None,
+        )
+        .await?
+        {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE payout_jobs
+            SET attempts = 0, next_attempt_at = ?, last_error = NULL, sent_at = NULL, updated_at = ?
+            WHERE id = ?
+            "#,
+            next_attempt_at,
+            updated_at,
+            job.id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    // Stop working on a job, either 'failed' so the player can claim again with a new
+    // invoice or 'needs_attention' when the outcome of a payment is unknown. A failed
+    // withdrawal goes back into the player's balance
+    pub async fn stop(
+        &self,
+        job: &PayoutJob,
+        status: PayoutStatus,
+        error: &str,
+    ) -> Result<(), Error> {
+        let updated_at = format_time(OffsetDateTime::now_utc())?;
+
+        let mut tx = self.db.begin().await?;
+
+        let stopped = transition(
+            &mut tx,
+            job,
+            &[PayoutStatus::InvoiceReceived, PayoutStatus::Sending],
+            status,
+            None,
+        )
+        .await?;
+
+        if stopped && job.kind == PayoutKind::Withdrawal && status == PayoutStatus::Failed {
+            reverse_withdrawal(&mut tx, job.target_id).await?;
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE payout_jobs
+            SET last_error = ?, updated_at = ?
+            WHERE id = ?
+            "#,
+            error,
+            updated_at,
+            job.id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+// Take a settled refund out of its competition's pot
+async fn release_refund(conn: &mut SqliteConnection, id: i64, now: &str) -> Result<Refund, Error> {
+    let refund = sqlx::query_as!(
+        Refund,
+        r#"
+        SELECT id, user_id, competition_id, amount_sats, reason, destination, payment_request,
+            payment_id, status as "status: PayoutStatus", created_at, updated_at, paid_at
+        FROM refunds
+        WHERE id = ?
+        "#,
+        id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE competitions
+        SET pot_sats = pot_sats - ?, updated_at = ?
+        WHERE id = ?
+        "#,
+        refund.amount_sats,
+        now,
+        refund.competition_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(refund)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        domain::testing::{create_test_user, pay_entry, test_competition_store, test_db},
+        Competition, CompetitionStore, LedgerStore, PaymentStore,
+    };
+
+    // A competition two players paid into, voided with a refund for each of them
+    async fn voided_competition(
+        db: &Pool<Sqlite>,
+        competitions: &CompetitionStore,
+    ) -> (Competition, Vec<Refund>) {
+        let payouts = PayoutStore::new(db.clone());
+        let competition = competitions.current_daily().await.unwrap();
+        let first = create_test_user(db).await;
+        let second = create_test_user(db).await;
+        pay_entry(db, first.id, competition.id, 500, 1).await;
+        pay_entry(db, first.id, competition.id, 500, 1).await;
+        pay_entry(db, second.id, competition.id, 500, 1).await;
+
+        competitions
+            .update_status(competition.id, "voided")
+            .await
+            .unwrap();
+        assert_eq!(
+            payouts
+                .create_refunds(competition.id, "Too few players")
+                .await
+                .unwrap(),
+            2
+        );
+
+        let mut refunds = payouts.get_refunds_for_user(first.id).await.unwrap();
+        refunds.extend(payouts.get_refunds_for_user(second.id).await.unwrap());
+        let competition = competitions
+            .find_by_id(competition.id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        (competition, refunds)
+    }
+
+    #[tokio::test]
+    async fn test_voided_competitions_refund_what_each_player_paid() {
+        let db = test_db().await;
+        let payouts = PayoutStore::new(db.clone());
+        let competitions = test_competition_store(&db);
+
+        let (competition, refunds) = voided_competition(&db, &competitions).await;
+        assert_eq!(competition.pot_sats, 1500);
+        assert_eq!(
+            refunds
+                .iter()
+                .map(|refund| (refund.amount_sats, refund.status))
+                .collect::<Vec<_>>(),
+            vec![(1000, PayoutStatus::Pending), (500, PayoutStatus::Pending)]
+        );
+        assert!(refunds
+            .iter()
+            .all(|refund| refund.reason == "Too few players"));
+
+        // Voiding it again leaves the refunds as they are
+        assert_eq!(
+            payouts
+                .create_refunds(competition.id, "Too few players")
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refunds_credited_to_the_balance() {
+        let db = test_db().await;
+        let payouts = PayoutStore::new(db.clone());
+        let payments = PaymentStore::new(db.clone());
+        let competitions = test_competition_store(&db);
+        let (competition, refunds) = voided_competition(&db, &competitions).await;
+        let refund = &refunds[0];
+
+        let credited = payouts.credit_refund(refund.id).await.unwrap().unwrap();
+        assert_eq!(credited.status, PayoutStatus::Credited);
+        assert_eq!(credited.destination.as_deref(), Some("balance"));
+        assert!(credited.paid_at.is_some());
+        assert_eq!(
+            payments.get_user_balance(refund.user_id).await.unwrap(),
+            1000
+        );
+
+        // The refund leaves the pot, in the competition and in the ledger
+        let competition = competitions
+            .find_by_id(competition.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(competition.pot_sats, 500);
+        let ledger = LedgerStore::new(db.clone());
+        assert_eq!(
+            ledger
+                .account_balance(LedgerAccount::Pot(competition.id))
+                .await
+                .unwrap(),
+            -500
+        );
+        assert!(ledger.check_invariants().await.unwrap().is_empty());
+
+        // A credited refund is settled, it cannot be credited or claimed again
+        assert!(payouts.credit_refund(refund.id).await.unwrap().is_none());
+        assert!(payouts
+            .enqueue_refund(refund.id, "bolt11", "lnbc10u1test")
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            payments.get_user_balance(refund.user_id).await.unwrap(),
+            1000
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refunds_paid_through_the_queue() {
+        let db = test_db().await;
+        let payouts = PayoutStore::new(db.clone());
+        let payments = PaymentStore::new(db.clone());
+        let competitions = test_competition_store(&db);
+        let (competition, refunds) = voided_competition(&db, &competitions).await;
+        let refund = &refunds[1];
+
+        let claimed = payouts
+            .enqueue_refund(refund.id, "bolt11", "lnbc5u1test")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(claimed.status, PayoutStatus::InvoiceReceived);
+        assert_eq!(claimed.payment_request.as_deref(), Some("lnbc5u1test"));
+
+        // Claimed refunds can be neither claimed again nor credited, nor paid before they are sent
+        assert!(payouts
+            .enqueue_refund(refund.id, "bolt11", "lnbc5u1other")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(payouts.credit_refund(refund.id).await.unwrap().is_none());
+        assert!(payouts
+            .mark_refund_paid(refund.id, "payment_1", 0)
+            .await
+            .unwrap()
+            .is_none());
+
+        // A failed payment lets the player claim again
+        let job = payouts
+            .get_job(PayoutKind::Refund, refund.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(job.amount_sats, 500);
+        let now = OffsetDateTime::now_utc();
+        assert!(payouts.start_attempt(&job, "payment_1", now).await.unwrap());
+        payouts
+            .stop(&job, PayoutStatus::Failed, "No route")
+            .await
+            .unwrap();
+        let failed = payouts.get_refund(refund.id).await.unwrap().unwrap();
+        assert_eq!(failed.status, PayoutStatus::Failed);
+        assert!(failed.status.is_claimable());
+
+        payouts
+            .enqueue_refund(refund.id, "bolt11", "lnbc5u1again")
+            .await
+            .unwrap()
+            .unwrap();
+        let job = payouts
+            .get_job(PayoutKind::Refund, refund.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(job.invoice, "lnbc5u1again");
+        assert_eq!(job.attempts, 0);
+        assert!(payouts.start_attempt(&job, "payment_2", now).await.unwrap());
+        let paid = payouts
+            .mark_refund_paid(refund.id, "payment_2", 1)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(paid.status, PayoutStatus::Paid);
+        assert_eq!(paid.payment_id.as_deref(), Some("payment_2"));
+
+        // Paying out sends the sats from the pot straight to the player, not into their balance
+        assert_eq!(payments.get_user_balance(refund.user_id).await.unwrap(), 0);
+        let competition = competitions
+            .find_by_id(competition.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(competition.pot_sats, 1000);
+        assert!(LedgerStore::new(db.clone())
+            .check_invariants()
+            .await
+            .unwrap()
+            .is_empty());
+
+        assert!(payouts
+            .mark_refund_paid(refund.id, "payment_2", 1)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(payouts.credit_refund(refund.id).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_settled_refunds_do_not_change() {
+        for next in [
+            PayoutStatus::Pending,
+            PayoutStatus::Credited,
+            PayoutStatus::InvoiceReceived,
+            PayoutStatus::Sending,
+            PayoutStatus::Paid,
+            PayoutStatus::Failed,
+            PayoutStatus::NeedsAttention,
+        ] {
+            assert!(!PayoutStatus::Credited.can_transition_to(next));
+            assert!(!PayoutStatus::Paid.can_transition_to(next));
+        }
+        assert!(!PayoutStatus::InvoiceReceived.can_transition_to(PayoutStatus::Credited));
+        assert!(!PayoutStatus::Sending.can_transition_to(PayoutStatus::Credited));
+        assert!(PayoutStatus::Failed.can_transition_to(PayoutStatus::Credited));
+    }
+}
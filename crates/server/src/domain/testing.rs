@@ -0,0 +1,90 @@
+// Fixtures shared by the store tests
+use nostr_sdk::Keys;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    Pool, Sqlite,
+};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{
+    CompetitionSettings, CompetitionStore, GamePayment, GameStore, PaymentStore, Rulesets, User,
+    UserStore, CLASSIC_RULESET,
+};
+
+// A fresh database with every migration run, in a file of its own so each test has one.
+// A single connection rolls back a dropped transaction before the next query runs
+pub async fn test_db() -> Pool<Sqlite> {
+    let path = std::env::temp_dir().join(format!("game_test_{}.db", Uuid::now_v7()));
+    let db = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(
+            SqliteConnectOptions::new()
+                .filename(path)
+                .create_if_missing(true),
+        )
+        .await
+        .expect("Failed to create test database");
+
+    sqlx::migrate!()
+        .run(&db)
+        .await
+        .expect("Failed to run migrations");
+
+    db
+}
+
+pub fn test_rulesets() -> Rulesets {
+    Rulesets::load(None, CLASSIC_RULESET).expect("Failed to load rulesets")
+}
+
+pub fn test_game_store(db: &Pool<Sqlite>) -> GameStore {
+    GameStore::new(db.clone(), test_rulesets(), Keys::generate())
+}
+
+pub fn test_competition_store(db: &Pool<Sqlite>) -> CompetitionStore {
+    CompetitionStore::new(db.clone(), CompetitionSettings::default(), test_rulesets())
+}
+
+// A new player with a pubkey of their own
+pub async fn create_test_user(db: &Pool<Sqlite>) -> User {
+    let users = UserStore::new(db.clone());
+    let pubkey = Keys::generate().public_key().to_string();
+
+    users.login(pubkey.clone()).await.expect("Failed to log in");
+    users
+        .find_by_pubkey(pubkey)
+        .await
+        .expect("Failed to find user")
+        .expect("User was not created")
+}
+
+// A paid invoice for `credits` games of a competition
+pub async fn pay_entry(
+    db: &Pool<Sqlite>,
+    user_id: i64,
+    competition_id: i64,
+    amount_sats: i64,
+    credits: i64,
+) -> GamePayment {
+    let payments = PaymentStore::new(db.clone());
+    let payment_id = format!("payment_{}", Uuid::now_v7());
+
+    payments
+        .create_game_payment(
+            user_id,
+            competition_id,
+            &payment_id,
+            "lnbc1test",
+            amount_sats,
+            credits,
+            OffsetDateTime::now_utc() + Duration::hours(1),
+        )
+        .await
+        .expect("Failed to create payment");
+    payments
+        .mark_payment_paid(&payment_id)
+        .await
+        .expect("Failed to mark payment paid")
+        .expect("Payment was already settled")
+}
@@ -1,5 +1,7 @@
 mod routes;
+mod stats;
 mod store;
 
 pub use routes::*;
+pub use stats::*;
 pub use store::*;
@@ -1,14 +1,22 @@
 use axum::{
-    extract::State,
+    extract::{Path, Query, State},
     http::StatusCode,
     response::{IntoResponse, Response},
     Json,
 };
 use log::{error, info};
+use nostr_sdk::PublicKey;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use time::OffsetDateTime;
 
-use crate::{map_error, nostr_extractor::NostrAuth, startup::AppState};
+use crate::{
+    domain::Error, map_error, nostr_extractor::NostrAuth, startup::AppState, GameConfig,
+    GameCredit, GamePayment, GameSession, PlayerStats, PracticeScore, PrizePayout, Refund, Score,
+    Withdrawal,
+};
+
+use super::store::User;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegisterPayload {
@@ -22,6 +30,21 @@ pub struct LoginResponse {
     pub pubkey: String,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct UserDataExport {
+    pub exported_at: String,
+    pub user: User,
+    pub game_sessions: Vec<GameSession>,
+    pub game_configs: Vec<GameConfig>,
+    pub scores: Vec<Score>,
+    pub practice_scores: Vec<PracticeScore>,
+    pub game_payments: Vec<GamePayment>,
+    pub game_credits: Vec<GameCredit>,
+    pub prize_payouts: Vec<PrizePayout>,
+    pub withdrawals: Vec<Withdrawal>,
+    pub refunds: Vec<Refund>,
+}
+
 pub async fn login(
     auth: NostrAuth,
     State(state): State<Arc<AppState>>,
@@ -68,3 +91,217 @@ pub async fn register(
         }
     }
 }
+
+// Export everything stored about the calling user
+pub async fn export_user_data(
+    auth: NostrAuth,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    let pubkey = auth.pubkey.to_string();
+    info!("Data export request from pubkey: {}", pubkey);
+
+    let user = match state.user_store.find_by_pubkey(pubkey).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "User not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    let game_sessions = state
+        .game_store
+        .get_sessions_for_user(user.id)
+        .await
+        .map_err(map_error)?;
+    let game_configs = state
+        .game_store
+        .get_configs_for_user(user.id)
+        .await
+        .map_err(map_error)?;
+    let scores = state
+        .game_store
+        .get_all_user_scores(user.id)
+        .await
+        .map_err(map_error)?;
+    let practice_scores = state
+        .game_store
+        .get_practice_scores_for_user(user.id)
+        .await
+        .map_err(map_error)?;
+    let game_payments = state
+        .payment_store
+        .get_payments_for_user(user.id)
+        .await
+        .map_err(map_error)?;
+    let game_credits = state
+        .payment_store
+        .get_credits_for_user(user.id)
+        .await
+        .map_err(map_error)?;
+    let prize_payouts = state
+        .payment_store
+        .get_prizes_for_user(user.id)
+        .await
+        .map_err(map_error)?;
+    let withdrawals = state
+        .payment_store
+        .get_withdrawals_for_user(user.id)
+        .await
+        .map_err(map_error)?;
+    let refunds = state
+        .payout_store
+        .get_refunds_for_user(user.id)
+        .await
+        .map_err(map_error)?;
+
+    let export = UserDataExport {
+        exported_at: OffsetDateTime::now_utc().to_string(),
+        user,
+        game_sessions,
+        game_configs,
+        scores,
+        practice_scores,
+        game_payments,
+        game_credits,
+        prize_payouts,
+        withdrawals,
+        refunds,
+    };
+
+    Ok((StatusCode::OK, Json(export)))
+}
+
+// Delete the calling user's account, keeping anonymised financial records
+pub async fn delete_account(
+    auth: NostrAuth,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    let pubkey = auth.pubkey.to_string();
+    info!("Account deletion request from pubkey: {}", pubkey);
+
+    let user = match state.user_store.find_by_pubkey(pubkey).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "User not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    // Once anonymised the prize can no longer be claimed, so make the user do that first.
+    // Prizes spent or withdrawn from the balance count as settled
+    let prizes = state
+        .payment_store
+        .get_prizes_for_user(user.id)
+        .await
+        .map_err(map_error)?;
+    if prizes.iter().any(|prize| !prize.status.is_settled()) {
+        return Err((
+            StatusCode::CONFLICT,
+            "You have an unpaid prize, claim it and wait for it to be paid before deleting your account",
+        )
+            .into_response());
+    }
+
+    let refunds = state
+        .payout_store
+        .get_refunds_for_user(user.id)
+        .await
+        .map_err(map_error)?;
+    if refunds.iter().any(|refund| !refund.status.is_settled()) {
+        return Err((
+            StatusCode::CONFLICT,
+            "You have an unpaid refund, claim it before deleting your account",
+        )
+            .into_response());
+    }
+
+    let balance = state
+        .payment_store
+        .get_user_balance(user.id)
+        .await
+        .map_err(map_error)?;
+    if balance > 0 {
+        return Err((
+            StatusCode::CONFLICT,
+            "You still have sats in your balance, withdraw them before deleting your account",
+        )
+            .into_response());
+    }
+
+    match state.user_store.delete_user(user.id).await {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(e) => {
+            error!("Account deletion error: {}", e);
+            Err(map_error(e))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatsQuery {
+    /// Days of daily breakdown, 30 if not given
+    pub days: Option<i64>,
+}
+
+// The calling player's stats, including what they have spent
+pub async fn get_my_stats(
+    auth: NostrAuth,
+    Query(query): Query<StatsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    let pubkey = auth.pubkey.to_string();
+    info!("Stats request from pubkey: {}", pubkey);
+
+    let user = match state.user_store.find_by_pubkey(pubkey).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "User not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    match get_stats(&state, user, query).await {
+        Ok(stats) => Ok((StatusCode::OK, Json(stats))),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+// Any player's stats by pubkey (hex or npub), without what they have spent
+pub async fn get_user_stats(
+    Path(pubkey): Path<String>,
+    Query(query): Query<StatsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, Response> {
+    info!("Public stats request for pubkey: {}", pubkey);
+
+    let pubkey = match PublicKey::parse(&pubkey) {
+        Ok(pubkey) => pubkey,
+        Err(e) => {
+            return Err((StatusCode::BAD_REQUEST, format!("Invalid pubkey: {}", e)).into_response())
+        }
+    };
+    let user = match state.user_store.find_by_pubkey(pubkey.to_string()).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, "User not found").into_response()),
+        Err(e) => return Err(map_error(e)),
+    };
+
+    match get_stats(&state, user, query).await {
+        Ok(stats) => Ok((
+            StatusCode::OK,
+            Json(PlayerStats {
+                sats_spent: None,
+                ..stats
+            }),
+        )),
+        Err(e) => Err(map_error(e)),
+    }
+}
+
+async fn get_stats(state: &AppState, user: User, query: StatsQuery) -> Result<PlayerStats, Error> {
+    let days = query.days.unwrap_or(30).clamp(1, 365);
+
+    state
+        .stats_store
+        .get_player_stats(
+            user.id,
+            user.username,
+            OffsetDateTime::now_utc().date(),
+            days,
+        )
+        .await
+}
@@ -0,0 +1,225 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+use time::{Date, Duration};
+
+use crate::{domain::Error, parse_date};
+
+/// A player's record across every competition game they have played
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerStats {
+    pub username: String,
+    pub games_played: i64,
+    pub best_score: Option<i64>,
+    pub average_score: Option<f64>,
+    pub highest_level: Option<i64>,
+    pub total_play_time_secs: i64,
+    pub average_play_time_secs: Option<f64>,
+    /// Paid entry fees, left out when anyone other than the player is looking
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sats_spent: Option<i64>,
+    /// Prizes awarded, paid out or not
+    pub sats_won: i64,
+    pub wins: i64,
+    /// Days in a row with at least one game, up to today or yesterday
+    pub current_streak_days: i64,
+    pub longest_streak_days: i64,
+    pub levels: Vec<LevelCount>,
+    /// Most recent days first
+    pub daily: Vec<DailyStats>,
+}
+
+/// How many games ended on a level
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LevelCount {
+    pub level: i64,
+    pub games: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyStats {
+    /// YYYY-MM-DD, UTC
+    pub date: String,
+    pub games: i64,
+    pub best_score: i64,
+    pub average_score: f64,
+    pub play_time_secs: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct StatsStore {
+    db: Pool<Sqlite>,
+}
+
+impl StatsStore {
+    pub fn new(db: Pool<Sqlite>) -> Self {
+        Self { db }
+    }
+
+    // Stats from the user's counted scores, entry fees and prizes, with a daily breakdown of
+    // the last `days` days up to `today`
+    pub async fn get_player_stats(
+        &self,
+        user_id: i64,
+        username: String,
+        today: Date,
+        days: i64,
+    ) -> Result<PlayerStats, Error> {
+        let totals = sqlx::query!(
+            r#"
+            SELECT
+                COUNT(*) as "games_played!: i64",
+                MAX(score) as "best_score: i64",
+                AVG(score) as "average_score: f64",
+                MAX(level) as "highest_level: i64",
+                COALESCE(SUM(play_time), 0) as "total_play_time_secs!: i64",
+                AVG(play_time) as "average_play_time_secs: f64"
+            FROM scores
+            WHERE user_id = ? AND voided_at IS NULL
+            "#,
+            user_id
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        let sats_spent = sqlx::query!(
+            r#"
+            SELECT COALESCE(SUM(amount_sats), 0) as "sats!: i64"
+            FROM game_payments
+            WHERE user_id = ? AND status = 'paid'
+            "#,
+            user_id
+        )
+        .fetch_one(&self.db)
+        .await?
+        .sats;
+
+        let prizes = sqlx::query!(
+            r#"
+            SELECT COUNT(*) as "wins!: i64", COALESCE(SUM(amount_sats), 0) as "sats!: i64"
+            FROM prize_payouts
+            WHERE user_id = ?
+            "#,
+            user_id
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        let levels = sqlx::query_as!(
+            LevelCount,
+            r#"
+            SELECT level, COUNT(*) as "games!: i64"
+            FROM scores
+            WHERE user_id = ? AND voided_at IS NULL
+            GROUP BY level
+            ORDER BY level ASC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        // Score times start with their date
+        let played_days = sqlx::query!(
+            r#"
+            SELECT DISTINCT substr(created_at, 1, 10) as "date!: String"
+            FROM scores
+            WHERE user_id = ? AND voided_at IS NULL
+            ORDER BY 1 ASC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db)
+        .await?
+        .into_iter()
+        .map(|row| parse_date(&row.date))
+        .collect::<Result<Vec<_>, _>>()?;
+        let (current_streak_days, longest_streak_days) = streaks(&played_days, today);
+
+        let since = (today - Duration::days(days - 1)).to_string();
+        let daily = sqlx::query_as!(
+            DailyStats,
+            r#"
+            SELECT
+                substr(created_at, 1, 10) as "date!: String",
+                COUNT(*) as "games!: i64",
+                MAX(score) as "best_score!: i64",
+                AVG(score) as "average_score!: f64",
+                SUM(play_time) as "play_time_secs!: i64"
+            FROM scores
+            WHERE user_id = ? AND voided_at IS NULL AND created_at >= ?
+            GROUP BY 1
+            ORDER BY 1 DESC
+            "#,
+            user_id,
+            since
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(PlayerStats {
+            username,
+            games_played: totals.games_played,
+            best_score: totals.best_score,
+            average_score: totals.average_score,
+            highest_level: totals.highest_level,
+            total_play_time_secs: totals.total_play_time_secs,
+            average_play_time_secs: totals.average_play_time_secs,
+            sats_spent: Some(sats_spent),
+            sats_won: prizes.sats,
+            wins: prizes.wins,
+            current_streak_days,
+            longest_streak_days,
+            levels,
+            daily,
+        })
+    }
+}
+
+// Current and longest runs of consecutive days played, from distinct days in order. The
+// current run is still alive if the last game was today or yesterday
+fn streaks(days: &[Date], today: Date) -> (i64, i64) {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<Date> = None;
+
+    for day in days {
+        run = match previous {
+            Some(previous) if previous.next_day() == Some(*day) => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        previous = Some(*day);
+    }
+
+    let current = match previous {
+        Some(last) if last == today || last.next_day() == Some(today) => run,
+        _ => 0,
+    };
+
+    (current, longest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::date;
+
+    #[test]
+    fn test_streaks() {
+        let days = [
+            date!(2025 - 06 - 28),
+            date!(2025 - 06 - 29),
+            date!(2025 - 06 - 30),
+            date!(2025 - 07 - 02),
+            date!(2025 - 07 - 03),
+        ];
+
+        assert_eq!(streaks(&days, date!(2025 - 07 - 03)), (2, 3));
+        assert_eq!(streaks(&days, date!(2025 - 07 - 04)), (2, 3));
+        assert_eq!(streaks(&days, date!(2025 - 07 - 05)), (0, 3));
+        assert_eq!(streaks(&[], date!(2025 - 07 - 05)), (0, 0));
+    }
+}
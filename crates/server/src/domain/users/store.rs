@@ -4,7 +4,7 @@ use sqlx::{Pool, Sqlite};
 use time::OffsetDateTime;
 use uuid::Uuid;
 
-use crate::domain::Error;
+use crate::{domain::Error, format_time, format_time_secs};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -24,6 +24,33 @@ pub struct UserInfo {
     pub session_id: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserSummary {
+    pub id: i64,
+    pub nostr_pubkey: String,
+    pub username: String,
+    pub created_at: String,
+    pub deleted_at: Option<String>,
+    pub banned: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Ban {
+    pub id: i64,
+    pub pubkey: String,
+    pub reason: String,
+    /// Pubkey of the admin who banned the player
+    pub banned_by: String,
+    pub created_at: String,
+    /// Bans without an expiry last until they are lifted
+    pub expires_at: Option<String>,
+    /// Set to the expiry, with no admin, once a ban that ran out is replaced
+    pub lifted_at: Option<String>,
+    pub lifted_by: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct UserStore {
     db: Pool<Sqlite>,
@@ -55,7 +82,196 @@ impl UserStore {
         Ok(user)
     }
 
+    pub async fn find_by_id(&self, user_id: i64) -> Result<Option<User>, Error> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            SELECT id, nostr_pubkey, username, created_at, updated_at
+            FROM users
+            WHERE id = ?
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(user)
+    }
+
+    // Users whose username or pubkey contains the search term, newest first
+    pub async fn search_users(
+        &self,
+        search: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<UserSummary>, Error> {
+        let pattern = format!("%{}%", search.unwrap_or_default());
+
+        let users = sqlx::query_as!(
+            UserSummary,
+            r#"
+            SELECT
+                u.id,
+                u.nostr_pubkey,
+                u.username,
+                u.created_at,
+                u.deleted_at,
+                EXISTS (
+                    SELECT 1 FROM active_bans b WHERE b.pubkey = u.nostr_pubkey
+                ) as "banned!: bool"
+            FROM users u
+            WHERE u.username LIKE ? OR u.nostr_pubkey LIKE ?
+            ORDER BY u.id DESC
+            LIMIT ? OFFSET ?
+            "#,
+            pattern,
+            pattern,
+            limit,
+            offset
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(users)
+    }
+
+    // The ban in force against a pubkey, if any
+    pub async fn find_active_ban(&self, pubkey: &str) -> Result<Option<Ban>, Error> {
+        let ban = sqlx::query_as!(
+            Ban,
+            r#"
+            SELECT id, pubkey, reason, banned_by, created_at, expires_at, lifted_at, lifted_by
+            FROM active_bans
+            WHERE pubkey = ?
+            "#,
+            pubkey
+        )
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(ban)
+    }
+
+    // Every ban a pubkey has had, newest first
+    pub async fn get_bans(&self, pubkey: &str) -> Result<Vec<Ban>, Error> {
+        let bans = sqlx::query_as!(
+            Ban,
+            r#"
+            SELECT id, pubkey, reason, banned_by, created_at, expires_at, lifted_at, lifted_by
+            FROM bans
+            WHERE pubkey = ?
+            ORDER BY id DESC
+            "#,
+            pubkey
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(bans)
+    }
+
+    // Turn away a banned pubkey
+    pub async fn check_not_banned(&self, pubkey: &str) -> Result<(), Error> {
+        match self.find_active_ban(pubkey).await? {
+            Some(ban) => match ban.expires_at {
+                Some(expires_at) => Err(Error::Forbidden(format!(
+                    "This account is banned until {}: {}",
+                    expires_at, ban.reason
+                ))),
+                None => Err(Error::Forbidden(format!(
+                    "This account is banned: {}",
+                    ban.reason
+                ))),
+            },
+            None => Ok(()),
+        }
+    }
+
+    // Ban a pubkey, until it is lifted or only until `expires_at` if given.
+    // Returns None if it is already banned
+    pub async fn ban(
+        &self,
+        pubkey: &str,
+        reason: &str,
+        banned_by: &str,
+        expires_at: Option<OffsetDateTime>,
+    ) -> Result<Option<Ban>, Error> {
+        let now = OffsetDateTime::now_utc();
+        let created_at = format_time(now)?;
+        let now = format_time_secs(now)?;
+        let expires_at = expires_at.map(format_time_secs).transpose()?;
+
+        let mut tx = self.db.begin().await?;
+
+        // A ban that ran out is still the one in force as far as the index is concerned
+        sqlx::query!(
+            r#"
+            UPDATE bans
+            SET lifted_at = expires_at
+            WHERE pubkey = ? AND lifted_at IS NULL AND expires_at <= ?
+            "#,
+            pubkey,
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO bans (pubkey, reason, banned_by, created_at, expires_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT DO NOTHING
+            "#,
+            pubkey,
+            reason,
+            banned_by,
+            created_at,
+            expires_at
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        info!("Pubkey {} banned by {}: {}", pubkey, banned_by, reason);
+
+        self.find_active_ban(pubkey).await
+    }
+
+    // Lift the ban on a pubkey, returns false if it was not banned
+    pub async fn lift_ban(&self, pubkey: &str, lifted_by: &str) -> Result<bool, Error> {
+        let now = OffsetDateTime::now_utc();
+        let lifted_at = format_time(now)?;
+        let now = format_time_secs(now)?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE bans
+            SET lifted_at = ?, lifted_by = ?
+            WHERE pubkey = ? AND lifted_at IS NULL AND (expires_at IS NULL OR expires_at > ?)
+            "#,
+            lifted_at,
+            lifted_by,
+            pubkey,
+            now
+        )
+        .execute(&self.db)
+        .await?;
+
+        if result.rows_affected() > 0 {
+            info!("Ban on pubkey {} lifted by {}", pubkey, lifted_by);
+        }
+
+        Ok(result.rows_affected() > 0)
+    }
+
     pub async fn login(&self, pubkey: String) -> Result<UserInfo, Error> {
+        self.check_not_banned(&pubkey).await?;
+
         // Find or create the user
         let user = match self.find_by_pubkey(pubkey.clone()).await? {
             Some(user) => user,
@@ -83,8 +299,10 @@ impl UserStore {
         pubkey: String,
         payload: crate::domain::users::routes::RegisterPayload,
     ) -> Result<UserInfo, Error> {
+        self.check_not_banned(&pubkey).await?;
+
         // Check if user already exists
-        if let Some(_) = self.find_by_pubkey(pubkey.clone()).await? {
+        if self.find_by_pubkey(pubkey.clone()).await?.is_some() {
             return Err(Error::InvalidInput(format!(
                 "User already exists with pubkey: {}",
                 pubkey
@@ -110,8 +328,70 @@ impl UserStore {
         })
     }
 
+    // Anonymise a user: the row stays so scores, entry fees and prize payouts
+    // still add up for past days, but it can no longer be tied to the pubkey
+    pub async fn delete_user(&self, user_id: i64) -> Result<(), Error> {
+        let now = format_time(OffsetDateTime::now_utc())?;
+        let placeholder_pubkey = format!("deleted_{}", Uuid::now_v7());
+        let username = String::from("deleted_player");
+
+        let mut tx = self.db.begin().await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET nostr_pubkey = ?, username = ?, updated_at = ?, deleted_at = ?
+            WHERE id = ?
+            "#,
+            placeholder_pubkey,
+            username,
+            now,
+            now,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // Sessions and configs are only needed while playing
+        sqlx::query!("DELETE FROM game_configs WHERE user_id = ?", user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!("DELETE FROM game_sessions WHERE user_id = ?", user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        // Practice games never count for anything, so they go entirely
+        sqlx::query!("DELETE FROM practice_scores WHERE user_id = ?", user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!("DELETE FROM practice_sessions WHERE user_id = ?", user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        // Invoices of prizes already paid out are no longer needed
+        sqlx::query!(
+            r#"
+            UPDATE prize_payouts
+            SET payment_request = NULL, updated_at = ?
+            WHERE user_id = ? AND status = 'paid'
+            "#,
+            now,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        info!("User {} deleted and anonymised", user_id);
+
+        Ok(())
+    }
+
     async fn create_user(&self, pubkey: String, username: String) -> Result<User, Error> {
-        let now = OffsetDateTime::now_utc().to_string();
+        let now = format_time(OffsetDateTime::now_utc())?;
 
         let user_id = sqlx::query!(
             r#"
@@ -138,3 +418,43 @@ impl UserStore {
         Ok(user)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::testing::{test_db, test_game_store};
+    use nostr_sdk::Keys;
+
+    #[tokio::test]
+    async fn test_deleted_user_is_anonymised() {
+        let db = test_db().await;
+        let users = UserStore::new(db.clone());
+        let games = test_game_store(&db);
+        let pubkey = Keys::generate().public_key().to_string();
+
+        users.login(pubkey.clone()).await.unwrap();
+        let user = users.find_by_pubkey(pubkey.clone()).await.unwrap().unwrap();
+        let practice = games.create_practice_session(Some(user.id)).await.unwrap();
+
+        users.delete_user(user.id).await.unwrap();
+
+        assert!(users
+            .find_by_pubkey(pubkey.clone())
+            .await
+            .unwrap()
+            .is_none());
+        let deleted = users.find_by_id(user.id).await.unwrap().unwrap();
+        assert_eq!(deleted.username, "deleted_player");
+        assert_ne!(deleted.nostr_pubkey, pubkey);
+        assert!(games
+            .find_practice_session(&practice.session_id)
+            .await
+            .unwrap()
+            .is_none());
+
+        // Logging in again starts a new account
+        users.login(pubkey.clone()).await.unwrap();
+        let user_again = users.find_by_pubkey(pubkey).await.unwrap().unwrap();
+        assert_ne!(user_again.id, user.id);
+    }
+}
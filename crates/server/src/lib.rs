@@ -4,14 +4,18 @@ mod domain;
 mod file_utils;
 mod lightning;
 mod nostr_extractor;
+mod payout_queue;
 mod routes;
 mod secrets;
 mod startup;
+mod supervisor;
 
 pub use config::*;
 pub use daily_tasks::*;
 pub use domain::*;
 pub use lightning::*;
+pub use payout_queue::*;
 pub use routes::*;
 pub use secrets::{get_key, SecretKeyHandler};
 pub use startup::*;
+pub use supervisor::*;
@@ -0,0 +1,188 @@
+// Amount encoded in the human readable part of a BOLT11 invoice, None if it has no amount
+pub fn invoice_amount_msats(invoice: &str) -> Option<i64> {
+    let invoice = invoice.to_lowercase();
+    let separator = invoice.rfind('1')?;
+    let prefix = invoice.get(..separator)?.strip_prefix("ln")?;
+
+    // Skip the currency, e.g. "bc" or "tb", to get to the amount
+    let amount = prefix.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+    if amount.is_empty() {
+        return None;
+    }
+
+    let (digits, multiplier) = match amount.chars().last()? {
+        'm' | 'u' | 'n' | 'p' => amount.split_at(amount.len() - 1),
+        _ => (amount, ""),
+    };
+    let value: i64 = digits.parse().ok()?;
+
+    match multiplier {
+        "" => value.checked_mul(100_000_000_000),
+        "m" => value.checked_mul(100_000_000),
+        "u" => value.checked_mul(100_000),
+        "n" => value.checked_mul(100),
+        // Pico-bitcoin amounts must be whole millisatoshis
+        "p" if value % 10 == 0 => Some(value / 10),
+        _ => None,
+    }
+}
+
+const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+// Invoices without an expiry field expire an hour after they were created
+const DEFAULT_EXPIRY_SECS: i64 = 3600;
+
+// Unix time a BOLT11 invoice expires at, from its timestamp and expiry field
+pub fn invoice_expires_at(invoice: &str) -> Option<i64> {
+    let words = invoice_words(invoice)?;
+    let timestamp = to_number(&words[..7]);
+    let fields = &words[7..words.len() - 104 - 6];
+
+    let mut expiry = DEFAULT_EXPIRY_SECS;
+    let mut position = 0;
+    while position + 3 <= fields.len() {
+        let tag = fields[position];
+        let length = to_number(&fields[position + 1..position + 3]) as usize;
+        let data = fields.get(position + 3..position + 3 + length)?;
+
+        // 'x' holds the number of seconds the invoice is valid for
+        if tag == 6 {
+            expiry = to_number(data);
+        }
+        position += 3 + length;
+    }
+
+    timestamp.checked_add(expiry)
+}
+
+// Data part of a BOLT11 invoice as 5 bit words: a 35 bit timestamp, then tagged fields,
+// then a 520 bit signature and the checksum
+fn invoice_words(invoice: &str) -> Option<Vec<i64>> {
+    let invoice = invoice.to_lowercase();
+    let separator = invoice.rfind('1')?;
+    let words = invoice
+        .get(separator + 1..)?
+        .chars()
+        .map(|c| BECH32_CHARSET.find(c).map(|value| value as i64))
+        .collect::<Option<Vec<i64>>>()?;
+
+    if words.len() < 7 + 104 + 6 {
+        return None;
+    }
+    Some(words)
+}
+
+// Payment hash of a BOLT11 invoice in hex, the hash the payer's preimage must match
+pub fn invoice_payment_hash(invoice: &str) -> Option<String> {
+    let words = invoice_words(invoice)?;
+    let fields = &words[7..words.len() - 104 - 6];
+
+    let mut position = 0;
+    while position + 3 <= fields.len() {
+        let tag = fields[position];
+        let length = to_number(&fields[position + 1..position + 3]) as usize;
+        let data = fields.get(position + 3..position + 3 + length)?;
+
+        // 'p' holds the 256 bit hash in 52 words, the last 4 bits padding
+        if tag == 1 && length == 52 {
+            return Some(hex::encode(to_bytes(data)));
+        }
+        position += 3 + length;
+    }
+
+    None
+}
+
+// Regroup 5 bit words into bytes, dropping the bits left over at the end
+fn to_bytes(words: &[i64]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut buffer = 0;
+    let mut bits = 0;
+    for word in words {
+        buffer = buffer << 5 | word;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    bytes
+}
+
+fn to_number(words: &[i64]) -> i64 {
+    words.iter().fold(0, |number, word| number << 5 | word)
+}
+
+// Split a Lightning Address into the user and the domain serving its LNURL endpoint
+pub fn parse_lightning_address(address: &str) -> Option<(&str, &str)> {
+    let (user, domain) = address.split_once('@')?;
+    let valid_user = !user.is_empty()
+        && user
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.+".contains(c));
+    let valid_domain = domain.contains('.')
+        && domain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-.:".contains(c));
+
+    if valid_user && valid_domain {
+        Some((user, domain))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invoice_amounts() {
+        assert_eq!(
+            invoice_amount_msats("lnbc2500u1pvjluezpp5qqqsyq"),
+            Some(250_000_000)
+        );
+        assert_eq!(invoice_amount_msats("lnbc10n1pjq9xyz"), Some(1_000));
+        assert_eq!(invoice_amount_msats("LNBC20M1PVJLUEZ"), Some(2_000_000_000));
+        assert_eq!(invoice_amount_msats("lntb5u1pjq9xyz"), Some(500_000));
+        assert_eq!(invoice_amount_msats("lnbc1pvjluezpp5qqqsyq"), None);
+        assert_eq!(invoice_amount_msats("lnbc15p1pjq9xyz"), None);
+        assert_eq!(invoice_amount_msats("not an invoice"), None);
+    }
+
+    #[test]
+    fn test_invoice_expiry() {
+        // Timestamp 1496314658 with an expiry of 60 seconds, from the BOLT11 examples
+        let with_expiry = "lnbc2500u1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5xysxxatsyp3k7enxv4jsxqzpu9qrsgquk0rl77nj30yxdy8j9vdx85fkpmdla2087ne0xh8nhedh8w27kyke0lp53ut353s06fv3qfegext0eh0ymjpf39tuven09sam30g4vgpfna3rh";
+        assert_eq!(invoice_expires_at(with_expiry), Some(1496314658 + 60));
+
+        let without_expiry = format!("lnbc1pvjluezpp5{}{}", "q".repeat(52), "q".repeat(110));
+        assert_eq!(invoice_expires_at(&without_expiry), Some(1496314658 + 3600));
+
+        assert_eq!(invoice_expires_at("lnbc1pvjluez"), None);
+    }
+
+    #[test]
+    fn test_invoice_payment_hash() {
+        // Payment hash 0001020304050607080900010203040506070809000102030405060708090102
+        // from the BOLT11 examples
+        let invoice = "lnbc2500u1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5xysxxatsyp3k7enxv4jsxqzpu9qrsgquk0rl77nj30yxdy8j9vdx85fkpmdla2087ne0xh8nhedh8w27kyke0lp53ut353s06fv3qfegext0eh0ymjpf39tuven09sam30g4vgpfna3rh";
+        assert_eq!(
+            invoice_payment_hash(invoice).as_deref(),
+            Some("0001020304050607080900010203040506070809000102030405060708090102")
+        );
+        assert_eq!(invoice_payment_hash("lnbc1pvjluez"), None);
+    }
+
+    #[test]
+    fn test_lightning_addresses() {
+        assert_eq!(
+            parse_lightning_address("satoshi@example.com"),
+            Some(("satoshi", "example.com"))
+        );
+        assert_eq!(parse_lightning_address("satoshi@localhost"), None);
+        assert_eq!(parse_lightning_address("@example.com"), None);
+        assert_eq!(parse_lightning_address("sat/oshi@example.com"), None);
+    }
+}
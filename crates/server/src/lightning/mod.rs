@@ -1,5 +1,7 @@
+mod invoice;
 mod models;
 mod service;
 
+pub use invoice::*;
 pub use models::*;
 pub use service::*;
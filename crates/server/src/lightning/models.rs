@@ -54,7 +54,7 @@ pub enum PaymentKind {
     Bip21,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub enum PaymentStatus {
     #[serde(rename = "sending")]
@@ -69,7 +69,7 @@ pub enum PaymentStatus {
 
 // Request models
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "snake_case")]
 pub struct ReceivePaymentRequest {
     pub id: String,
     pub wallet_id: String,
@@ -83,7 +83,7 @@ pub struct ReceivePaymentRequest {
 
 // Response models for bolt11 receive payments
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "snake_case")]
 pub struct Bolt11ReceiveData {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub payment_request: Option<String>,
@@ -93,7 +93,7 @@ pub struct Bolt11ReceiveData {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "snake_case")]
 pub struct ReceivePayment {
     pub id: String,
     pub wallet_id: String,
@@ -111,7 +111,7 @@ pub struct ReceivePayment {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "snake_case")]
 pub struct ReceiveError {
     pub detail: Option<String>,
     #[serde(rename = "type")]
@@ -120,7 +120,7 @@ pub struct ReceiveError {
 
 // Send payment models
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "snake_case")]
 pub struct SendPaymentRequest {
     pub id: String,
     pub wallet_id: String,
@@ -131,7 +131,7 @@ pub struct SendPaymentRequest {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "snake_case")]
 pub struct SendPaymentData {
     pub payment_request: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -141,7 +141,7 @@ pub struct SendPaymentData {
 
 // Response models for Lightning payments
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "snake_case")]
 pub struct Payment {
     pub id: String,
     pub wallet_id: String,
@@ -158,6 +158,30 @@ pub struct Payment {
     pub error: Option<serde_json::Value>,
 }
 
+impl Payment {
+    // Routing fee paid for a sent payment, rounded up to whole sats
+    pub fn fee_sats(&self) -> i64 {
+        let fee_msats = self.data["fee_msats"].as_i64().unwrap_or(0);
+        (fee_msats + 999) / 1000
+    }
+
+    // Preimage the receiver revealed for a completed payment, proof that it was paid
+    pub fn preimage(&self) -> Option<String> {
+        self.data["preimage"].as_str().map(String::from)
+    }
+
+    // Why the backend gave up on a failed payment, if it said
+    pub fn error_message(&self) -> Option<String> {
+        match self.error.as_ref()? {
+            serde_json::Value::String(message) => Some(message.clone()),
+            error => error["detail"]
+                .as_str()
+                .or(error["type"].as_str())
+                .map(String::from),
+        }
+    }
+}
+
 // Custom error type for the Lightning service
 #[derive(Debug, thiserror::Error)]
 pub enum LightningError {
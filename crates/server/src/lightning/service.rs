@@ -1,14 +1,17 @@
-use ::time::OffsetDateTime;
 use hyper::StatusCode;
 use log::{error, info, warn};
 use reqwest_middleware::ClientWithMiddleware;
+use serde::de::DeserializeOwned;
 use serde_json::Value;
 use std::time::Duration;
 use tokio::time;
 use uuid::Uuid;
 
-use super::models::LightningError;
-use super::models::*;
+use super::invoice::{invoice_amount_msats, parse_lightning_address};
+use super::models::{
+    Balance, Currency, LightningError, Payment, PaymentDirection, PaymentKind, PaymentStatus,
+    ReceivePayment, ReceivePaymentRequest, SendPaymentData, SendPaymentRequest,
+};
 
 #[derive(Debug, Clone)]
 pub struct LightningService {
@@ -48,17 +51,20 @@ impl LightningService {
         info!("Creating Lightning invoice for {} sats", amount_sats);
 
         let payment_id = Uuid::now_v7().to_string();
-        let amount_msats = amount_sats * 1000; // Convert sats to msats
 
         // Create request payload according to Voltage API spec
-        let request = serde_json::json!({
-            "id": payment_id.clone(),
-            "wallet_id": self.wallet_id,
-            "currency": "btc",
-            "amount_msats": amount_msats,
-            "payment_kind": "bolt11",
-            "description": description.unwrap_or("Asteroids Game Entry Fee")
-        });
+        let request = ReceivePaymentRequest {
+            id: payment_id.clone(),
+            wallet_id: self.wallet_id.clone(),
+            currency: Currency::Btc,
+            amount_msats: Some(amount_sats * 1000), // Convert sats to msats
+            payment_kind: PaymentKind::Bolt11,
+            description: Some(
+                description
+                    .unwrap_or("Asteroids Game Entry Fee")
+                    .to_string(),
+            ),
+        };
 
         let url = format!(
             "{}organizations/{}/environments/{}/payments",
@@ -97,138 +103,76 @@ impl LightningService {
     pub async fn get_payment_status(
         &self,
         payment_id: &str,
-    ) -> Result<Option<Value>, LightningError> {
+    ) -> Result<Option<Payment>, LightningError> {
         info!("Checking payment status for: {}", payment_id);
 
-        let url = format!(
-            "{}organizations/{}/environments/{}/payments/{}",
-            self.api_url, self.organization_id, self.environment_id, payment_id
-        );
-
-        let response = self
-            .client
-            .get(&url)
-            .header("x-api-key", &self.api_key)
-            .send()
-            .await
-            .map_err(LightningError::RequestError)?;
-
-        // Handle 404 specially - payment might be in the process of being created
-        if response.status() == StatusCode::NOT_FOUND {
-            info!("Payment {} not found yet (still being created)", payment_id);
-
-            // Return a placeholder "in progress" payment
-            return Ok(None);
+        let payment: Option<Payment> = self.get_payment(payment_id).await?;
+        if let Some(payment) = &payment {
+            info!("Payment {} status: {:?}", payment_id, payment.status);
         }
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-
-            return Err(LightningError::ApiError(format!(
-                "Failed to get payment status: {} - {}",
-                status, error_text
-            )));
-        }
-
-        let payment_json: Value = response.json().await.map_err(|e| {
-            LightningError::InvalidResponse(format!(
-                "Failed to parse payment status response: {}",
-                e
-            ))
-        })?;
-
-        info!("Payment details: {}", payment_json);
-
-        Ok(Some(payment_json))
+        Ok(payment)
     }
 
+    // Get the BOLT11 invoice of a receive payment, None until the backend has created it
     pub async fn get_payment_invoice(
         &self,
         payment_id: &str,
     ) -> Result<Option<String>, LightningError> {
         info!("Getting invoice for payment_id: {}", payment_id);
 
-        // Try to get the payment status
+        let payment: Option<ReceivePayment> = self.get_payment(payment_id).await?;
+
+        Ok(payment.and_then(|payment| {
+            (payment.payment_type == PaymentKind::Bolt11
+                && payment.direction == PaymentDirection::Receive)
+                .then_some(payment.data.payment_request)
+                .flatten()
+        }))
+    }
+
+    // Fetch a payment from the backend. A 404 means the payment may still be being created
+    async fn get_payment<T: DeserializeOwned>(
+        &self,
+        payment_id: &str,
+    ) -> Result<Option<T>, LightningError> {
+        let url = format!(
+            "{}organizations/{}/environments/{}/payments/{}",
+            self.api_url, self.organization_id, self.environment_id, payment_id
+        );
+
         let response = self
             .client
-            .get(&format!(
-                "{}organizations/{}/environments/{}/payments/{}",
-                self.api_url, self.organization_id, self.environment_id, payment_id
-            ))
+            .get(&url)
             .header("x-api-key", &self.api_key)
             .send()
             .await
             .map_err(LightningError::RequestError)?;
 
-        // Handle 404 responses specially - the payment exists but isn't ready yet
         if response.status() == StatusCode::NOT_FOUND {
             info!("Payment {} not found yet (still being created)", payment_id);
-            return Ok(None); // Return None to indicate "not ready yet"
+            return Ok(None);
         }
 
-        // Handle other error responses
         if !response.status().is_success() {
+            let status = response.status();
             let error_text = response
                 .text()
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            warn!("Error getting payment details: {}", error_text);
+            warn!("Error getting payment {}: {}", payment_id, error_text);
+
             return Err(LightningError::ApiError(format!(
-                "Failed to get payment details: {}",
-                error_text
+                "Failed to get payment: {} - {}",
+                status, error_text
             )));
         }
 
-        // Log response status and headers before consuming the body
-        info!(
-            "Response status: {}, headers: {:?}",
-            response.status(),
-            response.headers()
-        );
-
-        // Parse the successful response
-        let payment: Value = response.json().await.map_err(|e| {
+        let payment = response.json().await.map_err(|e| {
             LightningError::InvalidResponse(format!("Failed to parse payment response: {}", e))
         })?;
-        info!("Payment details: {:?}", payment);
-
-        // Extract the payment request
-        self.extract_invoice_from_payment(&payment)
-            .map(Some)
-            .or(Ok(None))
-    }
-
-    fn extract_invoice_from_payment(&self, payment: &Value) -> Result<String, LightningError> {
-        // Check payment type
-        let payment_type = payment["type"].as_str().ok_or_else(|| {
-            LightningError::PaymentError("Payment type not found or not a string".to_string())
-        })?;
-
-        // Check payment direction
-        let direction = payment["direction"].as_str().ok_or_else(|| {
-            LightningError::PaymentError("Payment direction not found or not a string".to_string())
-        })?;
-
-        // Verify that it's a BOLT11 receive payment
-        if payment_type == "bolt11" && direction == "receive" {
-            // Try to extract the payment request from the data
-            if let Some(data) = payment.get("data") {
-                if let Some(payment_request) = data.get("payment_request") {
-                    if let Some(request_str) = payment_request.as_str() {
-                        return Ok(request_str.to_string());
-                    }
-                }
-            }
-        }
 
-        // If we reach here, we couldn't find a payment request
-        Err(LightningError::PaymentError(
-            "No invoice found in payment data".to_string(),
-        ))
+        Ok(Some(payment))
     }
 
     // Wait for payment to be received with timeout
@@ -236,7 +180,7 @@ impl LightningService {
         &self,
         payment_id: &str,
         timeout_secs: u64,
-    ) -> Result<Value, LightningError> {
+    ) -> Result<Payment, LightningError> {
         info!(
             "Waiting for payment {} with timeout {}s",
             payment_id, timeout_secs
@@ -256,27 +200,24 @@ impl LightningService {
             }
 
             match self.get_payment_status(payment_id).await {
-                Ok(Some(payment)) => {
-                    // Check status
-                    if let Some(status) = payment["status"].as_str() {
-                        match status {
-                            "completed" => {
-                                info!("Payment {} completed", payment_id);
-                                return Ok(payment);
-                            }
-                            "failed" => {
-                                return Err(LightningError::PaymentError(format!(
-                                    "Payment {} failed",
-                                    payment_id
-                                )));
-                            }
-                            _ => {
-                                // Payment still in progress, continue polling
-                                info!("Payment {} status: {}", payment_id, status);
-                            }
-                        }
+                Ok(Some(payment)) => match payment.status {
+                    PaymentStatus::Completed => {
+                        info!("Payment {} completed", payment_id);
+                        return Ok(payment);
                     }
-                }
+                    PaymentStatus::Failed => {
+                        return Err(LightningError::PaymentError(
+                            match payment.error_message() {
+                                Some(reason) => {
+                                    format!("Payment {} failed: {}", payment_id, reason)
+                                }
+                                None => format!("Payment {} failed", payment_id),
+                            },
+                        ));
+                    }
+                    // Payment still in progress, continue polling
+                    PaymentStatus::Sending | PaymentStatus::Receiving => {}
+                },
                 Ok(None) => {
                     info!("Payment {} not found yet, will retry", payment_id);
                 }
@@ -296,24 +237,40 @@ impl LightningService {
         &self,
         invoice: &str,
         amount_msats: i64,
-    ) -> Result<Value, LightningError> {
+    ) -> Result<Payment, LightningError> {
+        let payment_id = Uuid::now_v7().to_string();
+
+        self.send_payment(&payment_id, invoice, amount_msats)
+            .await?;
+
+        // Wait for payment to complete
+        self.wait_for_payment(&payment_id, 60).await
+    }
+
+    // Hand a payment to the Lightning backend under our own id without waiting for it
+    // to complete. The id lets the payment be looked up again if the caller goes away
+    pub async fn send_payment(
+        &self,
+        payment_id: &str,
+        invoice: &str,
+        amount_msats: i64,
+    ) -> Result<(), LightningError> {
         info!(
-            "Sending payment for invoice, amount: {} msats",
-            amount_msats
+            "Sending payment {} for invoice, amount: {} msats",
+            payment_id, amount_msats
         );
 
-        let payment_id = Uuid::now_v7().to_string();
-
-        let request = serde_json::json!({
-            "id": payment_id,
-            "wallet_id": self.wallet_id,
-            "currency": "btc",
-            "type": "bolt11",
-            "data": {
-                "payment_request": invoice,
-                "max_fee_msats": amount_msats / 100, // 1% fee limit
-            }
-        });
+        let request = SendPaymentRequest {
+            id: payment_id.to_string(),
+            wallet_id: self.wallet_id.clone(),
+            currency: Currency::Btc,
+            payment_type: PaymentKind::Bolt11,
+            data: SendPaymentData {
+                payment_request: invoice.to_string(),
+                amount_msats: None,
+                max_fee_msats: amount_msats / 100, // 1% fee limit
+            },
+        };
 
         let url = format!(
             "{}organizations/{}/environments/{}/payments",
@@ -341,14 +298,142 @@ impl LightningService {
             )));
         }
 
-        let payment: Value = response.json().await.map_err(|e| {
-            LightningError::InvalidResponse(format!("Failed to parse payment response: {}", e))
+        info!("Payment {} accepted by the backend", payment_id);
+
+        Ok(())
+    }
+
+    // Get the balance of the game wallet, amounts are in msats
+    pub async fn get_wallet_balance(&self) -> Result<Balance, LightningError> {
+        let url = format!(
+            "{}organizations/{}/environments/{}/wallets/{}",
+            self.api_url, self.organization_id, self.environment_id, self.wallet_id
+        );
+
+        let response = self
+            .client
+            .get(&url)
+            .header("x-api-key", &self.api_key)
+            .send()
+            .await
+            .map_err(LightningError::RequestError)?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(LightningError::ApiError(format!(
+                "Failed to get wallet: {} - {}",
+                status, error_text
+            )));
+        }
+
+        let wallet: Value = response.json().await.map_err(|e| {
+            LightningError::InvalidResponse(format!("Failed to parse wallet response: {}", e))
         })?;
 
-        info!("Payment initiated with id: {}", payment["id"]);
+        let balances: Vec<Balance> =
+            serde_json::from_value(wallet["balances"].clone()).map_err(|e| {
+                LightningError::InvalidResponse(format!("Failed to parse wallet balances: {}", e))
+            })?;
 
-        // Wait for payment to complete
-        self.wait_for_payment(&payment["id"].as_str().unwrap_or(&payment_id), 60)
+        balances
+            .into_iter()
+            .find(|balance| matches!(balance.currency, Currency::Btc))
+            .ok_or_else(|| LightningError::InvalidResponse("Wallet has no btc balance".to_string()))
+    }
+    // Fetch a BOLT11 invoice for the given amount from a Lightning Address using LNURL-pay
+    pub async fn resolve_lightning_address(
+        &self,
+        address: &str,
+        amount_sats: i64,
+    ) -> Result<String, LightningError> {
+        let (user, domain) = parse_lightning_address(address).ok_or_else(|| {
+            LightningError::PaymentError(format!("Invalid Lightning Address: {}", address))
+        })?;
+        let amount_msats = amount_sats * 1000;
+
+        info!(
+            "Resolving Lightning Address {} for {} sats",
+            address, amount_sats
+        );
+
+        let pay_request = self
+            .get_lnurl_json(&format!("https://{}/.well-known/lnurlp/{}", domain, user))
+            .await?;
+
+        if pay_request["tag"].as_str() != Some("payRequest") {
+            return Err(LightningError::InvalidResponse(format!(
+                "{} is not a LNURL-pay endpoint",
+                address
+            )));
+        }
+
+        let callback = pay_request["callback"].as_str().ok_or_else(|| {
+            LightningError::InvalidResponse("LNURL-pay response has no callback".to_string())
+        })?;
+        let min_sendable = pay_request["minSendable"].as_i64().unwrap_or(0);
+        let max_sendable = pay_request["maxSendable"].as_i64().unwrap_or(i64::MAX);
+
+        if amount_msats < min_sendable || amount_msats > max_sendable {
+            return Err(LightningError::PaymentError(format!(
+                "{} accepts between {} and {} msats",
+                address, min_sendable, max_sendable
+            )));
+        }
+
+        let separator = if callback.contains('?') { '&' } else { '?' };
+        let invoice_response = self
+            .get_lnurl_json(&format!("{}{}amount={}", callback, separator, amount_msats))
+            .await?;
+
+        let invoice = invoice_response["pr"].as_str().ok_or_else(|| {
+            LightningError::PaymentError(format!(
+                "Lightning Address returned no invoice: {}",
+                invoice_response["reason"]
+                    .as_str()
+                    .unwrap_or("unknown reason")
+            ))
+        })?;
+
+        // Never trust the remote service to have honoured the requested amount
+        if invoice_amount_msats(invoice) != Some(amount_msats) {
+            return Err(LightningError::PaymentError(
+                "Lightning Address returned an invoice for the wrong amount".to_string(),
+            ));
+        }
+
+        Ok(invoice.to_string())
+    }
+
+    async fn get_lnurl_json(&self, url: &str) -> Result<Value, LightningError> {
+        let response = self
+            .client
+            .get(url)
+            .send()
             .await
+            .map_err(LightningError::RequestError)?;
+
+        if !response.status().is_success() {
+            return Err(LightningError::ApiError(format!(
+                "LNURL request failed: {}",
+                response.status()
+            )));
+        }
+
+        let body: Value = response.json().await.map_err(|e| {
+            LightningError::InvalidResponse(format!("Failed to parse LNURL response: {}", e))
+        })?;
+
+        if body["status"].as_str() == Some("ERROR") {
+            return Err(LightningError::PaymentError(format!(
+                "LNURL error: {}",
+                body["reason"].as_str().unwrap_or("unknown reason")
+            )));
+        }
+
+        Ok(body)
     }
 }
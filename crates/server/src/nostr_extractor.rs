@@ -8,34 +8,14 @@ use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use hyper::{header::AUTHORIZATION, StatusCode};
 use log::{info, warn};
 use nostr_sdk::{
-    hashes::sha256::Hash as Sha256Hash,
     nips::nip98::{HttpData, HttpMethod},
-    Event, EventBuilder, Keys, Kind, PublicKey, Url,
+    Event, Kind, PublicKey, Url,
 };
 use serde::{ser::SerializeStruct, Serialize, Serializer};
 use serde_json::json;
 use std::str::FromStr;
 use time::OffsetDateTime;
 
-pub async fn create_auth_event(
-    method: &str,
-    url: &str,
-    payload_hash: Option<Sha256Hash>,
-    keys: &Keys,
-) -> Event {
-    let http_method = HttpMethod::from_str(method).unwrap();
-    let http_url = Url::from_str(url).unwrap();
-    let mut http_data = HttpData::new(http_url, http_method);
-
-    if let Some(hash) = payload_hash {
-        http_data = http_data.payload(hash);
-    }
-
-    EventBuilder::http_auth(http_data)
-        .sign_with_keys(keys)
-        .expect("Failed to sign event")
-}
-
 #[derive(Clone, Debug)]
 pub struct NostrAuth {
     pub pubkey: PublicKey,
@@ -133,6 +113,27 @@ where
     }
 }
 
+// Lets handlers accept anonymous requests, a header that is present must still be valid
+#[derive(Clone, Debug)]
+pub struct OptionalNostrAuth(pub Option<NostrAuth>);
+
+impl<S> FromRequestParts<S> for OptionalNostrAuth
+where
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if !parts.headers.contains_key(AUTHORIZATION) {
+            return Ok(Self(None));
+        }
+
+        NostrAuth::from_request_parts(parts, state)
+            .await
+            .map(|auth| Self(Some(auth)))
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum AuthError {
     #[error("No authorization header found")]
@@ -237,6 +238,25 @@ mod tests {
     #[derive(Clone)]
     pub struct AppState;
 
+    pub async fn create_auth_event(
+        method: &str,
+        url: &str,
+        payload_hash: Option<Sha256Hash>,
+        keys: &Keys,
+    ) -> Event {
+        let http_method = HttpMethod::from_str(method).unwrap();
+        let http_url = Url::from_str(url).unwrap();
+        let mut http_data = HttpData::new(http_url, http_method);
+
+        if let Some(hash) = payload_hash {
+            http_data = http_data.payload(hash);
+        }
+
+        EventBuilder::http_auth(http_data)
+            .sign_with_keys(keys)
+            .expect("Failed to sign event")
+    }
+
     #[tokio::test]
     async fn test_valid_get_request() {
         let keys = Keys::generate();
@@ -567,4 +587,37 @@ mod tests {
 
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_optional_auth_without_header() {
+        let state = AppState;
+
+        let req = Request::builder()
+            .method("GET")
+            .uri("/test")
+            .header("host", "localhost")
+            .body(())
+            .unwrap();
+
+        let result = OptionalNostrAuth::from_request_parts(&mut req.into_parts().0, &state).await;
+
+        assert!(matches!(result, Ok(OptionalNostrAuth(None))));
+    }
+
+    #[tokio::test]
+    async fn test_optional_auth_rejects_invalid_header() {
+        let state = AppState;
+
+        let req = Request::builder()
+            .method("GET")
+            .uri("/test")
+            .header("host", "localhost")
+            .header(AUTHORIZATION, "Bearer token")
+            .body(())
+            .unwrap();
+
+        let result = OptionalNostrAuth::from_request_parts(&mut req.into_parts().0, &state).await;
+
+        assert!(matches!(result, Err(AuthError::InvalidAuthFormat)));
+    }
 }
@@ -0,0 +1,258 @@
+use log::{error, info, warn};
+use std::sync::Arc;
+use time::{Duration, OffsetDateTime};
+use tokio::{select, time as tokio_time};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::{
+    domain::Error, parse_time, startup::AppState, PaymentStatus, PayoutJob, PayoutKind,
+    PayoutStatus,
+};
+
+// Process to pay claimed prizes, refunds and withdrawals in the background. Jobs live in the database, so
+// payments that were in flight when the server stopped are checked on again after a restart
+pub async fn run_payout_queue(app_state: Arc<AppState>, shutdown: CancellationToken) {
+    info!("Starting payout queue");
+
+    let mut interval = tokio_time::interval(tokio_time::Duration::from_secs(
+        app_state.payout_settings.poll_interval_secs,
+    ));
+
+    loop {
+        select! {
+            _ = interval.tick() => {}
+            _ = shutdown.cancelled() => {
+                info!("Stopping payout queue");
+                return;
+            }
+        }
+
+        let jobs = match app_state
+            .payout_store
+            .get_due_jobs(OffsetDateTime::now_utc())
+            .await
+        {
+            Ok(jobs) => jobs,
+            Err(e) => {
+                error!("Failed to find due payout jobs: {}", e);
+                continue;
+            }
+        };
+
+        for job in jobs {
+            // Jobs left over are picked up again after the restart
+            if shutdown.is_cancelled() {
+                break;
+            }
+            if let Err(e) = process_payout_job(&app_state, &job).await {
+                error!("Failed to process payout job {}: {}", job.id, e);
+            }
+        }
+    }
+}
+
+// Move a payout job one step along, either sending its payment or checking on it
+pub async fn process_payout_job(app_state: &AppState, job: &PayoutJob) -> Result<(), Error> {
+    match job.status {
+        PayoutStatus::InvoiceReceived => send_payout(app_state, job).await,
+        PayoutStatus::Sending => check_payout(app_state, job).await,
+        PayoutStatus::Pending
+        | PayoutStatus::Paid
+        | PayoutStatus::Failed
+        | PayoutStatus::NeedsAttention
+        | PayoutStatus::Credited => Ok(()),
+    }
+}
+
+async fn send_payout(app_state: &AppState, job: &PayoutJob) -> Result<(), Error> {
+    let settings = &app_state.payout_settings;
+    let payment_id = Uuid::now_v7().to_string();
+    let next_check_at =
+        OffsetDateTime::now_utc() + Duration::seconds(settings.poll_interval_secs as i64);
+
+    // Record the payment id first, so the payment can be found again whatever happens next
+    if !app_state
+        .payout_store
+        .start_attempt(job, &payment_id, next_check_at)
+        .await?
+    {
+        return Ok(());
+    }
+
+    info!(
+        "Sending {} {} payment {}, attempt {}",
+        job.kind,
+        job.target_id,
+        payment_id,
+        job.attempts + 1
+    );
+
+    if let Err(e) = app_state
+        .lightning_service
+        .send_payment(&payment_id, &job.invoice, job.amount_sats * 1000)
+        .await
+    {
+        // The backend may still have taken the payment, the next check finds out
+        warn!(
+            "{} {} payment {} not confirmed: {}",
+            job.kind, job.target_id, payment_id, e
+        );
+        app_state
+            .payout_store
+            .schedule_check(job.id, next_check_at, Some(&e.to_string()))
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn check_payout(app_state: &AppState, job: &PayoutJob) -> Result<(), Error> {
+    let settings = &app_state.payout_settings;
+    let store = &app_state.payout_store;
+
+    let Some(payment_id) = job.payment_id.as_deref() else {
+        return store
+            .stop(
+                job,
+                PayoutStatus::NeedsAttention,
+                "Payment in flight without a payment id",
+            )
+            .await;
+    };
+
+    let now = OffsetDateTime::now_utc();
+    let sent_at = match job.sent_at.as_deref() {
+        Some(sent_at) => parse_time(sent_at)?,
+        None => now,
+    };
+    let in_flight = now - sent_at;
+    let timed_out = in_flight >= Duration::seconds(settings.in_flight_timeout_secs);
+    let next_check_at = now + Duration::seconds(settings.poll_interval_secs as i64);
+
+    match app_state
+        .lightning_service
+        .get_payment_status(payment_id)
+        .await
+    {
+        Ok(Some(payment)) => match payment.status {
+            PaymentStatus::Completed => {
+                mark_payout_paid(
+                    app_state,
+                    job,
+                    payment_id,
+                    payment.fee_sats(),
+                    payment.preimage().as_deref(),
+                )
+                .await?;
+                info!(
+                    "{} {} paid with payment {}",
+                    job.kind, job.target_id, payment_id
+                );
+                Ok(())
+            }
+            PaymentStatus::Failed => {
+                let error = match payment.error_message() {
+                    Some(reason) => format!("Payment {} failed: {}", payment_id, reason),
+                    None => format!("Payment {} failed", payment_id),
+                };
+                failed_attempt(app_state, job, &error).await
+            }
+            _ if timed_out => {
+                warn!(
+                    "{} {} payment {} still in flight after {}",
+                    job.kind, job.target_id, payment_id, in_flight
+                );
+                store
+                    .stop(job, PayoutStatus::NeedsAttention, "Payment still in flight")
+                    .await
+            }
+            _ => store.schedule_check(job.id, next_check_at, None).await,
+        },
+        // The backend never took the payment, so it is safe to send it again
+        Ok(None) if in_flight >= Duration::seconds(settings.retry_base_secs) => {
+            failed_attempt(
+                app_state,
+                job,
+                &format!("Payment {} was never created", payment_id),
+            )
+            .await
+        }
+        Ok(None) => store.schedule_check(job.id, next_check_at, None).await,
+        Err(e) if timed_out => {
+            warn!(
+                "Could not check {} {} payment {}: {}",
+                job.kind, job.target_id, payment_id, e
+            );
+            store
+                .stop(job, PayoutStatus::NeedsAttention, &e.to_string())
+                .await
+        }
+        Err(e) => {
+            store
+                .schedule_check(job.id, next_check_at, Some(&e.to_string()))
+                .await
+        }
+    }
+}
+
+// Settle the prize, refund or withdrawal behind a job, returns false if it was not being paid. A prize
+// keeps the payment's preimage so anyone can check it was paid
+pub async fn mark_payout_paid(
+    app_state: &AppState,
+    job: &PayoutJob,
+    payment_id: &str,
+    fee_sats: i64,
+    preimage: Option<&str>,
+) -> Result<bool, Error> {
+    let paid = match job.kind {
+        PayoutKind::Prize => app_state
+            .payment_store
+            .mark_prize_paid(job.target_id, payment_id, fee_sats, preimage)
+            .await?
+            .is_some(),
+        PayoutKind::Refund => app_state
+            .payout_store
+            .mark_refund_paid(job.target_id, payment_id, fee_sats)
+            .await?
+            .is_some(),
+        PayoutKind::Withdrawal => app_state
+            .payment_store
+            .complete_withdrawal(job.target_id, payment_id, fee_sats)
+            .await?
+            .is_some(),
+    };
+
+    Ok(paid)
+}
+
+// Back off and try again, or give up so the player can claim with a new invoice. A withdrawal
+// given up on goes back into the player's balance
+async fn failed_attempt(app_state: &AppState, job: &PayoutJob, error: &str) -> Result<(), Error> {
+    let settings = &app_state.payout_settings;
+
+    if job.attempts >= settings.max_attempts {
+        warn!(
+            "Giving up on {} {} after {} attempts: {}",
+            job.kind, job.target_id, job.attempts, error
+        );
+        return app_state
+            .payout_store
+            .stop(job, PayoutStatus::Failed, error)
+            .await;
+    }
+
+    let delay = settings.retry_delay_secs(job.attempts);
+    info!(
+        "Retrying {} {} in {}s: {}",
+        job.kind, job.target_id, delay, error
+    );
+    app_state
+        .payout_store
+        .retry(
+            job,
+            OffsetDateTime::now_utc() + Duration::seconds(delay),
+            error,
+        )
+        .await
+}
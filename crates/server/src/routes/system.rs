@@ -1,11 +1,21 @@
-use axum::{extract::State, response::ErrorResponse};
+use axum::{extract::State, response::ErrorResponse, Json};
 use hyper::StatusCode;
-use log::{debug, error};
+use log::{debug, error, warn};
+use serde::Serialize;
 use std::sync::Arc;
 
-use crate::{domain::map_error, AppState};
+use crate::{domain::map_error, AppState, WorkerState, WorkerStatus};
 
-pub async fn health_check(State(state): State<Arc<AppState>>) -> Result<StatusCode, ErrorResponse> {
+#[derive(Debug, Serialize)]
+pub struct HealthResponse {
+    /// "ok", or "degraded" with a 503 while a background worker is not running
+    pub status: &'static str,
+    pub workers: Vec<WorkerStatus>,
+}
+
+pub async fn health_check(
+    State(state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<HealthResponse>), ErrorResponse> {
     state.user_store.ping().await.map_err(|e| {
         error!("{}", e);
         map_error(e)
@@ -16,6 +26,23 @@ pub async fn health_check(State(state): State<Arc<AppState>>) -> Result<StatusCo
         map_error(e)
     })?;
 
-    debug!("service and db are up");
-    Ok(StatusCode::OK)
+    state.competition_store.ping().await.map_err(|e| {
+        error!("{}", e);
+        map_error(e)
+    })?;
+
+    let workers = state.supervisor.statuses();
+    // Unavailable while a worker is down, so load balancers and orchestrators notice
+    let (code, status) = if workers
+        .iter()
+        .all(|worker| worker.state == WorkerState::Running)
+    {
+        debug!("service and db are up");
+        (StatusCode::OK, "ok")
+    } else {
+        warn!("Background workers not all running: {:?}", workers);
+        (StatusCode::SERVICE_UNAVAILABLE, "degraded")
+    };
+
+    Ok((code, Json(HealthResponse { status, workers })))
 }
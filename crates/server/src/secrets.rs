@@ -50,7 +50,7 @@ fn is_pem_file(file_path: &str) -> bool {
     Path::new(file_path)
         .extension()
         .and_then(|s| s.to_str())
-        .map_or(false, |ext| ext == "pem")
+        .is_some_and(|ext| ext == "pem")
 }
 
 fn read_key<T: SecretKeyHandler>(file_path: &str) -> Result<T, anyhow::Error> {
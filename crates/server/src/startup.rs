@@ -5,7 +5,7 @@ use axum::{
     http::Extensions,
     middleware::{self, AddExtension, Next},
     response::IntoResponse,
-    routing::{get, post},
+    routing::{delete, get, post},
     serve::Serve,
     Router,
 };
@@ -14,35 +14,50 @@ use hyper::{
     Method,
 };
 use log::{error, info, warn};
+use nostr_sdk::{secp256k1::SecretKey, Keys};
 use reqwest_middleware::{
     reqwest::{self, Client, Response},
     ClientBuilder, ClientWithMiddleware, Middleware,
 };
 use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
 use std::sync::Arc;
-use std::{net::SocketAddr, str::FromStr};
+use std::{net::SocketAddr, str::FromStr, time::Duration};
+use tokio::signal::unix::{signal, SignalKind};
 use tokio::{net::TcpListener, select};
-use tokio::{
-    signal::unix::{signal, SignalKind},
-    spawn,
-};
 use tower_http::{
     cors::{Any, CorsLayer},
     services::{ServeDir, ServeFile},
 };
 
 use crate::{
-    check_payment_status, check_prize_eligibility, claim_prize, config::Settings,
-    file_utils::create_folder, get_game_config, get_top_scores, get_user_scores, health_check,
-    index_handler, login, register, run_daily_tasks, start_new_session, submit_score, GameStore,
-    LightningService, PaymentStore, UserStore,
+    admin_ban, admin_get_payments, admin_get_payouts, admin_get_rulesets,
+    admin_get_session_configs, admin_get_settlements, admin_get_suspicious_scores, admin_get_user,
+    admin_get_users, admin_get_wallet, admin_mark_payout_paid, admin_resettle_competition,
+    admin_retry_payout, admin_settle, admin_unban, admin_void_competition, admin_void_score,
+    check_payment_status, check_prize_eligibility, claim_prize, claim_refund, config::Settings,
+    delete_account, export_user_data, file_utils::create_folder, get_competition_leaderboard,
+    get_credit_balance, get_current_competitions, get_daily_competition, get_game_config, get_key,
+    get_leaderboard, get_live_competition, get_live_competition_events, get_my_stats,
+    get_pending_prizes, get_practice_config, get_prize_status, get_refunds, get_server_pubkey,
+    get_session_configs, get_top_scores, get_user_scores, get_user_stats, get_wallet, health_check,
+    index_handler, login, register, run_daily_tasks, run_payment_sweeper, run_payout_queue,
+    run_reconciliation, run_session_expiry, start_new_session, start_practice_session,
+    submit_practice_score, submit_score, withdraw, AdminSettings, CompetitionStore, GameSettings,
+    GameStore, LeaderboardStore, LedgerStore, LightningService, LiveBoard, PaymentStore,
+    PayoutSettings, PayoutStore, Rulesets, SettlementStore, StatsStore, Supervisor, UserStore,
+    WalletSettings,
 };
+
+// How long background workers get to finish what they are doing once the server has stopped
+const SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+
 pub struct Application {
     server: Serve<
         TcpListener,
         IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
         AddExtension<Router, ConnectInfo<SocketAddr>>,
     >,
+    supervisor: Supervisor,
 }
 
 impl Application {
@@ -53,13 +68,27 @@ impl Application {
         );
         let listener = SocketAddr::from_str(&address)?;
         let (app_state, serve_dir) = build_app(config).await?;
+        let supervisor = app_state.supervisor.clone();
         let server = build_server(listener, app_state, serve_dir).await?;
-        Ok(Self { server })
+        Ok(Self { server, supervisor })
     }
 
     pub async fn run_until_stopped(self) -> Result<(), anyhow::Error> {
         info!("Starting server...");
-        match self.server.with_graceful_shutdown(shutdown_signal()).await {
+        // Workers and event streams stop along with the server, or it would wait on them forever
+        let supervisor = self.supervisor.clone();
+        let shutdown = async move {
+            shutdown_signal().await;
+            supervisor.cancel();
+        };
+        let result = self.server.with_graceful_shutdown(shutdown).await;
+
+        info!("Waiting for background workers to finish");
+        self.supervisor
+            .shutdown(Duration::from_secs(SHUTDOWN_TIMEOUT_SECS))
+            .await;
+
+        match result {
             Ok(_) => {
                 info!("Shutdown complete");
                 Ok(())
@@ -78,8 +107,20 @@ pub struct AppState {
     pub remote_url: String,
     pub user_store: UserStore,
     pub game_store: GameStore,
+    pub competition_store: CompetitionStore,
     pub payment_store: PaymentStore,
+    pub ledger_store: LedgerStore,
+    pub leaderboard_store: LeaderboardStore,
+    pub stats_store: StatsStore,
+    pub settlement_store: SettlementStore,
+    pub live_board: LiveBoard,
+    pub supervisor: Supervisor,
+    pub payout_store: PayoutStore,
     pub lightning_service: LightningService,
+    pub wallet_settings: WalletSettings,
+    pub payout_settings: PayoutSettings,
+    pub admin_settings: AdminSettings,
+    pub game_settings: GameSettings,
 }
 
 pub async fn build_app(config: Settings) -> Result<(AppState, ServeDir<ServeFile>), anyhow::Error> {
@@ -95,6 +136,12 @@ pub async fn build_app(config: Settings) -> Result<(AppState, ServeDir<ServeFile
         )));
     info!("Public UI configured");
 
+    config.competition_settings.validate()?;
+    config.wallet_settings.validate()?;
+    config.payout_settings.validate()?;
+    config.admin_settings.validate()?;
+    config.game_settings.validate()?;
+
     create_folder(&config.db_settings.data_folder.clone());
 
     let db_path = format!("{}/game.db", config.db_settings.data_folder);
@@ -127,13 +174,51 @@ pub async fn build_app(config: Settings) -> Result<(AppState, ServeDir<ServeFile
         config.api_settings.voltage_wallet_id.clone(),
     );
 
+    // Generated on first start when the file does not exist yet
+    if let Some(creds_folder) = std::path::Path::new(&config.api_settings.private_key_file)
+        .parent()
+        .and_then(|folder| folder.to_str())
+        .filter(|folder| !folder.is_empty())
+    {
+        create_folder(creds_folder);
+    }
+    let secret_key: SecretKey = get_key(&config.api_settings.private_key_file)?;
+    let keys = Keys::new(secret_key.into());
+    info!("Signing game configs as {}", keys.public_key());
+
+    let rulesets = Rulesets::load(
+        config.game_settings.rulesets_dir.as_deref(),
+        &config.game_settings.default_ruleset,
+    )?;
+    let competition_store = CompetitionStore::new(
+        db_pool.clone(),
+        config.competition_settings,
+        rulesets.clone(),
+    );
+    for series in competition_store.series() {
+        rulesets.latest(&series.ruleset)?;
+    }
+    info!("Loaded {} rulesets", rulesets.all().len());
+
     let app_state = AppState {
         ui_dir: config.ui_settings.ui_dir,
         remote_url: config.ui_settings.remote_url,
         user_store: UserStore::new(db_pool.clone()),
-        game_store: GameStore::new(db_pool.clone()),
+        game_store: GameStore::new(db_pool.clone(), rulesets, keys),
+        live_board: LiveBoard::new(competition_store.clone()),
+        supervisor: Supervisor::new(),
+        competition_store,
         payment_store: PaymentStore::new(db_pool.clone()),
+        ledger_store: LedgerStore::new(db_pool.clone()),
+        leaderboard_store: LeaderboardStore::new(db_pool.clone()),
+        stats_store: StatsStore::new(db_pool.clone()),
+        settlement_store: SettlementStore::new(db_pool.clone()),
+        payout_store: PayoutStore::new(db_pool.clone()),
         lightning_service,
+        wallet_settings: config.wallet_settings,
+        payout_settings: config.payout_settings,
+        admin_settings: config.admin_settings,
+        game_settings: config.game_settings,
     };
     Ok((app_state, serve_dir))
 }
@@ -155,9 +240,29 @@ pub async fn build_server(
     info!("Setting up service");
     let app = app(app_state.clone(), serve_dir);
 
-    // Spawn the daily tasks in the background
-    // TODO have this close down with the server gracefully
-    spawn(run_daily_tasks(Arc::new(app_state)));
+    // Run every background worker on its own until the server shuts down, so one that is stuck
+    // or crashing leaves the others alone and shows up in the health check
+    let app_state = Arc::new(app_state);
+    let supervisor = app_state.supervisor.clone();
+    let state = app_state.clone();
+    supervisor.spawn("daily_tasks", move |shutdown| {
+        run_daily_tasks(state.clone(), shutdown)
+    });
+    let state = app_state.clone();
+    supervisor.spawn("payment_sweeper", move |shutdown| {
+        run_payment_sweeper(state.clone(), shutdown)
+    });
+    let state = app_state.clone();
+    supervisor.spawn("session_expiry", move |shutdown| {
+        run_session_expiry(state.clone(), shutdown)
+    });
+    let state = app_state.clone();
+    supervisor.spawn("reconciliation", move |shutdown| {
+        run_reconciliation(state.clone(), shutdown)
+    });
+    supervisor.spawn("payout_queue", move |shutdown| {
+        run_payout_queue(app_state.clone(), shutdown)
+    });
 
     let server = axum::serve(
         listener,
@@ -173,26 +278,91 @@ pub async fn build_server(
 
 pub fn app(app_state: AppState, serve_dir: ServeDir<ServeFile>) -> Router {
     let cors = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+        .allow_methods([Method::GET, Method::POST, Method::DELETE, Method::OPTIONS])
         .allow_headers([ACCEPT, CONTENT_TYPE, AUTHORIZATION])
         .allow_origin(Any);
 
     let users_endpoints = Router::new()
         .route("/login", post(login))
-        .route("/register", post(register));
+        .route("/register", post(register))
+        .route("/me", delete(delete_account))
+        .route("/me/export", get(export_user_data))
+        .route("/me/stats", get(get_my_stats))
+        .route("/{pubkey}/stats", get(get_user_stats));
 
     let game_endpoints = Router::new()
         .route("/config", get(get_game_config))
+        .route("/pubkey", get(get_server_pubkey))
         .route("/session", post(start_new_session))
+        .route("/session/{session_id}/configs", get(get_session_configs))
         .route("/score", post(submit_score))
+        .route("/practice", post(start_practice_session))
+        .route("/practice/config", get(get_practice_config))
+        .route("/practice/score", post(submit_practice_score))
         .route("/scores/top", get(get_top_scores))
         .route("/scores/user", get(get_user_scores));
 
-    let payment_endpoints = Router::new().route("/status/{payment_id}", get(check_payment_status));
+    let payment_endpoints = Router::new()
+        .route("/status/{payment_id}", get(check_payment_status))
+        .route("/credits", get(get_credit_balance));
 
     let prize_endpoints = Router::new()
         .route("/check", get(check_prize_eligibility))
-        .route("/claim", post(claim_prize));
+        .route("/claim", post(claim_prize))
+        .route("/pending", get(get_pending_prizes))
+        .route("/{prize_id}", get(get_prize_status));
+
+    let refund_endpoints = Router::new()
+        .route("/", get(get_refunds))
+        .route("/{refund_id}/claim", post(claim_refund));
+
+    let wallet_endpoints = Router::new()
+        .route("/", get(get_wallet))
+        .route("/withdraw", post(withdraw));
+
+    let competition_endpoints = Router::new()
+        .route("/", get(get_current_competitions))
+        .route("/live", get(get_live_competition))
+        .route("/live/events", get(get_live_competition_events))
+        .route("/{date}", get(get_daily_competition))
+        .route(
+            "/{competition_id}/leaderboard",
+            get(get_competition_leaderboard),
+        );
+
+    let admin_endpoints = Router::new()
+        .route("/users", get(admin_get_users))
+        .route("/users/{user_id}", get(admin_get_user))
+        .route("/payments", get(admin_get_payments))
+        .route("/payouts", get(admin_get_payouts))
+        .route(
+            "/payouts/{kind}/{target_id}/paid",
+            post(admin_mark_payout_paid),
+        )
+        .route(
+            "/payouts/{kind}/{target_id}/retry",
+            post(admin_retry_payout),
+        )
+        .route("/scores/suspicious", get(admin_get_suspicious_scores))
+        .route(
+            "/sessions/{session_id}/configs",
+            get(admin_get_session_configs),
+        )
+        .route("/scores/{score_id}/void", post(admin_void_score))
+        .route("/bans", post(admin_ban))
+        .route("/bans/{pubkey}", delete(admin_unban))
+        .route("/settlements", get(admin_get_settlements))
+        .route("/settlements/{date}", post(admin_settle))
+        .route(
+            "/competitions/{competition_id}/void",
+            post(admin_void_competition),
+        )
+        .route(
+            "/competitions/{competition_id}/resettle",
+            post(admin_resettle_competition),
+        )
+        .route("/wallet", get(admin_get_wallet))
+        .route("/rulesets", get(admin_get_rulesets));
 
     Router::new()
         .route("/", get(index_handler))
@@ -201,8 +371,13 @@ pub fn app(app_state: AppState, serve_dir: ServeDir<ServeFile>) -> Router {
         .route("/api/v1/health_check", get(health_check))
         .nest("/api/v1/users", users_endpoints)
         .nest("/api/v1/game", game_endpoints)
+        .route("/api/v1/leaderboard", get(get_leaderboard))
         .nest("/api/v1/payments", payment_endpoints)
         .nest("/api/v1/prizes", prize_endpoints)
+        .nest("/api/v1/competitions", competition_endpoints)
+        .nest("/api/v1/refunds", refund_endpoints)
+        .nest("/api/v1/wallet", wallet_endpoints)
+        .nest("/api/v1/admin", admin_endpoints)
         .layer(middleware::from_fn(log_request))
         .with_state(Arc::new(app_state))
         .nest_service("/ui", serve_dir.clone())
@@ -0,0 +1,210 @@
+use log::{error, info, warn};
+use serde::Serialize;
+use std::{
+    future::Future,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use time::OffsetDateTime;
+use tokio::{select, spawn, time as tokio_time};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+
+// Longest wait before starting a crashed worker again
+const MAX_RESTART_DELAY_SECS: u64 = 60;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkerState {
+    Running,
+    /// Crashed and waiting to be started again
+    Restarting,
+    /// Stopped for shutdown
+    Stopped,
+}
+
+/// How a background worker is doing, as shown by the health check
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkerStatus {
+    pub name: String,
+    pub state: WorkerState,
+    pub restarts: u32,
+    pub last_error: Option<String>,
+    pub started_at: String,
+}
+
+// Owns the background workers: restarts any that crash or stop by themselves, and on shutdown
+// tells them all to stop and waits for the work they have in hand
+#[derive(Clone, Debug, Default)]
+pub struct Supervisor {
+    shutdown: CancellationToken,
+    tracker: TaskTracker,
+    workers: Arc<RwLock<Vec<WorkerStatus>>>,
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Cancelled once the server starts shutting down, long running work should stop on it
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    // Run a worker until shutdown. The worker is handed the shutdown token and should return
+    // once it is cancelled, if it returns or panics before that it is started again
+    pub fn spawn<F, Fut>(&self, name: &str, worker: F)
+    where
+        F: Fn(CancellationToken) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let supervisor = self.clone();
+        let name = name.to_string();
+        self.workers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(WorkerStatus {
+                name: name.clone(),
+                state: WorkerState::Running,
+                restarts: 0,
+                last_error: None,
+                started_at: OffsetDateTime::now_utc().to_string(),
+            });
+
+        self.tracker.spawn(async move {
+            let mut restarts = 0;
+            loop {
+                // A task of its own, so a panic in the worker is caught here
+                let result = spawn(worker(supervisor.shutdown.clone())).await;
+                if supervisor.shutdown.is_cancelled() {
+                    info!("{} stopped", name);
+                    supervisor.update(&name, |status| status.state = WorkerState::Stopped);
+                    return;
+                }
+
+                let error = match result {
+                    Ok(()) => String::from("Stopped before shutdown"),
+                    Err(e) => e.to_string(),
+                };
+                restarts += 1;
+                let delay = restart_delay(restarts);
+                error!(
+                    "{} crashed: {}, restarting in {}s",
+                    name,
+                    error,
+                    delay.as_secs()
+                );
+                supervisor.update(&name, |status| {
+                    status.state = WorkerState::Restarting;
+                    status.restarts = restarts;
+                    status.last_error = Some(error.clone());
+                });
+
+                select! {
+                    _ = supervisor.shutdown.cancelled() => {
+                        supervisor.update(&name, |status| status.state = WorkerState::Stopped);
+                        return;
+                    }
+                    _ = tokio_time::sleep(delay) => {}
+                }
+
+                info!("Restarting {}", name);
+                supervisor.update(&name, |status| {
+                    status.state = WorkerState::Running;
+                    status.started_at = OffsetDateTime::now_utc().to_string();
+                });
+            }
+        });
+    }
+
+    // Tell every worker to stop, without waiting for them
+    pub fn cancel(&self) {
+        self.shutdown.cancel();
+    }
+
+    // Stop every worker and wait up to `timeout` for them to finish what they are doing.
+    // Returns false if some were still busy when it ran out
+    pub async fn shutdown(&self, timeout: Duration) -> bool {
+        self.shutdown.cancel();
+        self.tracker.close();
+
+        match tokio_time::timeout(timeout, self.tracker.wait()).await {
+            Ok(()) => true,
+            Err(_) => {
+                warn!(
+                    "Background workers still running after {}s, stopping anyway",
+                    timeout.as_secs()
+                );
+                false
+            }
+        }
+    }
+
+    pub fn statuses(&self) -> Vec<WorkerStatus> {
+        self.workers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn update(&self, name: &str, change: impl FnOnce(&mut WorkerStatus)) {
+        let mut workers = self.workers.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(status) = workers.iter_mut().find(|status| status.name == name) {
+            change(status);
+        }
+    }
+}
+
+// Doubles with each restart, up to a minute
+fn restart_delay(restarts: u32) -> Duration {
+    let secs = 1u64 << restarts.saturating_sub(1).min(6);
+    Duration::from_secs(secs.min(MAX_RESTART_DELAY_SECS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn test_crashed_worker_is_restarted_and_stopped_on_shutdown() {
+        let supervisor = Supervisor::new();
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let worker_runs = runs.clone();
+        supervisor.spawn("worker", move |shutdown| {
+            let runs = worker_runs.clone();
+            async move {
+                if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+                    panic!("first run fails");
+                }
+                shutdown.cancelled().await;
+            }
+        });
+
+        for _ in 0..30 {
+            if runs.load(Ordering::SeqCst) >= 2 {
+                break;
+            }
+            tokio_time::sleep(Duration::from_millis(100)).await;
+        }
+        let status = &supervisor.statuses()[0];
+        assert_eq!(status.state, WorkerState::Running);
+        assert_eq!(status.restarts, 1);
+        assert!(status.last_error.is_some());
+
+        assert!(supervisor.shutdown(Duration::from_secs(1)).await);
+        assert_eq!(supervisor.statuses()[0].state, WorkerState::Stopped);
+    }
+
+    #[test]
+    fn test_restart_delay_backs_off() {
+        assert_eq!(restart_delay(1), Duration::from_secs(1));
+        assert_eq!(restart_delay(3), Duration::from_secs(4));
+        assert_eq!(
+            restart_delay(20),
+            Duration::from_secs(MAX_RESTART_DELAY_SECS)
+        );
+    }
+}